@@ -0,0 +1,60 @@
+//! Replays a 16kHz mono WAV file to the live caption WebSocket at real-time
+//! speed and prints the caption messages it receives.
+//!
+//! ```bash
+//! cargo run --release -- serve --model tiny &
+//! cargo run --example caption_client -- ws://127.0.0.1:8080/stream test_files/test_speech.wav
+//! ```
+
+use anyhow::{bail, Context, Result};
+use futures_util::{SinkExt, StreamExt};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+/// Samples per frame: 100ms at 16kHz
+const FRAME_SAMPLES: usize = 1600;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let url = args.next().unwrap_or_else(|| "ws://127.0.0.1:8080/stream".to_string());
+    let wav_path = args.next().unwrap_or_else(|| "test_files/test_speech.wav".to_string());
+
+    let mut reader = hound::WavReader::open(&wav_path).context("Failed to open WAV file")?;
+    let spec = reader.spec();
+    if spec.sample_rate != 16000 || spec.channels != 1 || spec.bits_per_sample != 16 {
+        bail!("Expected 16kHz mono 16-bit WAV, got {:?}", spec);
+    }
+    let samples: Vec<i16> = reader.samples::<i16>().collect::<Result<_, _>>()?;
+
+    let (socket, _) = tokio_tungstenite::connect_async(url.as_str())
+        .await
+        .with_context(|| format!("Failed to connect to {}", url))?;
+    let (mut sender, mut receiver) = socket.split();
+
+    let replay = async move {
+        let mut ticker = tokio::time::interval(Duration::from_millis(100));
+        for frame in samples.chunks(FRAME_SAMPLES) {
+            ticker.tick().await;
+            let bytes: Vec<u8> = frame.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+            sender.send(Message::Binary(bytes.into())).await?;
+        }
+        sender.send(Message::Text(r#"{"type":"end"}"#.into())).await?;
+        anyhow::Ok(())
+    };
+
+    let print = async move {
+        while let Some(message) = receiver.next().await {
+            match message? {
+                Message::Text(text) => println!("{}", text),
+                Message::Close(_) => break,
+                _ => {}
+            }
+        }
+        anyhow::Ok(())
+    };
+
+    let (replayed, printed) = tokio::join!(replay, print);
+    replayed?;
+    printed
+}
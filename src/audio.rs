@@ -1,73 +1,176 @@
-use anyhow::{Context, Result};
+use std::io::ErrorKind;
 use std::path::{Path, PathBuf};
 use tokio::process::Command;
 use tracing::{info, debug, warn};
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
+use std::time::Duration;
+use crate::error::{Result, TranscribeError};
 
 /// Supported video/audio formats for input
-const SUPPORTED_FORMATS: &[&str] = &[
+pub(crate) const SUPPORTED_FORMATS: &[&str] = &[
     "mp4", "avi", "mov", "mkv", "wmv", "flv", "webm", "ogv", "3gp", "m4v",
     "vob", "ts", "mpg", "mpeg", "mp3", "wav", "flac", "aac", "ogg", "m4a"
 ];
 
-/// Extracts audio from a video file and returns the path to the extracted audio file
+/// URL schemes passed straight to FFmpeg instead of being treated as local paths
+const URL_SCHEMES: &[&str] = &["http://", "https://", "rtmp://", "rtmps://"];
+
+/// Connection settings for URL inputs (HTTP(S) files, HLS `.m3u8` playlists, RTMP streams)
+#[derive(Debug, Clone)]
+pub struct NetworkOptions {
+    /// Reconnect after dropped HTTP(S) connections instead of failing
+    pub reconnect: bool,
+    /// Longest back-off between reconnect attempts
+    pub reconnect_delay_max: Duration,
+    /// Give up when a read or connect stalls for this long
+    pub timeout: Option<Duration>,
+    /// Stop after this much audio, e.g. to sample a live stream
+    pub max_duration: Option<Duration>,
+}
+
+impl Default for NetworkOptions {
+    fn default() -> Self {
+        Self {
+            reconnect: true,
+            reconnect_delay_max: Duration::from_secs(5),
+            timeout: Some(Duration::from_secs(30)),
+            max_duration: None,
+        }
+    }
+}
+
+/// Whether an input should be opened by FFmpeg as a network URL
+pub fn is_url(input: &str) -> bool {
+    let lower = input.to_ascii_lowercase();
+    URL_SCHEMES.iter().any(|scheme| lower.starts_with(scheme))
+}
+
+/// Extracts audio from a video file and returns the path to the extracted audio file.
+/// URLs are also accepted and opened with the default [`NetworkOptions`].
 pub async fn extract_audio(video_path: &Path) -> Result<PathBuf> {
+    if let Some(url) = video_path.to_str().filter(|path| is_url(path)) {
+        return extract_audio_from_url(url, &NetworkOptions::default()).await;
+    }
+
     // Validate input file exists
     if !video_path.exists() {
-        anyhow::bail!("Input file does not exist: {:?}", video_path);
+        return Err(TranscribeError::MissingInput(video_path.to_path_buf()));
     }
 
     // Check if format is supported (optional warning, FFmpeg will try anyway)
-    if let Some(extension) = video_path.extension().and_then(|e| e.to_str()) {
-        if !SUPPORTED_FORMATS.contains(&extension.to_lowercase().as_str()) {
-            warn!("File extension '{}' is not in common supported formats list, but FFmpeg will attempt to process it", extension);
-        }
+    if let Some(extension) = video_path.extension().and_then(|e| e.to_str())
+        && !SUPPORTED_FORMATS.contains(&extension.to_lowercase().as_str())
+    {
+        warn!("File extension '{}' is not in common supported formats list, but FFmpeg will attempt to process it", extension);
     }
 
     let output_path = create_temp_audio_path(video_path);
-    
     debug!("Extracting audio from {:?} to {:?}", video_path, output_path);
-    
-    // Use ffmpeg to extract audio from video
-    let output = Command::new("ffmpeg")
-        .arg("-i")
-        .arg(video_path)
-        .arg("-vn") // No video
-        .arg("-acodec")
-        .arg("pcm_s16le") // 16-bit PCM (compatible with Whisper)
-        .arg("-ar")
-        .arg("16000") // 16kHz sample rate (optimal for Whisper)
-        .arg("-ac")
-        .arg("1") // Mono (Whisper works best with mono)
-        .arg("-y") // Overwrite output file if it exists
-        .arg("-hide_banner") // Reduce FFmpeg output verbosity
-        .arg("-loglevel")
-        .arg("error") // Only show errors
-        .arg(&output_path)
-        .output()
-        .await
-        .context("Failed to execute ffmpeg command")?;
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        anyhow::bail!("FFmpeg failed to extract audio: {}", stderr);
+    run_extraction(vec![OsString::from("-i"), video_path.into()], &output_path).await
+}
+
+/// Downloads or records audio from an HTTP(S), HLS or RTMP URL
+pub async fn extract_audio_from_url(url: &str, options: &NetworkOptions) -> Result<PathBuf> {
+    if !is_url(url) {
+        return Err(TranscribeError::UnsupportedFormat(format!(
+            "'{}' is not an http(s) or rtmp(s) URL",
+            url
+        )));
+    }
+
+    // Name the temporary file after the last path segment, without the query string
+    let name = url.split(['?', '#']).next().unwrap_or(url).rsplit('/').next().unwrap_or_default();
+    let output_path = create_temp_audio_path(Path::new(if name.is_empty() { "stream" } else { name }));
+    debug!("Extracting audio from {} to {:?}", url, output_path);
+
+    let args = url_input_args(url, options).into_iter().map(OsString::from).collect();
+    run_extraction(args, &output_path).await
+}
+
+/// FFmpeg input options and `-i` for a URL
+fn url_input_args(url: &str, options: &NetworkOptions) -> Vec<String> {
+    let mut args = Vec::new();
+    let is_http = url.to_ascii_lowercase().starts_with("http");
+
+    if options.reconnect && is_http {
+        args.extend(
+            ["-reconnect", "1", "-reconnect_streamed", "1", "-reconnect_on_network_error", "1"].map(String::from),
+        );
+        args.push("-reconnect_delay_max".to_string());
+        args.push(options.reconnect_delay_max.as_secs().max(1).to_string());
+    }
+    if let Some(timeout) = options.timeout {
+        // Microseconds; applies to connecting and to every read
+        args.push("-rw_timeout".to_string());
+        args.push(timeout.as_micros().to_string());
+    }
+    if let Some(duration) = options.max_duration {
+        args.push("-t".to_string());
+        args.push(format!("{:.3}", duration.as_secs_f64()));
     }
 
+    args.push("-i".to_string());
+    args.push(url.to_string());
+    args
+}
+
+/// Runs FFmpeg with the given input arguments, writing 16kHz mono PCM to `output_path`
+async fn run_extraction(input_args: Vec<OsString>, output_path: &Path) -> Result<PathBuf> {
+    // Use ffmpeg to extract audio from video
+    let mut args = input_args;
+    args.extend(
+        [
+            "-vn", // No video
+            "-acodec", "pcm_s16le", // 16-bit PCM (compatible with Whisper)
+            "-ar", "16000", // 16kHz sample rate (optimal for Whisper)
+            "-ac", "1", // Mono (Whisper works best with mono)
+        ]
+        .map(OsString::from),
+    );
+    args.push(output_path.into());
+    run_ffmpeg(args).await?;
+
     // Verify output file was created and has content
     if !output_path.exists() {
-        anyhow::bail!("Audio extraction failed: output file was not created");
+        return Err(TranscribeError::FfmpegFailed {
+            stderr: "output file was not created".to_string(),
+        });
     }
 
-    let metadata = tokio::fs::metadata(&output_path)
-        .await
-        .context("Failed to read audio file metadata")?;
+    let metadata = tokio::fs::metadata(output_path).await?;
     
     if metadata.len() == 0 {
-        anyhow::bail!("Audio extraction failed: output file is empty");
+        return Err(TranscribeError::FfmpegFailed {
+            stderr: "output file is empty".to_string(),
+        });
     }
 
     info!("✅ Audio extraction completed successfully ({} bytes)", metadata.len());
-    Ok(output_path)
+    Ok(output_path.to_path_buf())
+}
+
+/// Runs FFmpeg quietly, overwriting the output. A missing binary and a failed
+/// run are reported as distinct errors.
+pub(crate) async fn run_ffmpeg(args: Vec<OsString>) -> Result<()> {
+    let output = Command::new("ffmpeg")
+        .arg("-y") // Overwrite output file if it exists
+        .arg("-hide_banner") // Reduce FFmpeg output verbosity
+        .arg("-loglevel")
+        .arg("error") // Only show errors
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| match e.kind() {
+            ErrorKind::NotFound => TranscribeError::FfmpegNotInstalled,
+            _ => TranscribeError::Io(e),
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(TranscribeError::FfmpegFailed { stderr });
+    }
+    Ok(())
 }
 
 /// Creates a temporary path for the extracted audio file
@@ -95,4 +198,32 @@ mod tests {
         assert!(audio_path.file_name().unwrap().to_str().unwrap().starts_with("wayne_dyer_video_"));
         assert!(audio_path.extension().unwrap() == "wav");
     }
+
+    #[test]
+    fn test_is_url() {
+        assert!(is_url("https://example.com/talk.mp4"));
+        assert!(is_url("HTTP://example.com/live/index.m3u8"));
+        assert!(is_url("rtmp://live.example.com/app/key"));
+        assert!(!is_url("/videos/talk.mp4"));
+        assert!(!is_url("file:///videos/talk.mp4"));
+    }
+
+    #[test]
+    fn test_url_input_args() {
+        let options = NetworkOptions {
+            timeout: Some(Duration::from_secs(10)),
+            max_duration: Some(Duration::from_secs(90)),
+            ..Default::default()
+        };
+
+        let args = url_input_args("https://example.com/live/index.m3u8", &options);
+        assert_eq!(&args[..2], ["-reconnect", "1"]);
+        assert!(args.windows(2).any(|pair| pair == ["-rw_timeout", "10000000"]));
+        assert!(args.windows(2).any(|pair| pair == ["-t", "90.000"]));
+        assert_eq!(&args[args.len() - 2..], ["-i", "https://example.com/live/index.m3u8"]);
+
+        // Reconnect options only exist for the HTTP protocol
+        let args = url_input_args("rtmp://live.example.com/app/key", &options);
+        assert!(!args.contains(&"-reconnect".to_string()));
+    }
 }
@@ -0,0 +1,421 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::Path;
+use tracing::{info, warn};
+use crate::audio;
+use crate::error::Result;
+use crate::output::format_vtt_timestamp;
+use crate::text;
+use crate::transcription::{TranscriptionResult, TranscriptionSegment};
+
+/// Longest chapter title taken from the opening sentence (characters)
+const MAX_TITLE_CHARS: usize = 60;
+
+/// Settings for [`detect_chapters`]
+#[derive(Debug, Clone)]
+pub struct ChapterOptions {
+    /// Chapters shorter than this are merged into their neighbours (seconds)
+    pub min_chapter: f64,
+    /// Segments compared on either side of a candidate boundary
+    pub block_size: usize,
+    /// A pause this long counts as a full topic-shift signal (seconds)
+    pub long_pause: f64,
+}
+
+impl Default for ChapterOptions {
+    fn default() -> Self {
+        Self {
+            min_chapter: 60.0,
+            block_size: 4,
+            long_pause: 3.0,
+        }
+    }
+}
+
+/// A run of segments about one topic
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Chapter {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub title: String,
+}
+
+/// Splits a transcript into chapters at topic shifts.
+///
+/// Every gap between two segments is scored TextTiling-style: the lexical
+/// cohesion of the `block_size` segments on each side is compared, and the
+/// depth of the cohesion valley at the gap is combined with the length of
+/// the pause. Gaps scoring above `mean - stddev / 2` become boundaries,
+/// strongest first, as long as every chapter stays at least `min_chapter` long.
+pub fn detect_chapters(transcription: &TranscriptionResult, options: &ChapterOptions) -> Vec<Chapter> {
+    let segments = &transcription.segments;
+    let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+        return Vec::new();
+    };
+
+    let scores = boundary_scores(segments, options);
+    let mut candidates: Vec<(usize, f64)> = scores.iter().copied().enumerate().collect();
+
+    let boundaries = if candidates.is_empty() {
+        Vec::new()
+    } else {
+        let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+        let variance = scores.iter().map(|score| (score - mean).powi(2)).sum::<f64>() / scores.len() as f64;
+        let cutoff = mean - variance.sqrt() / 2.0;
+
+        candidates.retain(|&(_, score)| score > cutoff && score > 0.0);
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        // The chapter boundary for gap `i` is the start of segment `i + 1`
+        let mut accepted: Vec<f64> = Vec::new();
+        for (gap, _) in candidates {
+            let time = segments[gap + 1].start_time;
+            let far_enough = time - first.start_time >= options.min_chapter
+                && last.end_time - time >= options.min_chapter
+                && accepted.iter().all(|other| (other - time).abs() >= options.min_chapter);
+            if far_enough {
+                accepted.push(time);
+            }
+        }
+        accepted.sort_by(f64::total_cmp);
+        accepted
+    };
+
+    // Chapters start at 0:00 so YouTube accepts the list
+    let mut starts = vec![0.0];
+    starts.extend(boundaries);
+    let chapters: Vec<Chapter> = starts
+        .iter()
+        .enumerate()
+        .map(|(index, &start_time)| {
+            let end_time = starts.get(index + 1).copied().unwrap_or(last.end_time);
+            let opening = segments
+                .iter()
+                .find(|segment| segment.start_time >= start_time)
+                .map_or("", |segment| segment.text.as_str());
+            Chapter {
+                start_time,
+                end_time,
+                title: chapter_title(opening, index),
+            }
+        })
+        .collect();
+
+    info!("📑 Detected {} chapters", chapters.len());
+    chapters
+}
+
+/// Boundary strength for the gap after each segment but the last
+fn boundary_scores(segments: &[TranscriptionSegment], options: &ChapterOptions) -> Vec<f64> {
+    if segments.len() < 2 {
+        return Vec::new();
+    }
+
+    let term_counts: Vec<HashMap<String, f64>> = segments
+        .iter()
+        .map(|segment| {
+            let mut counts = HashMap::new();
+            for word in text::content_words(&segment.text) {
+                *counts.entry(word).or_insert(0.0) += 1.0;
+            }
+            counts
+        })
+        .collect();
+
+    let block = options.block_size.max(1);
+    let cohesion: Vec<f64> = (0..segments.len() - 1)
+        .map(|gap| {
+            let before = merge_counts(&term_counts[(gap + 1).saturating_sub(block)..=gap]);
+            let after = merge_counts(&term_counts[gap + 1..(gap + 1 + block).min(segments.len())]);
+            cosine_similarity(&before, &after)
+        })
+        .collect();
+
+    (0..cohesion.len())
+        .map(|gap| {
+            let pause = (segments[gap + 1].start_time - segments[gap].end_time).max(0.0);
+            depth_score(&cohesion, gap) + (pause / options.long_pause).min(1.0)
+        })
+        .collect()
+}
+
+fn merge_counts(blocks: &[HashMap<String, f64>]) -> HashMap<String, f64> {
+    let mut merged = HashMap::new();
+    for counts in blocks {
+        for (word, count) in counts {
+            *merged.entry(word.clone()).or_insert(0.0) += count;
+        }
+    }
+    merged
+}
+
+fn cosine_similarity(a: &HashMap<String, f64>, b: &HashMap<String, f64>) -> f64 {
+    let dot: f64 = a.iter().filter_map(|(word, x)| b.get(word).map(|y| x * y)).sum();
+    let norm = |counts: &HashMap<String, f64>| counts.values().map(|x| x * x).sum::<f64>().sqrt();
+    let denominator = norm(a) * norm(b);
+    if denominator == 0.0 { 0.0 } else { dot / denominator }
+}
+
+/// How far cohesion at `gap` dips below the nearest peaks on either side
+fn depth_score(cohesion: &[f64], gap: usize) -> f64 {
+    let value = cohesion[gap];
+    let mut left = value;
+    for &score in cohesion[..gap].iter().rev() {
+        if score < left {
+            break;
+        }
+        left = score;
+    }
+    let mut right = value;
+    for &score in &cohesion[gap + 1..] {
+        if score < right {
+            break;
+        }
+        right = score;
+    }
+    (left - value) + (right - value)
+}
+
+/// The opening sentence of a chapter, shortened to a title
+fn chapter_title(opening: &str, index: usize) -> String {
+    let sentence = opening
+        .split_inclusive(['.', '?', '!'])
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_end_matches(['.', ',', ';', ':']);
+    if sentence.is_empty() {
+        return format!("Chapter {}", index + 1);
+    }
+    if sentence.chars().count() <= MAX_TITLE_CHARS {
+        return sentence.to_string();
+    }
+
+    let mut title = String::new();
+    for word in sentence.split_whitespace() {
+        if title.chars().count() + word.chars().count() + 1 > MAX_TITLE_CHARS - 1 {
+            break;
+        }
+        if !title.is_empty() {
+            title.push(' ');
+        }
+        title.push_str(word);
+    }
+    format!("{}…", title.trim_end_matches([',', ';', ':']))
+}
+
+/// Chapter export formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChapterFormat {
+    /// FFmpeg metadata file, for `ffmpeg -i video -i chapters.ffmetadata -map_chapters 1`
+    FfMetadata,
+    /// `MM:SS Title` lines for a YouTube description
+    YouTube,
+    /// WebVTT chapter track
+    Vtt,
+}
+
+impl ChapterFormat {
+    /// Guess the format from a file name: `.vtt`, `.ffmetadata`/`.ffmeta`, otherwise YouTube timestamps
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_lowercase).as_deref() {
+            Some("vtt") => Self::Vtt,
+            Some("ffmetadata") | Some("ffmeta") => Self::FfMetadata,
+            _ => Self::YouTube,
+        }
+    }
+}
+
+pub fn format_chapters(chapters: &[Chapter], format: ChapterFormat) -> String {
+    match format {
+        ChapterFormat::FfMetadata => format_as_ffmetadata(chapters),
+        ChapterFormat::YouTube => format_as_youtube(chapters),
+        ChapterFormat::Vtt => format_as_vtt_chapters(chapters),
+    }
+}
+
+pub async fn save_chapters(chapters: &[Chapter], path: &Path) -> Result<()> {
+    tokio::fs::write(path, format_chapters(chapters, ChapterFormat::from_path(path))).await?;
+    Ok(())
+}
+
+pub fn format_as_ffmetadata(chapters: &[Chapter]) -> String {
+    let mut content = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        content.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            (chapter.start_time * 1000.0).round() as u64,
+            (chapter.end_time * 1000.0).round() as u64,
+            escape_ffmetadata(&chapter.title)
+        ));
+    }
+    content
+}
+
+pub fn format_as_youtube(chapters: &[Chapter]) -> String {
+    chapters
+        .iter()
+        .map(|chapter| format!("{} {}\n", youtube_timestamp(chapter.start_time), chapter.title))
+        .collect()
+}
+
+pub fn format_as_vtt_chapters(chapters: &[Chapter]) -> String {
+    let mut content = String::from("WEBVTT\n\n");
+    for (index, chapter) in chapters.iter().enumerate() {
+        content.push_str(&format!(
+            "{}\n{} --> {}\n{}\n\n",
+            index + 1,
+            format_vtt_timestamp(chapter.start_time),
+            format_vtt_timestamp(chapter.end_time),
+            chapter.title
+        ));
+    }
+    content
+}
+
+/// `M:SS`, or `H:MM:SS` from one hour on
+pub(crate) fn youtube_timestamp(seconds: f64) -> String {
+    let total = seconds as u64;
+    let (hours, minutes, secs) = (total / 3600, total % 3600 / 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{:02}:{:02}", minutes, secs)
+    }
+}
+
+/// Backslash-escapes the characters FFmetadata treats specially
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Writes a copy of `video` with the chapters added, copying all streams
+pub async fn embed_chapters(chapters: &[Chapter], video: &Path, output_path: &Path) -> Result<()> {
+    let metadata_path = std::env::temp_dir().join(format!(
+        "wayne_chapters_{}_{}.ffmetadata",
+        std::process::id(),
+        chrono::Utc::now().timestamp_millis()
+    ));
+    tokio::fs::write(&metadata_path, format_as_ffmetadata(chapters)).await?;
+
+    info!("📑 Adding {} chapters to {:?}", chapters.len(), output_path);
+    let mut args: Vec<OsString> = vec!["-i".into(), video.into(), "-i".into(), metadata_path.clone().into()];
+    args.extend(["-map", "0", "-map_metadata", "0", "-map_chapters", "1", "-c", "copy"].map(OsString::from));
+    args.push(output_path.into());
+    let result = audio::run_ffmpeg(args).await;
+
+    if let Err(e) = tokio::fs::remove_file(&metadata_path).await {
+        warn!("Could not clean up temporary chapter file: {}", e);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start_time: f64, end_time: f64, text: &str) -> TranscriptionSegment {
+        TranscriptionSegment {
+            start_time,
+            end_time,
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Two topics of ten 10-second segments each, separated by a long pause
+    fn two_topic_lecture() -> TranscriptionResult {
+        let intention = [
+            "Intention is a force in the universe.",
+            "Your intention shapes the energy around you.",
+            "Connect to intention and the universe responds.",
+            "The power of intention flows through everything.",
+            "Intention is not something you do, it is energy.",
+            "Align your energy with the universe and intention.",
+            "When intention and energy meet, the universe moves.",
+            "Trust the power of intention.",
+            "Intention lives in the energy of the universe.",
+            "Feel the universe answer your intention.",
+        ];
+        let service = [
+            "Service to others brings purpose and kindness.",
+            "Kindness is the purpose of a life of service.",
+            "Give service with kindness and purpose follows.",
+            "Purpose grows from kindness and service.",
+            "A life of service is a life of kindness.",
+            "Kindness and service reveal your purpose.",
+            "Serve with kindness, find your purpose.",
+            "Purpose is service offered with kindness.",
+            "Kindness multiplies through service.",
+            "Live your purpose through kindness and service.",
+        ];
+
+        let mut segments = Vec::new();
+        for (index, text) in intention.iter().enumerate() {
+            let start = index as f64 * 10.0;
+            segments.push(segment(start, start + 9.5, text));
+        }
+        for (index, text) in service.iter().enumerate() {
+            let start = 104.0 + index as f64 * 10.0;
+            segments.push(segment(start, start + 9.5, text));
+        }
+        let full_text = segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ");
+        TranscriptionResult { segments, full_text, language: None }
+    }
+
+    #[test]
+    fn test_detect_topic_shift() {
+        let chapters = detect_chapters(&two_topic_lecture(), &ChapterOptions::default());
+
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].start_time, 0.0);
+        assert_eq!(chapters[1].start_time, 104.0);
+        assert_eq!(chapters[1].end_time, 203.5);
+        assert_eq!(chapters[1].title, "Service to others brings purpose and kindness");
+    }
+
+    #[test]
+    fn test_min_chapter_length() {
+        let options = ChapterOptions {
+            min_chapter: 120.0,
+            ..Default::default()
+        };
+
+        assert_eq!(detect_chapters(&two_topic_lecture(), &options).len(), 1);
+    }
+
+    #[test]
+    fn test_chapter_title() {
+        assert_eq!(chapter_title("", 2), "Chapter 3");
+        let title = chapter_title(
+            "We are not human beings having a spiritual experience but spiritual beings having a human one.",
+            0,
+        );
+        assert!(title.ends_with('…'));
+        assert!(title.chars().count() <= MAX_TITLE_CHARS);
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let chapters = vec![
+            Chapter { start_time: 0.0, end_time: 95.5, title: "Intention".to_string() },
+            Chapter { start_time: 95.5, end_time: 3725.0, title: "Q&A; part=1".to_string() },
+            Chapter { start_time: 3725.0, end_time: 3800.0, title: "Closing".to_string() },
+        ];
+
+        assert_eq!(format_as_youtube(&chapters), "00:00 Intention\n01:35 Q&A; part=1\n1:02:05 Closing\n");
+        assert!(format_as_ffmetadata(&chapters)
+            .contains("[CHAPTER]\nTIMEBASE=1/1000\nSTART=95500\nEND=3725000\ntitle=Q&A\\; part\\=1\n"));
+        assert!(format_as_vtt_chapters(&chapters).contains("2\n00:01:35.500 --> 01:02:05.000\nQ&A; part=1\n"));
+        assert_eq!(ChapterFormat::from_path(Path::new("talk.ffmetadata")), ChapterFormat::FfMetadata);
+        assert_eq!(ChapterFormat::from_path(Path::new("description.txt")), ChapterFormat::YouTube);
+    }
+}
@@ -0,0 +1,216 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use tracing::info;
+use crate::audio;
+use crate::error::{Result, TranscribeError};
+use crate::mux;
+use crate::output;
+use crate::transcription::{TranscriptionResult, TranscriptionSegment};
+
+/// Settings for [`extract_clip`]
+#[derive(Debug, Clone)]
+pub struct ClipOptions {
+    /// Seconds added before the start and after the end
+    pub padding: f64,
+    /// Re-encode for frame-accurate cuts instead of copying streams, which
+    /// is fast but can only start at a keyframe
+    pub reencode: bool,
+}
+
+impl Default for ClipOptions {
+    fn default() -> Self {
+        Self {
+            padding: 0.5,
+            reencode: false,
+        }
+    }
+}
+
+/// Cuts `start..end` (plus padding) out of `media` into `output_path`. With a
+/// transcription, the overlapping subtitles are written next to the clip as SRT,
+/// re-based to start at zero; the SRT path is returned.
+pub async fn extract_clip(
+    media: &Path,
+    output_path: &Path,
+    start: f64,
+    end: f64,
+    transcription: Option<&TranscriptionResult>,
+    options: &ClipOptions,
+) -> Result<Option<PathBuf>> {
+    if !(start.is_finite() && end.is_finite() && start >= 0.0) {
+        return Err(TranscribeError::InvalidInput(format!("clip range {}s–{}s is not a valid time range", start, end)));
+    }
+    if end <= start {
+        return Err(TranscribeError::InvalidInput(format!(
+            "clip end ({:.2}s) must be after its start ({:.2}s)",
+            end,
+            start
+        )));
+    }
+    if !(options.padding.is_finite() && options.padding >= 0.0) {
+        return Err(TranscribeError::InvalidInput(format!(
+            "clip padding must be a non-negative number of seconds, got {}",
+            options.padding
+        )));
+    }
+    if !media.exists() {
+        return Err(TranscribeError::MissingInput(media.to_path_buf()));
+    }
+
+    let start = (start - options.padding).max(0.0);
+    let end = end + options.padding;
+    info!("✂️  Cutting {:.2}s–{:.2}s of {:?} into {:?}", start, end, media, output_path);
+    audio::run_ffmpeg(clip_args(media, output_path, start, end, options.reencode)).await?;
+
+    let Some(transcription) = transcription else {
+        return Ok(None);
+    };
+    let srt_path = output_path.with_extension("srt");
+    let cropped = crop_transcription(transcription, start, end);
+    tokio::fs::write(&srt_path, output::format_as_srt(&cropped)).await?;
+    Ok(Some(srt_path))
+}
+
+/// The segments overlapping `start..end`, clamped to it and shifted so the clip starts at zero
+pub fn crop_transcription(transcription: &TranscriptionResult, start: f64, end: f64) -> TranscriptionResult {
+    let segments: Vec<TranscriptionSegment> = transcription
+        .segments
+        .iter()
+        .filter(|segment| segment.end_time > start && segment.start_time < end)
+        .map(|segment| {
+            let mut segment = segment.clone();
+            segment.start_time = segment.start_time.max(start) - start;
+            segment.end_time = segment.end_time.min(end) - start;
+            segment.words.retain(|word| word.end_time > start && word.start_time < end);
+            for word in &mut segment.words {
+                word.start_time = word.start_time.max(start) - start;
+                word.end_time = word.end_time.min(end) - start;
+            }
+            segment
+        })
+        .collect();
+    let full_text = segments
+        .iter()
+        .map(|segment| segment.text.trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    TranscriptionResult { segments, full_text, language: transcription.language.clone() }
+}
+
+/// Default clip path: `talk.mp4` cut at 14:32 → `talk.clip-14m32s.mp4`
+pub fn clip_path(media: &Path, start: f64) -> PathBuf {
+    let seconds = start as u64;
+    mux::copy_path(media, &format!("clip-{}m{:02}s", seconds / 60, seconds % 60))
+}
+
+/// Parses `872.5`, `14:32` or `00:14:32.5` into seconds. Negative and
+/// non-finite parts (`-5`, `inf`, `NaN`) are rejected.
+pub fn parse_timestamp(value: &str) -> Result<f64> {
+    let invalid = || TranscribeError::InvalidInput(format!("invalid timestamp '{}'; use SECONDS, MM:SS or HH:MM:SS", value));
+    let parts: Vec<&str> = value.trim().split(':').collect();
+    if parts.len() > 3 {
+        return Err(invalid());
+    }
+    parts.iter().try_fold(0.0, |total, part| {
+        let part: f64 = part.parse().map_err(|_| invalid())?;
+        if !part.is_finite() || part.is_sign_negative() {
+            return Err(invalid());
+        }
+        Ok(total * 60.0 + part)
+    })
+}
+
+fn clip_args(media: &Path, output_path: &Path, start: f64, end: f64, reencode: bool) -> Vec<OsString> {
+    // Seeking before the input is fast, and exact when re-encoding
+    let mut args: Vec<OsString> = vec![
+        "-ss".into(),
+        format!("{:.3}", start).into(),
+        "-i".into(),
+        media.into(),
+        "-t".into(),
+        format!("{:.3}", end - start).into(),
+    ];
+    if !reencode {
+        args.extend(["-c", "copy", "-avoid_negative_ts", "make_zero"].map(OsString::from));
+    }
+    args.push(output_path.into());
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::WordTiming;
+
+    fn segment(start_time: f64, end_time: f64, text: &str) -> TranscriptionSegment {
+        TranscriptionSegment {
+            start_time,
+            end_time,
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_crop_transcription() {
+        let mut middle = segment(10.0, 20.0, "The power of intention.");
+        middle.words = vec![
+            WordTiming { start_time: 10.0, end_time: 10.5, word: "The".into(), ..Default::default() },
+            WordTiming { start_time: 19.0, end_time: 20.0, word: "intention.".into(), ..Default::default() },
+        ];
+        let transcription = TranscriptionResult {
+            segments: vec![segment(0.0, 10.0, "Welcome."), middle, segment(20.0, 30.0, "Thank you.")],
+            full_text: String::new(),
+            language: None,
+        };
+
+        let cropped = crop_transcription(&transcription, 9.5, 19.5);
+
+        assert_eq!(cropped.segments.len(), 2);
+        assert_eq!((cropped.segments[0].start_time, cropped.segments[0].end_time), (0.0, 0.5));
+        assert_eq!((cropped.segments[1].start_time, cropped.segments[1].end_time), (0.5, 10.0));
+        assert_eq!(cropped.segments[1].words.len(), 2);
+        assert_eq!(cropped.segments[1].words[1].end_time, 10.0);
+        assert_eq!(cropped.full_text, "Welcome. The power of intention.");
+        assert!(output::format_as_srt(&cropped).contains("00:00:00,500 --> 00:00:10,000"));
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("872.5").unwrap(), 872.5);
+        assert_eq!(parse_timestamp("14:32").unwrap(), 872.0);
+        assert_eq!(parse_timestamp("01:14:32.5").unwrap(), 4472.5);
+        assert!(parse_timestamp("1:2:3:4").is_err());
+        assert!(parse_timestamp("ten").is_err());
+        for value in ["inf", "-inf", "NaN", "1:infinity", "-5", "00:-1:00", "-0"] {
+            assert!(matches!(parse_timestamp(value), Err(TranscribeError::InvalidInput(_))), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_clip_args() {
+        let copy = clip_args(Path::new("talk.mp4"), Path::new("clip.mp4"), 871.5, 880.25, false);
+        let copy: Vec<_> = copy.iter().map(|arg| arg.to_str().unwrap()).collect();
+        assert_eq!(copy[..6], ["-ss", "871.500", "-i", "talk.mp4", "-t", "8.750"]);
+        assert!(copy.windows(2).any(|pair| pair == ["-c", "copy"]));
+
+        let reencode = clip_args(Path::new("talk.mp4"), Path::new("clip.mp4"), 0.0, 1.0, true);
+        assert!(!reencode.contains(&OsString::from("copy")));
+        assert_eq!(clip_path(Path::new("/videos/talk.mp4"), 872.4), Path::new("/videos/talk.clip-14m32s.mp4"));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_empty_range() {
+        for (start, end) in [(5.0, 5.0), (f64::NAN, 2.0), (1.0, f64::INFINITY), (-1.0, 2.0)] {
+            let result = extract_clip(Path::new("talk.mp4"), Path::new("clip.mp4"), start, end, None, &ClipOptions::default()).await;
+
+            assert!(matches!(result, Err(TranscribeError::InvalidInput(_))), "{}–{}", start, end);
+        }
+        for padding in [-0.5, f64::NAN, f64::INFINITY] {
+            let options = ClipOptions { padding, ..ClipOptions::default() };
+            let result = extract_clip(Path::new("talk.mp4"), Path::new("clip.mp4"), 1.0, 2.0, None, &options).await;
+
+            assert!(matches!(result, Err(TranscribeError::InvalidInput(_))), "padding {}", padding);
+        }
+    }
+}
@@ -0,0 +1,508 @@
+//! Layered settings from config files, named profiles and `WAYNE_*` environment
+//! variables. Keys are the long command-line option names, so the CLI can use
+//! the merged values as its defaults.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use crate::error::{Result, TranscribeError};
+
+/// Project config file, looked up in the current directory and its parents
+pub const PROJECT_CONFIG_FILE: &str = "wayne-transcriber.toml";
+
+/// Prefix of the environment variables that override config files
+pub const ENV_PREFIX: &str = "WAYNE_";
+
+/// Settings a config file, profile or environment variable can set.
+/// Anything left out keeps the command-line default.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Settings {
+    pub format: Option<String>,
+    pub model: Option<String>,
+    pub diarize: Option<bool>,
+    pub num_speakers: Option<usize>,
+    pub prompt: Option<String>,
+    pub vocab: Option<PathBuf>,
+    pub vocab_bias: Option<f32>,
+    pub drop_hallucinations: Option<bool>,
+    pub review_threshold: Option<f32>,
+    pub postprocess: Option<String>,
+    /// Output format → post-processing steps
+    pub postprocess_for: Option<BTreeMap<String, String>>,
+    pub max_line_chars: Option<usize>,
+    pub max_lines: Option<usize>,
+    pub max_cps: Option<f64>,
+    pub subtitle_language: Option<String>,
+    pub subtitle_codec: Option<String>,
+    pub summary_sentences: Option<usize>,
+    pub min_chapter: Option<f64>,
+    pub speaker_db: Option<PathBuf>,
+    pub timeout: Option<f64>,
+    pub no_reconnect: Option<bool>,
+    pub padding: Option<f64>,
+    pub reencode: Option<bool>,
+    pub addr: Option<String>,
+    pub concurrency: Option<usize>,
+    pub max_queue: Option<usize>,
+    pub partial_interval: Option<f64>,
+    pub commit_silence: Option<f64>,
+    pub max_segment: Option<f64>,
+}
+
+impl Settings {
+    /// Every key, for reading `WAYNE_<KEY>` environment variables
+    pub const KEYS: &[&str] = &[
+        "format", "model", "diarize", "num-speakers", "prompt", "vocab", "vocab-bias", "drop-hallucinations",
+        "review-threshold", "postprocess", "postprocess-for", "max-line-chars", "max-lines", "max-cps",
+        "subtitle-language", "subtitle-codec",
+        "summary-sentences", "min-chapter", "speaker-db", "timeout", "no-reconnect", "padding", "reencode", "addr",
+        "concurrency", "max-queue", "partial-interval", "commit-silence", "max-segment",
+    ];
+
+    /// Keys whose values are paths, resolved against the directory of the file setting them
+    const PATH_KEYS: &[&str] = &["vocab", "speaker-db"];
+}
+
+/// A config file: defaults, named profiles and the profile to use
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigFile {
+    /// Profile used when none is given with `--profile` or `WAYNE_PROFILE`
+    pub profile: Option<String>,
+    pub defaults: toml::Table,
+    pub profiles: BTreeMap<String, toml::Table>,
+}
+
+impl ConfigFile {
+    /// Reads a config file, or `None` if it does not exist
+    pub async fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = tokio::fs::read_to_string(path).await?;
+        let mut file: Self = toml::from_str(&content).map_err(|e| invalid(path, e))?;
+
+        // Check the values now, so errors name the file they came from
+        let base = path.parent().unwrap_or(Path::new("."));
+        for table in std::iter::once(&mut file.defaults).chain(file.profiles.values_mut()) {
+            Settings::deserialize(table.clone()).map_err(|e| invalid(path, e))?;
+            resolve_paths(table, base);
+        }
+        Ok(Some(file))
+    }
+}
+
+fn invalid(path: &Path, error: impl std::fmt::Display) -> TranscribeError {
+    TranscribeError::Other(anyhow::anyhow!("Invalid config file {:?}: {}", path, error))
+}
+
+fn resolve_paths(table: &mut toml::Table, base: &Path) {
+    for key in Settings::PATH_KEYS {
+        if let Some(toml::Value::String(path)) = table.get_mut(*key)
+            && Path::new(path.as_str()).is_relative()
+        {
+            *path = base.join(&*path).to_string_lossy().into_owned();
+        }
+    }
+}
+
+/// Profiles available without a config file; files can redefine them
+pub fn builtin_profiles() -> BTreeMap<String, toml::Table> {
+    let quick: toml::Table = toml::toml! {
+        model = "tiny"
+        format = "txt"
+    };
+    // Two lines of 37 characters read at up to 17 characters a second, as in
+    // common broadcast subtitle guidelines
+    let broadcast: toml::Table = toml::toml! {
+        model = "large"
+        format = "ttml"
+        drop-hallucinations = true
+        max-line-chars = 37
+        max-lines = 2
+        max-cps = 17.0
+        postprocess = "fillers,digits,case,punctuation"
+        [postprocess-for]
+        srt = "fillers,digits,case,punctuation,profanity"
+        vtt = "fillers,digits,case,punctuation,profanity"
+        ttml = "fillers,digits,case,punctuation,profanity"
+    };
+    BTreeMap::from([("quick".to_string(), quick), ("broadcast".to_string(), broadcast)])
+}
+
+/// `$XDG_CONFIG_HOME/wayne-transcriber/config.toml`, falling back to `~/.config`
+pub fn user_config_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("wayne-transcriber").join("config.toml"))
+}
+
+/// The nearest `wayne-transcriber.toml` in `dir` or one of its parents
+pub fn find_project_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+/// Settings merged from every layer, remembering where each value came from
+#[derive(Debug, Clone, Default)]
+pub struct EffectiveConfig {
+    pub profile: Option<String>,
+    pub settings: Settings,
+    /// Merged values, keyed by option name
+    pub values: toml::Table,
+    /// Key → description of the layer that set it
+    pub origins: BTreeMap<String, String>,
+}
+
+/// Merges, lowest precedence first:
+///
+/// 1. `[defaults]` of the user config
+/// 2. `[defaults]` of the project config
+/// 3. the profile: its built-in definition, then the user's, then the project's
+/// 4. `WAYNE_*` environment variables
+///
+/// Command-line options override the result. The profile itself is
+/// `cli_profile`, else `WAYNE_PROFILE`, else the `profile` of the project
+/// config, then of the user config. A profile picked by the user config sits
+/// between the two files instead, so the project's `[defaults]` override it.
+pub fn resolve(
+    user: Option<&ConfigFile>,
+    project: Option<&ConfigFile>,
+    env: impl IntoIterator<Item = (String, String)>,
+    cli_profile: Option<&str>,
+) -> Result<EffectiveConfig> {
+    let env: BTreeMap<String, String> = env
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.strip_prefix(ENV_PREFIX)?.to_string(), value)))
+        .collect();
+    let selected = cli_profile
+        .map(str::to_string)
+        .or_else(|| env.get("PROFILE").cloned())
+        .or_else(|| project.and_then(|file| file.profile.clone()));
+    let user_profile = user.and_then(|file| file.profile.clone()).filter(|_| selected.is_none());
+
+    let mut config = EffectiveConfig {
+        profile: selected.clone().or_else(|| user_profile.clone()),
+        ..Default::default()
+    };
+    let files = [("user config", user), ("project config", project)];
+    if let Some(file) = user {
+        config.merge(&file.defaults, "user config");
+    }
+    if let Some(profile) = &user_profile {
+        config.merge_profile(profile, &files)?;
+    }
+    if let Some(file) = project {
+        config.merge(&file.defaults, "project config");
+    }
+    if let Some(profile) = &selected {
+        config.merge_profile(profile, &files)?;
+    }
+
+    let mut from_env = toml::Table::new();
+    for key in Settings::KEYS {
+        let name = key.to_uppercase().replace('-', "_");
+        if let Some(raw) = env.get(&name) {
+            from_env.insert(key.to_string(), env_value(key, raw)?);
+        }
+    }
+    config.merge(&from_env, "environment");
+
+    config.settings = Settings::deserialize(config.values.clone())
+        .map_err(|e| TranscribeError::Other(anyhow::anyhow!("Invalid settings: {}", e)))?;
+    Ok(config)
+}
+
+impl EffectiveConfig {
+    fn merge(&mut self, table: &toml::Table, origin: &str) {
+        for (key, value) in table {
+            self.values.insert(key.clone(), value.clone());
+            self.origins.insert(key.clone(), origin.to_string());
+        }
+    }
+
+    /// The built-in definition of `profile`, then those of `files`
+    fn merge_profile(&mut self, profile: &str, files: &[(&str, Option<&ConfigFile>)]) -> Result<()> {
+        let builtin = builtin_profiles();
+        let mut found = false;
+        if let Some(table) = builtin.get(profile) {
+            self.merge(table, &format!("built-in profile {}", profile));
+            found = true;
+        }
+        for (name, file) in files {
+            if let Some(table) = file.and_then(|file| file.profiles.get(profile)) {
+                self.merge(table, &format!("profile {} ({})", profile, name));
+                found = true;
+            }
+        }
+        if !found {
+            let mut known: Vec<String> = builtin.into_keys().collect();
+            for file in files.iter().filter_map(|(_, file)| *file) {
+                known.extend(file.profiles.keys().cloned());
+            }
+            known.sort();
+            known.dedup();
+            return Err(TranscribeError::Other(anyhow::anyhow!(
+                "Unknown profile '{}'. Available: {}",
+                profile,
+                known.join(", ")
+            )));
+        }
+        Ok(())
+    }
+
+    /// Each setting as command-line values: tables become `KEY=VALUE` entries
+    pub fn cli_defaults(&self) -> Vec<(String, Vec<String>)> {
+        self.values
+            .iter()
+            .map(|(key, value)| {
+                let values = match value {
+                    toml::Value::String(value) => vec![value.clone()],
+                    toml::Value::Table(table) => table
+                        .iter()
+                        .map(|(name, value)| format!("{}={}", name, value.as_str().unwrap_or_default()))
+                        .collect(),
+                    other => vec![other.to_string()],
+                };
+                (key.clone(), values)
+            })
+            .collect()
+    }
+}
+
+/// Reads `WAYNE_<KEY>` as the type the key expects: `postprocess-for` takes
+/// `srt=fillers;vtt=case`, everything else a plain value
+fn env_value(key: &str, raw: &str) -> Result<toml::Value> {
+    let value = if key == "postprocess-for" {
+        let mut table = toml::Table::new();
+        for entry in raw.split(';').filter(|entry| !entry.trim().is_empty()) {
+            let (format, steps) = entry.split_once('=').unwrap_or((entry, ""));
+            table.insert(format.trim().to_string(), toml::Value::String(steps.trim().to_string()));
+        }
+        toml::Value::Table(table)
+    } else {
+        // Numbers and booleans parse as TOML; anything else is a string
+        let parsed = format!("value = {}", raw)
+            .parse::<toml::Table>()
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+            .filter(|value| !value.is_table() && !value.is_array());
+        let single = |value: toml::Value| Settings::deserialize(toml::Table::from_iter([(key.to_string(), value)]));
+        match parsed {
+            Some(value) if single(value.clone()).is_ok() => value,
+            _ => toml::Value::String(raw.to_string()),
+        }
+    };
+
+    Settings::deserialize(toml::Table::from_iter([(key.to_string(), value.clone())])).map_err(|e| {
+        TranscribeError::Other(anyhow::anyhow!(
+            "Invalid {}{}: {}",
+            ENV_PREFIX,
+            key.to_uppercase().replace('-', "_"),
+            e
+        ))
+    })?;
+    Ok(value)
+}
+
+/// The merged settings as TOML, each line commented with where it came from
+pub fn format_effective(config: &EffectiveConfig) -> String {
+    let mut content = format!(
+        "# profile: {}\n",
+        config.profile.as_deref().unwrap_or("(none)")
+    );
+    let mut tables = String::new();
+    for (key, value) in &config.values {
+        let origin = &config.origins[key];
+        match value {
+            toml::Value::Table(table) => {
+                tables.push_str(&format!("\n[{}]  # {}\n", key, origin));
+                tables.push_str(&toml::to_string(table).unwrap_or_default());
+            }
+            value => content.push_str(&format!("{} = {}  # {}\n", key, value, origin)),
+        }
+    }
+    content + &tables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(content: &str) -> ConfigFile {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn test_keys_match_settings() {
+        let all = Settings {
+            format: Some("srt".into()),
+            model: Some("tiny".into()),
+            diarize: Some(true),
+            num_speakers: Some(2),
+            prompt: Some("Wayne Dyer".into()),
+            vocab: Some("vocab.txt".into()),
+            vocab_bias: Some(1.0),
+            drop_hallucinations: Some(true),
+            review_threshold: Some(0.5),
+            postprocess: Some("case".into()),
+            postprocess_for: Some(BTreeMap::from([("srt".into(), "fillers".into())])),
+            max_line_chars: Some(42),
+            max_lines: Some(2),
+            max_cps: Some(17.0),
+            subtitle_language: Some("eng".into()),
+            subtitle_codec: Some("srt".into()),
+            summary_sentences: Some(3),
+            min_chapter: Some(30.0),
+            speaker_db: Some("speakers.json".into()),
+            timeout: Some(10.0),
+            no_reconnect: Some(true),
+            padding: Some(1.0),
+            reencode: Some(true),
+            addr: Some("0.0.0.0:8080".into()),
+            concurrency: Some(2),
+            max_queue: Some(8),
+            partial_interval: Some(0.5),
+            commit_silence: Some(0.4),
+            max_segment: Some(10.0),
+        };
+        let table = toml::Table::try_from(&all).unwrap();
+
+        let keys: Vec<&str> = table.keys().map(String::as_str).collect();
+        let mut expected = Settings::KEYS.to_vec();
+        expected.sort_unstable();
+        assert_eq!(keys, expected);
+    }
+
+    #[test]
+    fn test_precedence() {
+        let user = file(
+            r#"
+            profile = "quick"
+            [defaults]
+            model = "small"
+            format = "vtt"
+            summary-sentences = 3
+            [profiles.quick]
+            model = "base"
+            "#,
+        );
+        let project = file(
+            r#"
+            [defaults]
+            format = "json"
+            [profiles.quick]
+            diarize = true
+            "#,
+        );
+        let env = [("WAYNE_DIARIZE".to_string(), "false".to_string()), ("HOME".to_string(), "/root".to_string())];
+
+        let config = resolve(Some(&user), Some(&project), env, None).unwrap();
+
+        assert_eq!(config.profile.as_deref(), Some("quick"));
+        // The user's quick profile overrides the built-in one and the user's defaults,
+        // but not the project's
+        assert_eq!(config.settings.model.as_deref(), Some("base"));
+        assert_eq!(config.settings.format.as_deref(), Some("json"));
+        assert_eq!(config.settings.summary_sentences, Some(3));
+        assert_eq!(config.settings.diarize, Some(false));
+        assert_eq!(config.origins["model"], "profile quick (user config)");
+        assert_eq!(config.origins["diarize"], "environment");
+    }
+
+    #[test]
+    fn test_project_defaults_override_the_user_profile() {
+        let user = file("profile = \"broadcast\"\n[defaults]\nmax-cps = 20.0\n");
+        let project = file("[defaults]\nformat = \"srt\"\nmax-line-chars = 42\nsummary-sentences = 3\n");
+
+        let config = resolve(Some(&user), Some(&project), [], None).unwrap();
+
+        assert_eq!(config.profile.as_deref(), Some("broadcast"));
+        assert_eq!(config.settings.format.as_deref(), Some("srt"));
+        assert_eq!(config.origins["format"], "project config");
+        assert_eq!(config.settings.max_line_chars, Some(42));
+        // The profile still beats the user's own defaults and fills keys the project leaves alone
+        assert_eq!(config.settings.max_cps, Some(17.0));
+        assert_eq!(config.origins["max-cps"], "built-in profile broadcast");
+        assert_eq!(config.settings.summary_sentences, Some(3));
+
+        // Picked anywhere above the project config, the profile wins over its defaults
+        let env = [("WAYNE_PROFILE".to_string(), "broadcast".to_string())];
+        let config = resolve(Some(&user), Some(&project), env, None).unwrap();
+        assert_eq!(config.settings.format.as_deref(), Some("ttml"));
+        assert_eq!(config.settings.max_line_chars, Some(37));
+        let config = resolve(Some(&user), Some(&project), [], Some("broadcast")).unwrap();
+        assert_eq!(config.settings.format.as_deref(), Some("ttml"));
+
+        let env = [("WAYNE_MAX_LINE_CHARS".to_string(), "32".to_string())];
+        let config = resolve(Some(&user), Some(&project), env, None).unwrap();
+        assert_eq!(config.settings.max_line_chars, Some(32));
+    }
+
+    #[test]
+    fn test_profile_selection() {
+        let project = file("profile = \"quick\"\n[profiles.archive]\nformat = \"json\"\n");
+
+        let cli = resolve(None, Some(&project), [], Some("broadcast")).unwrap();
+        assert_eq!(cli.settings.model.as_deref(), Some("large"));
+        assert_eq!(
+            cli.settings.postprocess_for.unwrap()["srt"],
+            "fillers,digits,case,punctuation,profanity"
+        );
+
+        let env = [("WAYNE_PROFILE".to_string(), "archive".to_string())];
+        assert_eq!(resolve(None, Some(&project), env, None).unwrap().settings.format.as_deref(), Some("json"));
+
+        let error = resolve(None, Some(&project), [], Some("podcast")).unwrap_err();
+        assert!(error.to_string().contains("Available: archive, broadcast, quick"));
+    }
+
+    #[test]
+    fn test_env_values() {
+        let env = [
+            ("WAYNE_VOCAB_BIAS".to_string(), "2.5".to_string()),
+            ("WAYNE_PROMPT".to_string(), "42".to_string()),
+            ("WAYNE_POSTPROCESS_FOR".to_string(), "srt=fillers,profanity; txt=case".to_string()),
+        ];
+        let config = resolve(None, None, env, None).unwrap();
+
+        assert_eq!(config.settings.vocab_bias, Some(2.5));
+        assert_eq!(config.settings.prompt.as_deref(), Some("42"));
+        let defaults = config.cli_defaults();
+        assert!(defaults.contains(&("postprocess-for".to_string(), vec!["srt=fillers,profanity".into(), "txt=case".into()])));
+
+        let bad = [("WAYNE_NUM_SPEAKERS".to_string(), "several".to_string())];
+        assert!(resolve(None, None, bad, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_load_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(PROJECT_CONFIG_FILE);
+        std::fs::write(&path, "[defaults]\nvocab = \"terms.txt\"\nmodel = \"tiny\"\n").unwrap();
+
+        let loaded = ConfigFile::load(&path).await.unwrap().unwrap();
+        assert_eq!(loaded.defaults["vocab"].as_str().unwrap(), dir.path().join("terms.txt").to_str().unwrap());
+        assert_eq!(find_project_config(&dir.path().join("sub/dir")), Some(path.clone()));
+
+        std::fs::write(&path, "[defaults]\nmodle = \"tiny\"\n").unwrap();
+        let error = ConfigFile::load(&path).await.unwrap_err();
+        assert!(error.to_string().contains("modle"));
+
+        assert!(ConfigFile::load(&dir.path().join("missing.toml")).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_format_effective() {
+        let config = resolve(None, None, [], Some("broadcast")).unwrap();
+        let shown = format_effective(&config);
+
+        assert!(shown.starts_with("# profile: broadcast\n"));
+        assert!(shown.contains("model = \"large\"  # built-in profile broadcast\n"));
+        assert!(shown.contains("[postprocess-for]  # built-in profile broadcast\nsrt = "));
+    }
+}
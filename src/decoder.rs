@@ -0,0 +1,883 @@
+//! Whisper inference with Candle: log-mel features sized for the checkpoint,
+//! the audio encoder, and a token decoder whose timestamp tokens split each
+//! window into segments.
+
+use candle_core::{Device, IndexOp, Tensor};
+use candle_transformers::models::whisper::{self as whisper, model, quantized_model, Config};
+use rustfft::{num_complex::Complex, FftPlanner};
+use std::path::Path;
+use tokenizers::Tokenizer;
+use tracing::{debug, info};
+use crate::error::{Result, TranscribeError};
+use crate::transcription::{DecodingOptions, LoadedModel, TranscriptionSegment, WordTiming};
+use crate::vad::SpeechRegion;
+
+/// Seconds between two timestamp tokens
+const TIMESTAMP_STEP: f64 = 0.02;
+/// Latest timestamp a window may open with, in timestamp steps (1s)
+const MAX_INITIAL_TIMESTAMP: usize = 50;
+/// Windows start this long before the speech VAD found, so onsets are not clipped
+const SPEECH_PADDING: f64 = 0.2;
+const START_OF_PREV_TOKEN: &str = "<|startofprev|>";
+/// Probability a timestamp token needs while a text token is sampled for that
+/// time to be taken as where the token starts
+const TOKEN_TIMESTAMP_THRESHOLD: f32 = 0.01;
+
+/// Full-precision or GGUF-quantized Whisper weights
+#[derive(Debug, Clone)]
+pub enum WhisperWeights {
+    Full(model::Whisper),
+    Quantized(quantized_model::Whisper),
+}
+
+impl WhisperWeights {
+    fn encode(&mut self, mel: &Tensor) -> candle_core::Result<Tensor> {
+        match self {
+            Self::Full(model) => model.encoder.forward(mel, true),
+            Self::Quantized(model) => model.encoder.forward(mel, true),
+        }
+    }
+
+    /// Hidden states for every position of `tokens`
+    fn decode(&mut self, tokens: &Tensor, audio_features: &Tensor, flush: bool) -> candle_core::Result<Tensor> {
+        match self {
+            Self::Full(model) => model.decoder.forward(tokens, audio_features, flush),
+            Self::Quantized(model) => model.decoder.forward(tokens, audio_features, flush),
+        }
+    }
+
+    /// Vocabulary logits of the hidden state at `position`
+    fn logits(&self, hidden: &Tensor, position: usize) -> candle_core::Result<Vec<f32>> {
+        let hidden = hidden.i((..1, position..position + 1))?;
+        let logits = match self {
+            Self::Full(model) => model.decoder.final_linear(&hidden)?,
+            Self::Quantized(model) => model.decoder.final_linear(&hidden)?,
+        };
+        logits.flatten_all()?.to_vec1()
+    }
+}
+
+/// Ids of Whisper's control tokens in a checkpoint's tokenizer
+#[derive(Debug, Clone)]
+pub struct SpecialTokens {
+    pub sot: u32,
+    pub eot: u32,
+    pub transcribe: u32,
+    pub translate: Option<u32>,
+    pub no_timestamps: u32,
+    pub no_speech: Option<u32>,
+    pub start_of_prev: Option<u32>,
+    /// `<|en|>`, `<|de|>`, … by language code; empty for English-only models
+    pub languages: Vec<(String, u32)>,
+}
+
+impl SpecialTokens {
+    pub fn from_tokenizer(tokenizer: &Tokenizer) -> Result<Self> {
+        let required = |token: &str| {
+            tokenizer
+                .token_to_id(token)
+                .ok_or_else(|| TranscribeError::UnsupportedFormat(format!("the tokenizer has no {} token", token)))
+        };
+        let mut languages: Vec<(String, u32)> = tokenizer
+            .get_vocab(true)
+            .into_iter()
+            .filter_map(|(token, id)| {
+                let code = token.strip_prefix("<|")?.strip_suffix("|>")?;
+                (matches!(code.len(), 2 | 3) && code.chars().all(|c| c.is_ascii_lowercase()))
+                    .then(|| (code.to_string(), id))
+            })
+            .collect();
+        languages.sort_by_key(|(_, id)| *id);
+
+        Ok(Self {
+            sot: required(whisper::SOT_TOKEN)?,
+            eot: required(whisper::EOT_TOKEN)?,
+            transcribe: required(whisper::TRANSCRIBE_TOKEN)?,
+            translate: tokenizer.token_to_id(whisper::TRANSLATE_TOKEN),
+            no_timestamps: required(whisper::NO_TIMESTAMPS_TOKEN)?,
+            no_speech: whisper::NO_SPEECH_TOKENS.iter().find_map(|token| tokenizer.token_to_id(token)),
+            start_of_prev: tokenizer.token_to_id(START_OF_PREV_TOKEN),
+            languages,
+        })
+    }
+
+    /// `<|0.00|>`, the first timestamp token
+    pub fn timestamp_begin(&self) -> u32 {
+        self.no_timestamps + 1
+    }
+}
+
+/// Reads the `tokenizer.json` kept beside (or inside) a checkpoint
+pub fn load_tokenizer(model_path: &Path) -> Result<Tokenizer> {
+    let path = crate::models::sidecar(model_path, crate::models::TOKENIZER_FILE);
+    if !path.is_file() {
+        return Err(TranscribeError::UnsupportedFormat(format!(
+            "{:?} has no {} beside it",
+            model_path,
+            crate::models::TOKENIZER_FILE
+        )));
+    }
+    Tokenizer::from_file(&path)
+        .map_err(|e| TranscribeError::UnsupportedFormat(format!("{:?} is not a valid tokenizer: {}", path, e)))
+}
+
+/// Slaney-scale mel filterbank as librosa (and Whisper's `mel_filters.npz`)
+/// builds it for 16kHz audio and 400-point FFTs: `num_mel_bins` rows of 201 weights
+pub fn mel_filters(num_mel_bins: usize) -> Vec<f32> {
+    const F_SP: f64 = 200.0 / 3.0;
+    const MIN_LOG_HZ: f64 = 1000.0;
+    const MIN_LOG_MEL: f64 = MIN_LOG_HZ / F_SP;
+    let log_step = 6.4f64.ln() / 27.0;
+    let hz_to_mel = |hz: f64| {
+        if hz >= MIN_LOG_HZ { MIN_LOG_MEL + (hz / MIN_LOG_HZ).ln() / log_step } else { hz / F_SP }
+    };
+    let mel_to_hz = |mel: f64| {
+        if mel >= MIN_LOG_MEL { MIN_LOG_HZ * (log_step * (mel - MIN_LOG_MEL)).exp() } else { F_SP * mel }
+    };
+
+    let n_freqs = whisper::N_FFT / 2 + 1;
+    let sample_rate = whisper::SAMPLE_RATE as f64;
+    let max_mel = hz_to_mel(sample_rate / 2.0);
+    let points: Vec<f64> = (0..num_mel_bins + 2)
+        .map(|i| mel_to_hz(max_mel * i as f64 / (num_mel_bins + 1) as f64))
+        .collect();
+
+    let mut filters = vec![0.0f32; num_mel_bins * n_freqs];
+    for band in 0..num_mel_bins {
+        let (lower, center, upper) = (points[band], points[band + 1], points[band + 2]);
+        let norm = 2.0 / (upper - lower);
+        for bin in 0..n_freqs {
+            let freq = bin as f64 * sample_rate / whisper::N_FFT as f64;
+            let rising = (freq - lower) / (center - lower);
+            let falling = (upper - freq) / (upper - center);
+            filters[band * n_freqs + bin] = (rising.min(falling).max(0.0) * norm) as f32;
+        }
+    }
+    filters
+}
+
+/// Whisper's log-mel spectrogram of `samples`, zero-padded or cut to
+/// `frames` frames: a centred (reflect-padded) STFT with a periodic Hann
+/// window, `log10` band powers floored 8 below the peak, then scaled by 1/4.
+/// Laid out band by band.
+pub fn log_mel_spectrogram(samples: &[f32], filters: &[f32], frames: usize) -> Vec<f32> {
+    let (n_fft, hop) = (whisper::N_FFT, whisper::HOP_LENGTH);
+    let n_freqs = n_fft / 2 + 1;
+    let n_mels = filters.len() / n_freqs;
+
+    let mut audio = samples.to_vec();
+    audio.resize(frames * hop, 0.0);
+    let pad = n_fft / 2;
+    let reflect = |index: isize| {
+        let last = audio.len() as isize - 1;
+        let index = if index < 0 { -index } else if index > last { 2 * last - index } else { index };
+        audio.get(index.clamp(0, last.max(0)) as usize).copied().unwrap_or(0.0)
+    };
+    let window: Vec<f32> = (0..n_fft)
+        .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / n_fft as f32).cos())
+        .collect();
+
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(n_fft);
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); n_fft];
+    let mut mel = vec![0.0f32; n_mels * frames];
+    for frame in 0..frames {
+        let start = (frame * hop) as isize - pad as isize;
+        for (offset, value) in buffer.iter_mut().enumerate() {
+            *value = Complex::new(reflect(start + offset as isize) * window[offset], 0.0);
+        }
+        fft.process(&mut buffer);
+        let power: Vec<f32> = buffer[..n_freqs].iter().map(|c| c.norm_sqr()).collect();
+        for (band, weights) in filters.chunks(n_freqs).enumerate() {
+            let energy: f32 = weights.iter().zip(&power).map(|(w, p)| w * p).sum();
+            mel[band * frames + frame] = energy.max(1e-10).log10();
+        }
+    }
+
+    let floor = mel.iter().copied().fold(f32::NEG_INFINITY, f32::max) - 8.0;
+    for value in &mut mel {
+        *value = (value.max(floor) + 4.0) / 4.0;
+    }
+    mel
+}
+
+/// One decoding pass over a window
+#[derive(Debug, Clone)]
+struct DecodingResult {
+    /// Sampled tokens, without the prompt
+    tokens: Vec<u32>,
+    /// Scores of each sampled token
+    scores: Vec<TokenScore>,
+    avg_logprob: f32,
+    no_speech_prob: Option<f32>,
+}
+
+/// How sure the decoder was of a sampled token, and when it placed it
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TokenScore {
+    logprob: f32,
+    /// The most likely timestamp at that step, relative to the window, when
+    /// it was at least `TOKEN_TIMESTAMP_THRESHOLD` likely
+    timestamp: Option<f64>,
+}
+
+/// Tokens of one segment, with times relative to the start of its window
+#[derive(Debug, Clone, PartialEq)]
+struct TokenSegment {
+    start: f64,
+    end: f64,
+    tokens: Vec<u32>,
+    /// Index of each of `tokens` in the sampled tokens
+    positions: Vec<usize>,
+}
+
+/// Decodes `audio` in windows of the encoder's length (30s for released
+/// models), skipping stretches VAD found no speech in. Each window continues
+/// from the last complete segment of the previous one. Returns the segments
+/// and the language they were decoded as.
+pub fn transcribe(
+    model: &LoadedModel,
+    audio: &[f32],
+    speech_regions: &[SpeechRegion],
+    options: &DecodingOptions,
+) -> Result<(Vec<TranscriptionSegment>, Option<String>)> {
+    let mut decoder = Decoder::new(model, options);
+    let window = 2 * model.config.max_source_positions * whisper::HOP_LENGTH;
+    let sample_rate = whisper::SAMPLE_RATE as f64;
+    let mut segments = Vec::new();
+    let mut prefix: Option<Vec<u32>> = None;
+    let mut seek = 0;
+
+    while seek < audio.len() {
+        let Some(region) = speech_regions.iter().find(|region| region.end_time > seek as f64 / sample_rate) else {
+            break;
+        };
+        seek = seek.max(((region.start_time - SPEECH_PADDING).max(0.0) * sample_rate) as usize);
+        if seek >= audio.len() {
+            break;
+        }
+        let end = (seek + window).min(audio.len());
+        let offset = seek as f64 / sample_rate;
+        let length = (end - seek) as f64 / sample_rate;
+
+        let mel = decoder.mel(&audio[seek..end])?;
+        let features = decoder.weights.encode(&mel)?;
+        if prefix.is_none() {
+            prefix = Some(decoder.prefix(&features, options)?);
+        }
+        let result = decoder.decode(&features, prefix.as_deref().unwrap_or_default())?;
+        debug!("Window at {:.1}s: {} tokens, avg logprob {:.2}", offset, result.tokens.len(), result.avg_logprob);
+
+        // Whisper's own silence rule: the decoder is sure there is no speech and unsure of its text
+        if result.no_speech_prob.is_some_and(|prob| prob > whisper::NO_SPEECH_THRESHOLD as f32)
+            && result.avg_logprob < whisper::LOGPROB_THRESHOLD as f32
+        {
+            seek = end;
+            continue;
+        }
+
+        let complete = end == audio.len() || result.tokens.last() == Some(&model.special_tokens.eot);
+        let (window_segments, consumed) = split_segments(
+            &result.tokens,
+            model.special_tokens.timestamp_begin(),
+            model.special_tokens.eot,
+            length,
+            complete,
+        );
+        for segment in window_segments {
+            let text = model
+                .tokenizer
+                .decode(&segment.tokens, true)
+                .map_err(|e| TranscribeError::Other(anyhow::anyhow!("Failed to decode tokens: {}", e)))?;
+            let text = text.trim();
+            if text.is_empty() {
+                continue;
+            }
+            let words = segment_words(&model.tokenizer, &segment, &result.scores)?
+                .into_iter()
+                .map(|word| WordTiming {
+                    start_time: offset + word.start_time,
+                    end_time: offset + word.end_time,
+                    no_speech_prob: result.no_speech_prob,
+                    ..word
+                })
+                .collect();
+            segments.push(TranscriptionSegment {
+                start_time: offset + segment.start,
+                end_time: offset + segment.end,
+                text: text.to_string(),
+                avg_logprob: Some(result.avg_logprob),
+                no_speech_prob: result.no_speech_prob,
+                words,
+                ..Default::default()
+            });
+        }
+        seek += ((consumed * sample_rate) as usize).clamp(1, end - seek);
+    }
+    Ok((segments, decoder.language.or_else(|| options.language.clone())))
+}
+
+/// Per-transcription decoding state; the weights are cloned so that each
+/// transcription has its own key/value caches
+struct Decoder<'a> {
+    weights: WhisperWeights,
+    config: &'a Config,
+    tokens: &'a SpecialTokens,
+    filters: Vec<f32>,
+    device: &'a Device,
+    /// `<|startofprev|>` and the prompt, fed ahead of every window
+    prompt: Vec<u32>,
+    /// Tokens that spell out the vocabulary terms, and what is added to their logits
+    biased_tokens: Vec<u32>,
+    logit_bias: f32,
+    /// Language code the prefix selected, once it is built
+    language: Option<String>,
+    temperature: f32,
+    /// State of the random generator used when sampling at a temperature
+    rng: u64,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(model: &'a LoadedModel, options: &DecodingOptions) -> Self {
+        let encode = |text: &str| {
+            model.tokenizer.encode(text, false).map(|encoding| encoding.get_ids().to_vec()).unwrap_or_default()
+        };
+        let text_token = |token: &u32| *token < model.special_tokens.eot;
+
+        let prompt_text = options.initial_prompt.clone().or_else(|| options.vocabulary.initial_prompt(None));
+        let mut prompt = Vec::new();
+        match (prompt_text, model.special_tokens.start_of_prev) {
+            (Some(text), Some(start_of_prev)) => {
+                // Whisper attends to at most half its context as prompt
+                let mut tokens: Vec<u32> = encode(&format!(" {}", text.trim())).into_iter().filter(text_token).collect();
+                let keep = (model.config.max_target_positions / 2).saturating_sub(1);
+                tokens.drain(..tokens.len().saturating_sub(keep));
+                if !tokens.is_empty() {
+                    prompt.push(start_of_prev);
+                    prompt.extend(tokens);
+                }
+            }
+            (Some(_), None) => debug!("The tokenizer has no {} token; ignoring the prompt", START_OF_PREV_TOKEN),
+            (None, _) => {}
+        }
+
+        let mut biased_tokens: Vec<u32> = Vec::new();
+        if options.logit_bias != 0.0 {
+            for term in &options.vocabulary.terms {
+                // Mid-sentence words start with a space in Whisper's vocabulary
+                biased_tokens.extend(encode(&format!(" {}", term)).iter().chain(&encode(term)).filter(|token| text_token(token)));
+            }
+            biased_tokens.sort_unstable();
+            biased_tokens.dedup();
+        }
+
+        Self {
+            weights: model.weights.clone(),
+            config: &model.config,
+            tokens: &model.special_tokens,
+            filters: mel_filters(model.config.num_mel_bins),
+            device: &model.device,
+            prompt,
+            biased_tokens,
+            logit_bias: options.logit_bias,
+            language: None,
+            temperature: options.temperature,
+            // A fixed seed keeps sampled transcriptions reproducible
+            rng: 0x5eed,
+        }
+    }
+
+    /// Log-mel features of one window, zero-padded to the encoder's input length
+    fn mel(&self, samples: &[f32]) -> Result<Tensor> {
+        let frames = 2 * self.config.max_source_positions;
+        let mel = log_mel_spectrogram(samples, &self.filters, frames);
+        Ok(Tensor::from_vec(mel, (1, self.config.num_mel_bins, frames), self.device)?)
+    }
+
+    /// The prompt, `<|startoftranscript|>`, the language and the task,
+    /// detecting the language from the first window unless one is given
+    fn prefix(&mut self, features: &Tensor, options: &DecodingOptions) -> Result<Vec<u32>> {
+        let mut prefix = vec![self.tokens.sot];
+        let languages = &self.tokens.languages;
+        match &options.language {
+            Some(code) if languages.is_empty() && code != "en" => {
+                return Err(TranscribeError::UnsupportedFormat(format!(
+                    "this model only transcribes English, not '{}'",
+                    code
+                )));
+            }
+            Some(code) if !languages.is_empty() => {
+                let (_, token) = languages.iter().find(|(language, _)| language == code).ok_or_else(|| {
+                    TranscribeError::UnsupportedFormat(format!("this model does not know the language '{}'", code))
+                })?;
+                prefix.push(*token);
+                self.language = Some(code.clone());
+            }
+            None if !languages.is_empty() => {
+                let input = Tensor::new(prefix.as_slice(), self.device)?.unsqueeze(0)?;
+                let hidden = self.weights.decode(&input, features, true)?;
+                let logits = self.weights.logits(&hidden, 0)?;
+                let (code, token) = languages
+                    .iter()
+                    .max_by(|a, b| logits[a.1 as usize].total_cmp(&logits[b.1 as usize]))
+                    .unwrap();
+                info!("🌐 Detected language: {}", code);
+                prefix.push(*token);
+                self.language = Some(code.clone());
+            }
+            // English-only models
+            _ => self.language = Some("en".to_string()),
+        }
+        if options.translate {
+            let translate = self.tokens.translate.ok_or_else(|| {
+                TranscribeError::UnsupportedFormat("this model cannot translate".to_string())
+            })?;
+            prefix.push(translate);
+        } else {
+            prefix.push(self.tokens.transcribe);
+        }
+        Ok([self.prompt.as_slice(), &prefix].concat())
+    }
+
+    /// Decoding of one window after `prefix`, with timestamps: greedy, or
+    /// sampled from the softmax of the logits over the temperature
+    fn decode(&mut self, features: &Tensor, prefix: &[u32]) -> Result<DecodingResult> {
+        let sot_index = prefix.iter().position(|token| *token == self.tokens.sot).unwrap_or(0);
+        let mut tokens = prefix.to_vec();
+        let mut scores = Vec::new();
+        let mut sum_logprob = 0.0f32;
+        let mut no_speech_prob = None;
+
+        for step in 0..self.config.max_target_positions / 2 {
+            if tokens.len() >= self.config.max_target_positions {
+                break;
+            }
+            let input = Tensor::new(tokens.as_slice(), self.device)?.unsqueeze(0)?;
+            let hidden = self.weights.decode(&input, features, step == 0)?;
+            if step == 0
+                && let Some(no_speech) = self.tokens.no_speech
+            {
+                let logits = self.weights.logits(&hidden, sot_index)?;
+                no_speech_prob = Some(log_softmax(&logits)[no_speech as usize].exp());
+            }
+
+            let mut logits = self.weights.logits(&hidden, tokens.len() - 1)?;
+            let timestamp = likely_timestamp(&logits, self.tokens.timestamp_begin() as usize);
+            apply_timestamp_rules(&mut logits, &tokens[prefix.len()..], self.tokens, &self.config.suppress_tokens);
+            for token in &self.biased_tokens {
+                if let Some(logit) = logits.get_mut(*token as usize) {
+                    *logit += self.logit_bias;
+                }
+            }
+            let next = if self.temperature > 0.0 {
+                sample(&logits, self.temperature, &mut self.rng)
+            } else {
+                argmax(&logits)
+            };
+            let Some(next) = next else {
+                tokens.push(self.tokens.eot);
+                break;
+            };
+            let logprob = log_softmax(&logits)[next];
+            sum_logprob += logprob;
+            scores.push(TokenScore { logprob, timestamp });
+            tokens.push(next as u32);
+            if next as u32 == self.tokens.eot {
+                break;
+            }
+        }
+
+        let tokens = tokens.split_off(prefix.len());
+        let text_tokens = tokens.iter().filter(|token| **token != self.tokens.eot).count();
+        Ok(DecodingResult {
+            avg_logprob: sum_logprob / (text_tokens + 1) as f32,
+            tokens,
+            scores,
+            no_speech_prob,
+        })
+    }
+}
+
+/// Whisper's logit filters: control tokens are never sampled, the first
+/// token is an early timestamp, timestamps come in pairs and never go back,
+/// and a timestamp is forced when all of them together beat any text token
+fn apply_timestamp_rules(logits: &mut [f32], sampled: &[u32], tokens: &SpecialTokens, suppress_tokens: &[u32]) {
+    let vocab = logits.len();
+    let eot = tokens.eot as usize;
+    let timestamp_begin = (tokens.timestamp_begin() as usize).min(vocab);
+    let mut suppress = |range: std::ops::Range<usize>| {
+        for logit in &mut logits[range.start.min(vocab)..range.end.min(vocab)] {
+            *logit = f32::NEG_INFINITY;
+        }
+    };
+
+    for token in suppress_tokens {
+        suppress(*token as usize..*token as usize + 1);
+    }
+    suppress(eot + 1..timestamp_begin);
+    if sampled.is_empty() {
+        suppress(eot..eot + 1);
+    }
+
+    let is_timestamp = |token: &u32| *token as usize >= timestamp_begin;
+    let last_was_timestamp = sampled.last().is_some_and(is_timestamp);
+    let penultimate_was_timestamp = sampled.len() < 2 || is_timestamp(&sampled[sampled.len() - 2]);
+    if last_was_timestamp {
+        if penultimate_was_timestamp {
+            suppress(timestamp_begin..vocab);
+        } else {
+            suppress(0..eot);
+        }
+    }
+    if let Some(last) = sampled.iter().rev().find(|token| is_timestamp(token)) {
+        let last = *last as usize;
+        let first_allowed = if last_was_timestamp && !penultimate_was_timestamp { last } else { last + 1 };
+        suppress(timestamp_begin..first_allowed);
+    }
+    if sampled.is_empty() {
+        suppress(0..timestamp_begin);
+        suppress(timestamp_begin + MAX_INITIAL_TIMESTAMP + 1..vocab);
+    }
+
+    let logprobs = log_softmax(logits);
+    let timestamp_logprob = log_sum_exp(&logprobs[timestamp_begin..]);
+    let max_text_logprob = logprobs[..timestamp_begin].iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if timestamp_logprob > max_text_logprob {
+        for logit in &mut logits[..timestamp_begin] {
+            *logit = f32::NEG_INFINITY;
+        }
+    }
+}
+
+/// Splits sampled tokens at their timestamps. Returns the segments and how
+/// many seconds of the window they cover: all of it when the window is
+/// `complete`, otherwise up to the last timestamp, so text cut off at the
+/// window's end is decoded again from the next window.
+fn split_segments(tokens: &[u32], timestamp_begin: u32, eot: u32, length: f64, complete: bool) -> (Vec<TokenSegment>, f64) {
+    let mut segments = Vec::new();
+    let mut start = 0.0;
+    let mut text = Vec::new();
+    let mut positions = Vec::new();
+
+    for (position, &token) in tokens.iter().enumerate() {
+        if token >= timestamp_begin {
+            let time = ((token - timestamp_begin) as f64 * TIMESTAMP_STEP).min(length);
+            if !text.is_empty() && time > start {
+                segments.push(TokenSegment {
+                    start,
+                    end: time,
+                    tokens: std::mem::take(&mut text),
+                    positions: std::mem::take(&mut positions),
+                });
+            }
+            if text.is_empty() {
+                start = time;
+            }
+        } else if token < eot {
+            text.push(token);
+            positions.push(position);
+        }
+    }
+
+    if text.is_empty() || complete || start == 0.0 {
+        if !text.is_empty() && length > start {
+            segments.push(TokenSegment { start, end: length, tokens: text, positions });
+        }
+        (segments, length)
+    } else {
+        (segments, start)
+    }
+}
+
+/// Splits a segment into its words, each scored by the average log-probability
+/// of its tokens. Times are relative to the window: a word starts where the
+/// decoder placed its first token, when it was confident of that and it keeps
+/// the words in order, and the words in between share the remaining time in
+/// proportion to their length.
+fn segment_words(tokenizer: &Tokenizer, segment: &TokenSegment, scores: &[TokenScore]) -> Result<Vec<WordTiming>> {
+    let decode = |tokens: &[u32]| {
+        tokenizer
+            .decode(tokens, true)
+            .map_err(|e| TranscribeError::Other(anyhow::anyhow!("Failed to decode tokens: {}", e)))
+    };
+    let text = decode(&segment.tokens)?;
+    let words: Vec<&str> = text.split_whitespace().collect();
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // A token belongs to the last word of the text decoded up to and including it
+    let mut logprobs = vec![Vec::new(); words.len()];
+    let mut hints = vec![None; words.len()];
+    for (index, position) in segment.positions.iter().enumerate() {
+        let word = decode(&segment.tokens[..=index])?.split_whitespace().count().clamp(1, words.len()) - 1;
+        let Some(score) = scores.get(*position) else { continue };
+        if logprobs[word].is_empty() {
+            hints[word] = score.timestamp;
+        }
+        logprobs[word].push(score.logprob);
+    }
+
+    let mut starts: Vec<Option<f64>> = vec![None; words.len()];
+    starts[0] = Some(segment.start);
+    let mut latest = segment.start;
+    for (start, hint) in starts.iter_mut().zip(&hints).skip(1) {
+        if let Some(time) = hint.filter(|time| *time > latest && *time < segment.end) {
+            *start = Some(time);
+            latest = time;
+        }
+    }
+    let weight = |word: &str| word.chars().count().max(1) as f64;
+    let mut anchor = 0;
+    while anchor < words.len() {
+        let next = (anchor + 1..words.len()).find(|&index| starts[index].is_some()).unwrap_or(words.len());
+        let from = starts[anchor].unwrap_or(segment.start);
+        let to = starts.get(next).copied().flatten().unwrap_or(segment.end);
+        let total: f64 = words[anchor..next].iter().map(|word| weight(word)).sum();
+        let mut time = from;
+        for index in anchor..next {
+            starts[index] = Some(time);
+            time += (to - from) * weight(words[index]) / total;
+        }
+        anchor = next;
+    }
+
+    Ok(words
+        .iter()
+        .enumerate()
+        .map(|(index, word)| WordTiming {
+            start_time: starts[index].unwrap_or(segment.start),
+            end_time: starts.get(index + 1).copied().flatten().unwrap_or(segment.end),
+            word: word.to_string(),
+            avg_logprob: (!logprobs[index].is_empty())
+                .then(|| logprobs[index].iter().sum::<f32>() / logprobs[index].len() as f32),
+            no_speech_prob: None,
+        })
+        .collect())
+}
+
+/// Time of the most likely timestamp token, relative to the window, when it
+/// is likely enough to say where the text token sampled at this step starts
+fn likely_timestamp(logits: &[f32], timestamp_begin: usize) -> Option<f64> {
+    let logprobs = log_softmax(logits);
+    let (index, logprob) = logprobs
+        .get(timestamp_begin..)?
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))?;
+    (logprob.exp() >= TOKEN_TIMESTAMP_THRESHOLD).then_some(index as f64 * TIMESTAMP_STEP)
+}
+
+fn argmax(logits: &[f32]) -> Option<usize> {
+    logits
+        .iter()
+        .enumerate()
+        .filter(|(_, logit)| logit.is_finite())
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .map(|(index, _)| index)
+}
+
+/// Draws a token from the softmax of `logits / temperature`
+fn sample(logits: &[f32], temperature: f32, rng: &mut u64) -> Option<usize> {
+    let scaled: Vec<f32> = logits.iter().map(|logit| logit / temperature).collect();
+    let total = log_sum_exp(&scaled);
+    if !total.is_finite() {
+        return argmax(logits);
+    }
+
+    // SplitMix64, for a uniform draw in [0, 1)
+    *rng = rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *rng;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    let mut remaining = ((z ^ (z >> 31)) >> 11) as f32 / (1u64 << 53) as f32;
+
+    let mut last = None;
+    for (index, logit) in scaled.iter().enumerate().filter(|(_, logit)| logit.is_finite()) {
+        remaining -= (logit - total).exp();
+        last = Some(index);
+        if remaining < 0.0 {
+            break;
+        }
+    }
+    last
+}
+
+fn log_sum_exp(values: &[f32]) -> f32 {
+    let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    if max == f32::NEG_INFINITY {
+        return max;
+    }
+    max + values.iter().map(|value| (value - max).exp()).sum::<f32>().ln()
+}
+
+fn log_softmax(logits: &[f32]) -> Vec<f32> {
+    let total = log_sum_exp(logits);
+    logits.iter().map(|logit| logit - total).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn special_tokens() -> SpecialTokens {
+        // Text 0..10, then the control tokens, then timestamps from 19
+        SpecialTokens {
+            sot: 11,
+            eot: 10,
+            transcribe: 14,
+            translate: Some(13),
+            no_timestamps: 18,
+            no_speech: Some(17),
+            start_of_prev: Some(16),
+            languages: vec![("en".into(), 12)],
+        }
+    }
+
+    #[test]
+    fn test_mel_filters() {
+        let filters = mel_filters(80);
+        assert_eq!(filters.len(), 80 * 201);
+        // The values in Whisper's mel_filters.npz
+        assert!((filters[1] - 0.024_862_59).abs() < 1e-6);
+        assert!((filters[201 + 1] - 0.001_990_821).abs() < 1e-6);
+        assert!(filters.chunks(201).all(|band| band.iter().any(|weight| *weight > 0.0)));
+        assert_eq!(mel_filters(128).len(), 128 * 201);
+    }
+
+    #[test]
+    fn test_log_mel_spectrogram() {
+        let filters = mel_filters(80);
+        // A 1kHz tone for half a second, then silence
+        let tone: Vec<f32> = (0..8000).map(|i| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 16000.0).sin()).collect();
+        let mel = log_mel_spectrogram(&tone, &filters, 100);
+
+        assert_eq!(mel.len(), 80 * 100);
+        let band = |frame: usize| (0..80).max_by(|a, b| mel[a * 100 + frame].total_cmp(&mel[b * 100 + frame])).unwrap();
+        // 1kHz is mel 15 of 45.2, so the 27th of 80 bands
+        assert_eq!(band(20), band(30));
+        assert_eq!(band(20), 26);
+        // Silence sits at the floor, 8 (2 after scaling) below the peak
+        let peak = mel.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        assert!((mel[5 * 100 + 90] - (peak - 2.0)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_split_segments() {
+        let tokens = special_tokens();
+        let ts = |seconds: f64| tokens.timestamp_begin() + (seconds / TIMESTAMP_STEP).round() as u32;
+
+        // <|0.00|> 1 2 <|1.00|><|1.00|> 3 <|2.00|> <|eot|>
+        let sampled = [ts(0.0), 1, 2, ts(1.0), ts(1.0), 3, ts(2.0), tokens.eot];
+        let (segments, consumed) = split_segments(&sampled, tokens.timestamp_begin(), tokens.eot, 30.0, true);
+        assert_eq!(
+            segments,
+            vec![
+                TokenSegment { start: 0.0, end: 1.0, tokens: vec![1, 2], positions: vec![1, 2] },
+                TokenSegment { start: 1.0, end: 2.0, tokens: vec![3], positions: vec![5] },
+            ]
+        );
+        assert_eq!(consumed, 30.0);
+
+        // Text cut off by the end of the window is left for the next one
+        let sampled = [ts(0.0), 1, ts(1.0), ts(1.0), 2, 3];
+        let (segments, consumed) = split_segments(&sampled, tokens.timestamp_begin(), tokens.eot, 30.0, false);
+        assert_eq!(segments.len(), 1);
+        assert_eq!(consumed, 1.0);
+
+        // ... unless this is the last window
+        let (segments, consumed) = split_segments(&sampled, tokens.timestamp_begin(), tokens.eot, 4.0, true);
+        assert_eq!(segments[1], TokenSegment { start: 1.0, end: 4.0, tokens: vec![2, 3], positions: vec![4, 5] });
+        assert_eq!(consumed, 4.0);
+    }
+
+    #[test]
+    fn test_segment_words() {
+        // Whitespace-separated words 0..6, as in a word-level tokenizer
+        let vocab: serde_json::Map<String, serde_json::Value> =
+            ["we", "are", "spiritual", "beings", "having", "a"].iter().enumerate().map(|(id, word)| (word.to_string(), id.into())).collect();
+        let tokenizer: Tokenizer = serde_json::json!({
+            "version": "1.0", "truncation": null, "padding": null, "added_tokens": [], "normalizer": null,
+            "pre_tokenizer": {"type": "Whitespace"}, "post_processor": null, "decoder": null,
+            "model": {"type": "WordLevel", "vocab": vocab, "unk_token": "a"},
+        })
+        .to_string()
+        .parse()
+        .unwrap();
+        let score = |logprob: f32, timestamp: Option<f64>| TokenScore { logprob, timestamp };
+        // <|2.00|> we are spiritual beings <|6.00|>
+        let scores = [
+            score(0.0, None),
+            score(-0.1, Some(2.0)),
+            score(-0.3, Some(1.0)),
+            score(-2.0, Some(3.0)),
+            score(-0.2, None),
+        ];
+        let segment = TokenSegment { start: 2.0, end: 6.0, tokens: vec![0, 1, 2, 3], positions: vec![1, 2, 3, 4] };
+
+        let words = segment_words(&tokenizer, &segment, &scores).unwrap();
+        let summary: Vec<_> = words.iter().map(|word| (word.word.as_str(), word.start_time, word.end_time)).collect();
+        // "are" has no usable hint (it goes back in time), so it shares 2.0..3.0 with "we";
+        // "spiritual" starts at its hint and "beings" splits what is left by length
+        assert_eq!(summary[0], ("we", 2.0, 2.4));
+        assert_eq!(summary[1], ("are", 2.4, 3.0));
+        assert_eq!(summary[2].1, 3.0);
+        assert!((summary[2].2 - (3.0 + 3.0 * 9.0 / 15.0)).abs() < 1e-9);
+        assert_eq!(summary[3].2, 6.0);
+        assert_eq!(words[2].avg_logprob, Some(-2.0));
+    }
+
+    #[test]
+    fn test_likely_timestamp() {
+        let mut logits = vec![0.0f32; 30];
+        logits[19 + 7] = 8.0;
+        assert_eq!(likely_timestamp(&logits, 19), Some(7.0 * TIMESTAMP_STEP));
+        logits[19 + 7] = -20.0;
+        logits[3] = 20.0;
+        assert_eq!(likely_timestamp(&logits, 19), None);
+    }
+
+    #[test]
+    fn test_sample() {
+        let logits = [0.0, 2.0, f32::NEG_INFINITY, 1.0];
+        let mut rng = 1;
+        let mut counts = [0usize; 4];
+        for _ in 0..2000 {
+            counts[sample(&logits, 1.0, &mut rng).unwrap()] += 1;
+        }
+        // Softmax of 0, 2, 1: 0.09, 0.67, 0.24; suppressed tokens are never drawn
+        assert_eq!(counts[2], 0);
+        assert!((1200..1500).contains(&counts[1]), "{:?}", counts);
+        assert!(counts[3] > counts[0] && counts[0] > 100);
+        // A low temperature is close to greedy
+        assert!((0..100).all(|_| sample(&logits, 0.05, &mut rng) == Some(1)));
+    }
+
+    #[test]
+    fn test_timestamp_rules() {
+        let tokens = special_tokens();
+        let ts = tokens.timestamp_begin();
+        let logits = || vec![0.0f32; 40];
+
+        // The first token is a timestamp
+        let mut first = logits();
+        apply_timestamp_rules(&mut first, &[], &tokens, &[]);
+        assert!(first[..ts as usize].iter().all(|logit| logit.is_infinite()));
+        assert!(first[ts as usize..].iter().all(|logit| logit.is_finite()));
+
+        // An open pair is closed by a timestamp no earlier than the opening one, or ends
+        let mut open = logits();
+        open[tokens.eot as usize] = 10.0;
+        apply_timestamp_rules(&mut open, &[ts + 5, 1, ts + 7], &tokens, &[]);
+        assert!(open[..tokens.eot as usize].iter().all(|logit| logit.is_infinite()));
+        assert!(open[tokens.eot as usize].is_finite());
+        assert!(open[ts as usize + 6].is_infinite());
+        assert!(open[ts as usize + 7].is_finite());
+
+        // A closed pair is followed by text, and control tokens are never sampled
+        let mut closed = logits();
+        apply_timestamp_rules(&mut closed, &[ts, 1, ts + 7, ts + 7], &tokens, &[3]);
+        assert!(closed[ts as usize..].iter().all(|logit| logit.is_infinite()));
+        assert!(closed[3].is_infinite() && closed[4].is_finite());
+        assert!(closed[tokens.sot as usize].is_infinite());
+    }
+}
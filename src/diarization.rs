@@ -0,0 +1,446 @@
+use rustfft::{num_complex::Complex, FftPlanner};
+use tracing::{debug, info};
+use crate::speakers::SpeakerDatabase;
+use crate::transcription::TranscriptionSegment;
+use crate::vad::{detect_speech_regions, SpeechRegion, FRAME_HOP, FRAME_LENGTH, SAMPLE_RATE};
+
+/// FFT size and number of mel bands used for speaker embeddings
+const FFT_SIZE: usize = 512;
+const MEL_BANDS: usize = 24;
+
+/// Maximum length of audio summarised by one embedding (seconds)
+const EMBEDDING_WINDOW: f64 = 1.5;
+
+/// Cosine distance above which clusters are no longer merged when estimating speaker count
+const CLUSTER_THRESHOLD: f32 = 0.5;
+
+/// Runs VAD, speaker embedding and clustering over 16kHz mono samples and
+/// writes speaker labels onto the overlapping segments. Clusters that match an
+/// enrolled voice are labelled by name, the rest as `Speaker N`.
+pub fn diarize(
+    audio_data: &[f32],
+    segments: &mut [TranscriptionSegment],
+    num_speakers: Option<usize>,
+    known_speakers: &SpeakerDatabase,
+) {
+    let regions = detect_speech_regions(audio_data);
+    info!("🗣️  Detected {} speech regions", regions.len());
+
+    let windows = split_regions(&regions, EMBEDDING_WINDOW);
+    if windows.is_empty() {
+        info!("No speech detected, skipping speaker labelling");
+        return;
+    }
+
+    let raw_embeddings = window_embeddings(audio_data, &windows);
+    let mut embeddings = raw_embeddings.clone();
+    normalize_embeddings(&mut embeddings);
+
+    let labels = cluster_embeddings(&embeddings, num_speakers);
+    let speaker_count = labels.iter().max().map_or(0, |max| max + 1);
+    info!("👥 Identified {} speaker(s)", speaker_count);
+
+    let names = name_clusters(&raw_embeddings, &labels, speaker_count, known_speakers);
+    assign_speakers(segments, &windows, &labels, &names);
+}
+
+/// Computes the voice signature of a reference clip for speaker enrollment
+pub fn voice_embedding(audio_data: &[f32]) -> Option<Vec<f32>> {
+    let windows = split_regions(&detect_speech_regions(audio_data), EMBEDDING_WINDOW);
+    if windows.is_empty() {
+        return None;
+    }
+    Some(voice_signature(&window_embeddings(audio_data, &windows)))
+}
+
+fn window_embeddings(audio_data: &[f32], windows: &[SpeechRegion]) -> Vec<Vec<f32>> {
+    windows
+        .iter()
+        .map(|window| {
+            let start = (window.start_time * SAMPLE_RATE as f64) as usize;
+            let end = ((window.end_time * SAMPLE_RATE as f64) as usize).min(audio_data.len());
+            compute_embedding(&audio_data[start..end])
+        })
+        .collect()
+}
+
+/// Averages raw window embeddings and removes overall loudness, leaving the
+/// spectral shape of the voice so it can be compared across recordings
+fn voice_signature(embeddings: &[Vec<f32>]) -> Vec<f32> {
+    let count = embeddings.len() as f32;
+    let mut signature: Vec<f32> = (0..MEL_BANDS * 2)
+        .map(|d| embeddings.iter().map(|e| e[d]).sum::<f32>() / count)
+        .collect();
+
+    let gain = signature[..MEL_BANDS].iter().sum::<f32>() / MEL_BANDS as f32;
+    for value in &mut signature[..MEL_BANDS] {
+        *value -= gain;
+    }
+    signature
+}
+
+/// Picks a display name for every cluster, preferring enrolled speaker names.
+/// Each enrolled speaker is given to at most one cluster, the closest one.
+fn name_clusters(
+    raw_embeddings: &[Vec<f32>],
+    labels: &[usize],
+    speaker_count: usize,
+    known_speakers: &SpeakerDatabase,
+) -> Vec<String> {
+    let mut matches: Vec<(usize, &str, f32)> = Vec::new();
+    if !known_speakers.is_empty() {
+        for cluster in 0..speaker_count {
+            let members: Vec<Vec<f32>> = raw_embeddings
+                .iter()
+                .zip(labels)
+                .filter(|(_, label)| **label == cluster)
+                .map(|(embedding, _)| embedding.clone())
+                .collect();
+            if let Some((name, distance)) = known_speakers.identify(&voice_signature(&members)) {
+                matches.push((cluster, name, distance));
+            }
+        }
+    }
+    matches.sort_by(|a, b| a.2.total_cmp(&b.2));
+
+    let mut names: Vec<Option<String>> = vec![None; speaker_count];
+    for (cluster, name, distance) in matches {
+        if names[cluster].is_none() && !names.iter().flatten().any(|taken| taken == name) {
+            debug!("Cluster {} matches enrolled speaker {} (distance {:.3})", cluster + 1, name, distance);
+            names[cluster] = Some(name.to_string());
+        }
+    }
+
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(cluster, name)| name.unwrap_or_else(|| format!("Speaker {}", cluster + 1)))
+        .collect()
+}
+
+/// Splits speech regions into windows no longer than `max_length` seconds
+fn split_regions(regions: &[SpeechRegion], max_length: f64) -> Vec<SpeechRegion> {
+    let mut windows = Vec::new();
+    for region in regions {
+        let length = region.end_time - region.start_time;
+        let pieces = (length / max_length).ceil().max(1.0) as usize;
+        let piece_length = length / pieces as f64;
+        for piece in 0..pieces {
+            windows.push(SpeechRegion {
+                start_time: region.start_time + piece as f64 * piece_length,
+                end_time: region.start_time + (piece + 1) as f64 * piece_length,
+            });
+        }
+    }
+    windows
+}
+
+/// Computes a speaker embedding as the mean and deviation of log mel band energies
+pub fn compute_embedding(samples: &[f32]) -> Vec<f32> {
+    let mut planner = FftPlanner::<f32>::new();
+    let fft = planner.plan_fft_forward(FFT_SIZE);
+    let filterbank = mel_filterbank();
+    let window: Vec<f32> = (0..FRAME_LENGTH)
+        .map(|n| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * n as f32 / (FRAME_LENGTH - 1) as f32).cos())
+        .collect();
+
+    let mut frames: Vec<Vec<f32>> = Vec::new();
+    let mut buffer = vec![Complex::new(0.0f32, 0.0); FFT_SIZE];
+    for frame in samples.windows(FRAME_LENGTH).step_by(FRAME_HOP) {
+        for (slot, value) in buffer.iter_mut().enumerate() {
+            let sample = if slot < FRAME_LENGTH { frame[slot] * window[slot] } else { 0.0 };
+            *value = Complex::new(sample, 0.0);
+        }
+        fft.process(&mut buffer);
+
+        let power: Vec<f32> = buffer[..FFT_SIZE / 2 + 1].iter().map(|c| c.norm_sqr()).collect();
+        frames.push(
+            filterbank
+                .iter()
+                .map(|band| (band.iter().zip(&power).map(|(w, p)| w * p).sum::<f32>() + 1e-10).ln())
+                .collect(),
+        );
+    }
+
+    let mut embedding = vec![0.0f32; MEL_BANDS * 2];
+    if frames.is_empty() {
+        return embedding;
+    }
+
+    let count = frames.len() as f32;
+    for band in 0..MEL_BANDS {
+        let mean = frames.iter().map(|f| f[band]).sum::<f32>() / count;
+        let variance = frames.iter().map(|f| (f[band] - mean).powi(2)).sum::<f32>() / count;
+        embedding[band] = mean;
+        embedding[MEL_BANDS + band] = variance.sqrt();
+    }
+    embedding
+}
+
+/// Triangular mel filters spanning 80Hz..7600Hz over the positive FFT bins
+fn mel_filterbank() -> Vec<Vec<f32>> {
+    let to_mel = |hz: f32| 2595.0 * (1.0 + hz / 700.0).log10();
+    let to_hz = |mel: f32| 700.0 * (10f32.powf(mel / 2595.0) - 1.0);
+    let (low, high) = (to_mel(80.0), to_mel(7600.0));
+    let bin_hz = SAMPLE_RATE as f32 / FFT_SIZE as f32;
+
+    let edges: Vec<f32> = (0..MEL_BANDS + 2)
+        .map(|i| to_hz(low + (high - low) * i as f32 / (MEL_BANDS + 1) as f32) / bin_hz)
+        .collect();
+
+    (0..MEL_BANDS)
+        .map(|band| {
+            let (left, centre, right) = (edges[band], edges[band + 1], edges[band + 2]);
+            (0..FFT_SIZE / 2 + 1)
+                .map(|bin| {
+                    let bin = bin as f32;
+                    if bin <= left || bin >= right {
+                        0.0
+                    } else if bin <= centre {
+                        (bin - left) / (centre - left)
+                    } else {
+                        (right - bin) / (right - centre)
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Removes the recording-wide average so embeddings describe what differs between voices
+fn normalize_embeddings(embeddings: &mut [Vec<f32>]) {
+    let Some(dims) = embeddings.first().map(Vec::len) else {
+        return;
+    };
+    let count = embeddings.len() as f32;
+    let mean: Vec<f32> = (0..dims)
+        .map(|d| embeddings.iter().map(|e| e[d]).sum::<f32>() / count)
+        .collect();
+    for embedding in embeddings.iter_mut() {
+        for (value, m) in embedding.iter_mut().zip(&mean) {
+            *value -= m;
+        }
+    }
+}
+
+/// Cosine distance between two embeddings (0 = identical direction, 2 = opposite).
+/// A zero embedding has no direction, so it counts as unrelated to everything.
+pub fn cosine_distance(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 1.0;
+    }
+    1.0 - dot / (norm_a * norm_b)
+}
+
+/// Average-linkage agglomerative clustering. Merges until `num_speakers` clusters
+/// remain, or, when no count is given, until the closest clusters are further
+/// apart than `CLUSTER_THRESHOLD`. Labels are numbered in order of first appearance.
+pub fn cluster_embeddings(embeddings: &[Vec<f32>], num_speakers: Option<usize>) -> Vec<usize> {
+    let count = embeddings.len();
+    let target = num_speakers.unwrap_or(1).max(1);
+
+    // Average linkage never merges at a smaller distance than an earlier merge,
+    // so cutting the full dendrogram is the same as stopping early
+    let mut parent: Vec<usize> = (0..count).collect();
+    fn root(parent: &mut [usize], mut node: usize) -> usize {
+        while parent[node] != node {
+            parent[node] = parent[parent[node]];
+            node = parent[node];
+        }
+        node
+    }
+    for (a, b, distance) in average_linkage(embeddings).into_iter().take(count.saturating_sub(target)) {
+        if num_speakers.is_none() && distance > CLUSTER_THRESHOLD {
+            break;
+        }
+        let (a, b) = (root(&mut parent, a), root(&mut parent, b));
+        parent[a.max(b)] = a.min(b);
+    }
+
+    let mut labels = vec![0; count];
+    let mut roots = Vec::new();
+    for (index, label) in labels.iter_mut().enumerate() {
+        let cluster = root(&mut parent, index);
+        *label = roots.iter().position(|r| *r == cluster).unwrap_or_else(|| {
+            roots.push(cluster);
+            roots.len() - 1
+        });
+    }
+    labels
+}
+
+/// Every merge of average-linkage clustering as `(member, member, distance)`,
+/// closest first. Uses the nearest-neighbour chain over a distance matrix with
+/// Lance–Williams updates: O(n²) time and memory.
+fn average_linkage(embeddings: &[Vec<f32>]) -> Vec<(usize, usize, f32)> {
+    let count = embeddings.len();
+    let mut distances = vec![0.0f32; count * count];
+    for a in 0..count {
+        for b in a + 1..count {
+            let distance = cosine_distance(&embeddings[a], &embeddings[b]);
+            distances[a * count + b] = distance;
+            distances[b * count + a] = distance;
+        }
+    }
+
+    // A cluster lives on at the index of one of its members
+    let mut sizes = vec![1usize; count];
+    let mut active = vec![true; count];
+    let mut merges = Vec::with_capacity(count.saturating_sub(1));
+    let mut chain: Vec<usize> = Vec::new();
+
+    while merges.len() + 1 < count {
+        if chain.is_empty() {
+            chain.extend(active.iter().position(|is_active| *is_active));
+        }
+        // Grow the chain until two clusters are each other's nearest neighbour,
+        // preferring the previous link on ties so that the chain terminates
+        let (a, b) = loop {
+            let current = chain[chain.len() - 1];
+            let previous = chain.len().checked_sub(2).map(|index| chain[index]);
+            let mut nearest = previous;
+            for candidate in (0..count).filter(|&candidate| active[candidate] && candidate != current) {
+                let distance = distances[current * count + candidate];
+                if nearest.is_none_or(|best| distance < distances[current * count + best]) {
+                    nearest = Some(candidate);
+                }
+            }
+            let nearest = nearest.expect("at least two active clusters");
+            if Some(nearest) == previous {
+                chain.truncate(chain.len() - 2);
+                break (current, nearest);
+            }
+            chain.push(nearest);
+        };
+
+        let (keep, gone) = (a.min(b), a.max(b));
+        merges.push((keep, gone, distances[keep * count + gone]));
+        let (size_keep, size_gone) = (sizes[keep] as f32, sizes[gone] as f32);
+        for other in (0..count).filter(|&other| active[other] && other != keep && other != gone) {
+            let distance = (size_keep * distances[other * count + keep] + size_gone * distances[other * count + gone])
+                / (size_keep + size_gone);
+            distances[other * count + keep] = distance;
+            distances[keep * count + other] = distance;
+        }
+        sizes[keep] += sizes[gone];
+        active[gone] = false;
+    }
+
+    merges.sort_by(|a, b| a.2.total_cmp(&b.2));
+    merges
+}
+
+/// Gives each segment the name of the speaker it overlaps the most
+fn assign_speakers(
+    segments: &mut [TranscriptionSegment],
+    windows: &[SpeechRegion],
+    labels: &[usize],
+    names: &[String],
+) {
+    let speaker_count = names.len();
+    for segment in segments.iter_mut() {
+        let mut overlap = vec![0.0f64; speaker_count];
+        for (window, &label) in windows.iter().zip(labels) {
+            let shared = segment.end_time.min(window.end_time) - segment.start_time.max(window.start_time);
+            if shared > 0.0 {
+                overlap[label] += shared;
+            }
+        }
+
+        segment.speaker = overlap
+            .iter()
+            .enumerate()
+            .filter(|(_, time)| **time > 0.0)
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(label, _)| names[label].clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequency: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * frequency * n as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    fn silence(seconds: f32) -> Vec<f32> {
+        vec![0.0; (seconds * SAMPLE_RATE as f32) as usize]
+    }
+
+    fn two_voice_recording() -> Vec<f32> {
+        [tone(200.0, 2.0), silence(1.0), tone(3000.0, 2.0), silence(1.0), tone(200.0, 2.0)].concat()
+    }
+
+    fn segment(start_time: f64, end_time: f64) -> TranscriptionSegment {
+        TranscriptionSegment {
+            start_time,
+            end_time,
+            text: "text".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_clustering_with_fixed_count() {
+        let embeddings = vec![vec![1.0, 0.0], vec![0.9, 0.1], vec![0.0, 1.0], vec![1.0, 0.05]];
+        assert_eq!(cluster_embeddings(&embeddings, Some(2)), vec![0, 0, 1, 0]);
+        assert_eq!(cluster_embeddings(&embeddings, Some(1)), vec![0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_zero_embeddings_are_not_merged() {
+        assert_eq!(cosine_distance(&[0.0, 0.0], &[1.0, 0.0]), 1.0);
+        let embeddings = vec![vec![1.0, 0.0], vec![0.0, 0.0], vec![0.0, 0.0], vec![0.98, 0.02]];
+        assert_eq!(cluster_embeddings(&embeddings, None), vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn test_clustering_many_windows() {
+        // Two voices alternating over an hour of 1.5s windows
+        let embeddings: Vec<Vec<f32>> = (0..2400)
+            .map(|i| if (i / 20) % 2 == 0 { vec![1.0, 0.01 * (i % 7) as f32] } else { vec![0.01 * (i % 5) as f32, 1.0] })
+            .collect();
+        let labels = cluster_embeddings(&embeddings, None);
+        assert_eq!(labels.iter().max(), Some(&1));
+        assert!(labels.iter().enumerate().all(|(i, label)| *label == (i / 20) % 2));
+    }
+
+    #[test]
+    fn test_clustering_estimates_speaker_count() {
+        let embeddings = vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.95, 0.05], vec![0.05, 0.95]];
+        assert_eq!(cluster_embeddings(&embeddings, None), vec![0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn test_diarize_labels_alternating_speakers() {
+        let mut segments = vec![segment(0.0, 2.0), segment(3.0, 5.0), segment(6.0, 8.0)];
+        diarize(&two_voice_recording(), &mut segments, None, &SpeakerDatabase::default());
+
+        let speakers: Vec<_> = segments.iter().map(|s| s.speaker.as_deref()).collect();
+        assert_eq!(speakers, vec![Some("Speaker 1"), Some("Speaker 2"), Some("Speaker 1")]);
+    }
+
+    #[test]
+    fn test_diarize_uses_enrolled_names() {
+        let mut database = SpeakerDatabase::default();
+        database.enroll("Host", voice_embedding(&tone(3000.0, 3.0)).unwrap());
+
+        let mut segments = vec![segment(0.0, 2.0), segment(3.0, 5.0), segment(6.0, 8.0)];
+        diarize(&two_voice_recording(), &mut segments, None, &database);
+
+        let speakers: Vec<_> = segments.iter().map(|s| s.speaker.as_deref()).collect();
+        assert_eq!(speakers, vec![Some("Speaker 1"), Some("Host"), Some("Speaker 1")]);
+    }
+
+    #[test]
+    fn test_voice_embedding_requires_speech() {
+        assert!(voice_embedding(&silence(2.0)).is_none());
+        assert!(voice_embedding(&tone(200.0, 2.0)).is_some());
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Errors returned at the library boundary, so callers can tell a missing
+/// model from a corrupt input without parsing messages
+#[derive(Debug, Error)]
+pub enum TranscribeError {
+    #[error("Input file does not exist: {0:?}")]
+    MissingInput(PathBuf),
+
+    #[error("FFmpeg is not installed or not on PATH")]
+    FfmpegNotInstalled,
+
+    #[error("FFmpeg failed to extract audio: {stderr}")]
+    FfmpegFailed { stderr: String },
+
+    #[error("Model file not found: {0:?}")]
+    ModelNotFound(PathBuf),
+
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(String),
+
+    #[error("Failed to decode audio: {0}")]
+    Decode(String),
+
+    #[error("Invalid input: {0}")]
+    InvalidInput(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+pub type Result<T, E = TranscribeError> = std::result::Result<T, E>;
+
+impl TranscribeError {
+    /// Process exit code for the CLI. Only `FfmpegFailed` and `Io` (codes 6 and 9)
+    /// can succeed when retried; the rest need the input or setup to change.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::MissingInput(_) => 3,
+            Self::UnsupportedFormat(_) => 4,
+            Self::FfmpegNotInstalled => 5,
+            Self::FfmpegFailed { .. } => 6,
+            Self::ModelNotFound(_) => 7,
+            Self::Decode(_) => 8,
+            Self::Io(_) => 9,
+            Self::InvalidInput(_) => 10,
+            Self::Other(_) => 1,
+        }
+    }
+
+    /// Whether running the same job again might succeed
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::FfmpegFailed { .. } | Self::Io(_))
+    }
+}
+
+impl From<hound::Error> for TranscribeError {
+    fn from(error: hound::Error) -> Self {
+        match error {
+            hound::Error::IoError(e) => Self::Io(e),
+            hound::Error::Unsupported => Self::UnsupportedFormat("WAV encoding not supported".to_string()),
+            other => Self::Decode(other.to_string()),
+        }
+    }
+}
+
+impl From<candle_core::Error> for TranscribeError {
+    fn from(error: candle_core::Error) -> Self {
+        Self::Decode(error.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            TranscribeError::MissingInput(PathBuf::from("talk.mp4")),
+            TranscribeError::UnsupportedFormat("docx".to_string()),
+            TranscribeError::FfmpegNotInstalled,
+            TranscribeError::FfmpegFailed { stderr: String::new() },
+            TranscribeError::ModelNotFound(PathBuf::from("models/ggml-tiny.bin")),
+            TranscribeError::Decode("truncated".to_string()),
+            TranscribeError::Io(std::io::Error::other("disk full")),
+            TranscribeError::InvalidInput("-5".to_string()),
+            TranscribeError::Other(anyhow::anyhow!("unexpected")),
+        ];
+
+        let mut codes: Vec<u8> = errors.iter().map(TranscribeError::exit_code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        assert!(!codes.contains(&0) && !codes.contains(&2));
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        assert!(TranscribeError::FfmpegFailed { stderr: "Connection reset".to_string() }.is_retryable());
+        assert!(!TranscribeError::ModelNotFound(PathBuf::from("missing.bin")).is_retryable());
+    }
+}
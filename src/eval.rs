@@ -0,0 +1,401 @@
+use serde::Serialize;
+use crate::text;
+use crate::transcription::{Performance, TranscriptionResult};
+
+/// Text normalization applied to both sides before comparing
+#[derive(Debug, Clone)]
+pub struct NormalizeOptions {
+    pub lowercase: bool,
+    pub strip_punctuation: bool,
+    /// Spell out digits ("21" → "twenty one") so both spellings compare equal
+    pub spell_numbers: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self {
+            lowercase: true,
+            strip_punctuation: true,
+            spell_numbers: true,
+        }
+    }
+}
+
+/// Accuracy of a hypothesis transcript against a reference
+#[derive(Debug, Clone, Serialize)]
+pub struct Evaluation {
+    pub reference_words: usize,
+    pub hypothesis_words: usize,
+    pub substitutions: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// Word error rate: (S + I + D) / reference words
+    pub wer: f64,
+    /// Character error rate over the normalized text
+    pub cer: f64,
+    /// Only when both transcripts carry timestamps
+    pub timing: Option<TimingDeviation>,
+    /// Speed and memory, when the hypothesis was transcribed for the evaluation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub performance: Option<Performance>,
+    #[serde(skip)]
+    pub alignment: Vec<Edit>,
+}
+
+/// How far matched words are from their reference time, in seconds
+#[derive(Debug, Clone, Serialize)]
+pub struct TimingDeviation {
+    pub matched_words: usize,
+    /// Positive when the hypothesis is late
+    pub mean_offset: f64,
+    pub mean_absolute: f64,
+    pub max_absolute: f64,
+}
+
+/// One step of the word alignment
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Match(String),
+    Substitute { reference: String, hypothesis: String },
+    Insert(String),
+    Delete(String),
+}
+
+/// Aligns `hypothesis` to `reference` word by word after normalization
+pub fn evaluate(reference: &TranscriptionResult, hypothesis: &TranscriptionResult, options: &NormalizeOptions) -> Evaluation {
+    let (reference_words, reference_times) = timed_words(reference, options);
+    let (hypothesis_words, hypothesis_times) = timed_words(hypothesis, options);
+    let steps = align(&reference_words, &hypothesis_words);
+
+    let mut evaluation = Evaluation {
+        reference_words: reference_words.len(),
+        hypothesis_words: hypothesis_words.len(),
+        substitutions: 0,
+        insertions: 0,
+        deletions: 0,
+        wer: 0.0,
+        cer: 0.0,
+        timing: None,
+        performance: None,
+        alignment: Vec::with_capacity(steps.len()),
+    };
+    let mut offsets = Vec::new();
+    for step in steps {
+        let edit = match step {
+            (Some(r), Some(h)) if reference_words[r] == hypothesis_words[h] => {
+                if let (Some(reference_times), Some(hypothesis_times)) = (&reference_times, &hypothesis_times) {
+                    offsets.push(hypothesis_times[h] - reference_times[r]);
+                }
+                Edit::Match(reference_words[r].clone())
+            }
+            (Some(r), Some(h)) => {
+                evaluation.substitutions += 1;
+                Edit::Substitute { reference: reference_words[r].clone(), hypothesis: hypothesis_words[h].clone() }
+            }
+            (None, Some(h)) => {
+                evaluation.insertions += 1;
+                Edit::Insert(hypothesis_words[h].clone())
+            }
+            (Some(r), None) => {
+                evaluation.deletions += 1;
+                Edit::Delete(reference_words[r].clone())
+            }
+            (None, None) => unreachable!("alignment steps consume at least one word"),
+        };
+        evaluation.alignment.push(edit);
+    }
+
+    let errors = evaluation.substitutions + evaluation.insertions + evaluation.deletions;
+    evaluation.wer = error_rate(errors, reference_words.len());
+    let reference_chars: Vec<char> = reference_words.join(" ").chars().collect();
+    let hypothesis_chars: Vec<char> = hypothesis_words.join(" ").chars().collect();
+    evaluation.cer = error_rate(text::edit_distance(&reference_chars, &hypothesis_chars), reference_chars.len());
+
+    if !offsets.is_empty() {
+        let count = offsets.len() as f64;
+        evaluation.timing = Some(TimingDeviation {
+            matched_words: offsets.len(),
+            mean_offset: offsets.iter().sum::<f64>() / count,
+            mean_absolute: offsets.iter().map(|offset| offset.abs()).sum::<f64>() / count,
+            max_absolute: offsets.iter().fold(0.0, |max, offset| offset.abs().max(max)),
+        });
+    }
+    evaluation
+}
+
+fn error_rate(errors: usize, total: usize) -> f64 {
+    match total {
+        0 if errors == 0 => 0.0,
+        0 => 1.0,
+        _ => errors as f64 / total as f64,
+    }
+}
+
+/// Normalized words of a transcript and, when it has segments, the time each
+/// word is spoken: the middle of its word timing, or its share of the segment
+fn timed_words(transcription: &TranscriptionResult, options: &NormalizeOptions) -> (Vec<String>, Option<Vec<f64>>) {
+    if transcription.segments.is_empty() {
+        return (normalize(&transcription.full_text, options), None);
+    }
+
+    let mut words = Vec::new();
+    let mut times = Vec::new();
+    for segment in &transcription.segments {
+        if segment.words.is_empty() {
+            let segment_words = normalize(&segment.text, options);
+            let step = (segment.end_time - segment.start_time) / segment_words.len().max(1) as f64;
+            times.extend((0..segment_words.len()).map(|index| segment.start_time + (index as f64 + 0.5) * step));
+            words.extend(segment_words);
+        } else {
+            for word in &segment.words {
+                let tokens = normalize(&word.word, options);
+                times.extend(std::iter::repeat_n((word.start_time + word.end_time) / 2.0, tokens.len()));
+                words.extend(tokens);
+            }
+        }
+    }
+    (words, Some(times))
+}
+
+/// Words of `text` after the enabled normalization steps
+pub fn normalize(text: &str, options: &NormalizeOptions) -> Vec<String> {
+    let mut text = if options.spell_numbers { text::spell_numbers(text) } else { text.to_string() };
+    if options.lowercase {
+        text = text.to_lowercase();
+    }
+    if options.strip_punctuation {
+        text = text
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '\'' || c == '’' { c } else { ' ' })
+            .collect();
+        return text
+            .split_whitespace()
+            .map(|word| word.trim_matches(['\'', '’']).replace('’', "'"))
+            .filter(|word| !word.is_empty())
+            .collect();
+    }
+    text.split_whitespace().map(str::to_string).collect()
+}
+
+/// Cells of the full edit table below which a piece of the alignment is
+/// backtracked directly instead of being split further
+const FULL_TABLE_CELLS: usize = 4096;
+
+/// Minimum-edit alignment as (reference index, hypothesis index) pairs;
+/// `None` on one side is an insertion or deletion.
+///
+/// Hirschberg's method keeps memory linear for long transcripts: two-row
+/// distances from both ends find where the middle reference word meets the
+/// hypothesis, and only pieces small enough are backtracked through a full
+/// table.
+fn align<T: PartialEq>(reference: &[T], hypothesis: &[T]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut steps = Vec::with_capacity(reference.len().max(hypothesis.len()));
+    align_into(reference, hypothesis, (0, 0), &mut steps);
+    steps
+}
+
+fn align_into<T: PartialEq>(
+    reference: &[T],
+    hypothesis: &[T],
+    offset: (usize, usize),
+    steps: &mut Vec<(Option<usize>, Option<usize>)>,
+) {
+    let (n, m) = (reference.len(), hypothesis.len());
+    if n <= 1 || (n + 1) * (m + 1) <= FULL_TABLE_CELLS {
+        backtrack(reference, hypothesis, offset, steps);
+        return;
+    }
+
+    let middle = n / 2;
+    let forward = text::edit_distance_row(&reference[..middle], hypothesis);
+    let reversed_reference: Vec<&T> = reference[middle..].iter().rev().collect();
+    let reversed_hypothesis: Vec<&T> = hypothesis.iter().rev().collect();
+    let backward = text::edit_distance_row(&reversed_reference, &reversed_hypothesis);
+    let split = (0..=m).min_by_key(|&j| forward[j] + backward[m - j]).unwrap();
+
+    align_into(&reference[..middle], &hypothesis[..split], offset, steps);
+    align_into(&reference[middle..], &hypothesis[split..], (offset.0 + middle, offset.1 + split), steps);
+}
+
+/// Appends the alignment of a small piece, read back from its full edit table
+fn backtrack<T: PartialEq>(
+    reference: &[T],
+    hypothesis: &[T],
+    offset: (usize, usize),
+    steps: &mut Vec<(Option<usize>, Option<usize>)>,
+) {
+    let (n, m) = (reference.len(), hypothesis.len());
+    let mut costs = vec![vec![0u32; m + 1]; n + 1];
+    for (i, row) in costs.iter_mut().enumerate() {
+        row[0] = i as u32;
+    }
+    for (j, cost) in costs[0].iter_mut().enumerate() {
+        *cost = j as u32;
+    }
+    for i in 1..=n {
+        for j in 1..=m {
+            let substitution = costs[i - 1][j - 1] + u32::from(reference[i - 1] != hypothesis[j - 1]);
+            costs[i][j] = substitution.min(costs[i - 1][j] + 1).min(costs[i][j - 1] + 1);
+        }
+    }
+
+    let first = steps.len();
+    let (mut i, mut j) = (n, m);
+    while i > 0 || j > 0 {
+        if i > 0 && j > 0 && costs[i][j] == costs[i - 1][j - 1] + u32::from(reference[i - 1] != hypothesis[j - 1]) {
+            steps.push((Some(offset.0 + i - 1), Some(offset.1 + j - 1)));
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && costs[i][j] == costs[i - 1][j] + 1 {
+            steps.push((Some(offset.0 + i - 1), None));
+            i -= 1;
+        } else {
+            steps.push((None, Some(offset.1 + j - 1)));
+            j -= 1;
+        }
+    }
+    steps[first..].reverse();
+}
+
+pub fn format_report(evaluation: &Evaluation) -> String {
+    let mut report = format!(
+        "WER: {:.2}% ({} substitutions, {} insertions, {} deletions over {} reference words)\n\
+         CER: {:.2}%\n",
+        evaluation.wer * 100.0,
+        evaluation.substitutions,
+        evaluation.insertions,
+        evaluation.deletions,
+        evaluation.reference_words,
+        evaluation.cer * 100.0
+    );
+    if let Some(timing) = &evaluation.timing {
+        report.push_str(&format!(
+            "Timing: mean offset {:+.2}s, mean deviation {:.2}s, max {:.2}s over {} matched words\n",
+            timing.mean_offset, timing.mean_absolute, timing.max_absolute, timing.matched_words
+        ));
+    }
+    if let Some(performance) = &evaluation.performance {
+        report.push_str(&format!("Speed: {}\n", performance));
+    }
+    report
+}
+
+/// The alignment as a word diff: `[-reference-]{+hypothesis+}`
+pub fn format_diff(evaluation: &Evaluation) -> String {
+    let words: Vec<String> = evaluation
+        .alignment
+        .iter()
+        .map(|edit| match edit {
+            Edit::Match(word) => word.clone(),
+            Edit::Substitute { reference, hypothesis } => format!("[-{}-]{{+{}+}}", reference, hypothesis),
+            Edit::Insert(word) => format!("{{+{}+}}", word),
+            Edit::Delete(word) => format!("[-{}-]", word),
+        })
+        .collect();
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::TranscriptionSegment;
+
+    fn text(full_text: &str) -> TranscriptionResult {
+        TranscriptionResult { segments: Vec::new(), full_text: full_text.to_string(), language: None }
+    }
+
+    fn timed(cues: &[(f64, f64, &str)]) -> TranscriptionResult {
+        let segments: Vec<TranscriptionSegment> = cues
+            .iter()
+            .map(|&(start_time, end_time, text)| TranscriptionSegment {
+                start_time,
+                end_time,
+                text: text.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        TranscriptionResult { segments, full_text: String::new(), language: None }
+    }
+
+    #[test]
+    fn test_error_breakdown() {
+        let evaluation = evaluate(
+            &text("the power of intention is energy"),
+            &text("the power of attention is pure energy today"),
+            &NormalizeOptions::default(),
+        );
+
+        assert_eq!((evaluation.substitutions, evaluation.insertions, evaluation.deletions), (1, 2, 0));
+        assert_eq!(evaluation.wer, 0.5);
+        assert_eq!(
+            format_diff(&evaluation),
+            "the power of [-intention-]{+attention+} is {+pure+} energy {+today+}"
+        );
+        assert!(evaluation.timing.is_none());
+    }
+
+    #[test]
+    fn test_long_alignment_is_minimal() {
+        let reference: Vec<u32> = (0..500).map(|i| i % 37).collect();
+        let mut hypothesis = reference.clone();
+        hypothesis.drain(40..55);
+        hypothesis[200] = 99;
+        hypothesis.splice(300..300, [7, 7, 7]);
+
+        let steps = align(&reference, &hypothesis);
+
+        // Every word appears once, in order, at the minimal number of edits
+        let references: Vec<usize> = steps.iter().filter_map(|step| step.0).collect();
+        let hypotheses: Vec<usize> = steps.iter().filter_map(|step| step.1).collect();
+        assert_eq!(references, (0..reference.len()).collect::<Vec<_>>());
+        assert_eq!(hypotheses, (0..hypothesis.len()).collect::<Vec<_>>());
+        let edits = steps
+            .iter()
+            .filter(|step| match step {
+                (Some(r), Some(h)) => reference[*r] != hypothesis[*h],
+                _ => true,
+            })
+            .count();
+        assert_eq!(edits, text::edit_distance(&reference, &hypothesis));
+        assert_eq!(edits, 19);
+    }
+
+    #[test]
+    fn test_normalization() {
+        let options = NormalizeOptions::default();
+        assert_eq!(normalize("Hello, World! It's 21 o'clock.", &options), ["hello", "world", "it's", "twenty", "one", "o'clock"]);
+
+        let evaluation = evaluate(&text("Twenty-one ways!"), &text("21 ways"), &options);
+        assert_eq!(evaluation.wer, 0.0);
+
+        let strict = NormalizeOptions { lowercase: false, strip_punctuation: false, spell_numbers: false };
+        assert_eq!(evaluate(&text("Twenty-one ways!"), &text("21 ways"), &strict).wer, 1.0);
+    }
+
+    #[test]
+    fn test_character_error_rate() {
+        let evaluation = evaluate(&text("intention"), &text("intension"), &NormalizeOptions::default());
+
+        assert_eq!(evaluation.wer, 1.0);
+        assert!((evaluation.cer - 1.0 / 9.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_timing_deviation() {
+        let reference = timed(&[(0.0, 2.0, "hello everyone"), (2.0, 4.0, "welcome back")]);
+        let hypothesis = timed(&[(0.5, 2.5, "hello everyone"), (2.5, 4.5, "welcome")]);
+
+        let timing = evaluate(&reference, &hypothesis, &NormalizeOptions::default()).timing.unwrap();
+
+        assert_eq!(timing.matched_words, 3);
+        // "welcome" is spread over the whole hypothesis segment, landing 1s late
+        assert!((timing.max_absolute - 1.0).abs() < 1e-9);
+        assert!((timing.mean_offset - 2.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_empty_reference() {
+        assert_eq!(evaluate(&text(""), &text(""), &NormalizeOptions::default()).wer, 0.0);
+        assert_eq!(evaluate(&text(""), &text("extra"), &NormalizeOptions::default()).wer, 1.0);
+    }
+}
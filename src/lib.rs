@@ -0,0 +1,42 @@
+//! Transcribe video and audio with OpenAI Whisper.
+//!
+//! ```no_run
+//! use wayne_transcriber::{output, AudioSource, OutputFormat, Transcriber, WhisperModel};
+//!
+//! # async fn run() -> Result<(), wayne_transcriber::TranscribeError> {
+//! let transcriber = Transcriber::builder().model(WhisperModel::Tiny).build()?;
+//! let transcription = transcriber.transcribe(AudioSource::File("talk.mp4".into())).await?;
+//! let srt = output::format_transcription(&transcription, &OutputFormat::Srt)?;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod audio;
+pub mod chapters;
+pub mod clip;
+pub mod config;
+pub mod decoder;
+pub mod diarization;
+pub mod error;
+pub mod eval;
+pub mod models;
+pub mod mux;
+pub mod output;
+pub mod postprocess;
+pub mod server;
+pub mod search;
+pub mod speakers;
+pub mod streaming;
+pub mod summary;
+pub mod transcription;
+pub mod vad;
+pub mod vocabulary;
+mod text;
+mod transcriber;
+
+pub use candle_core::Device;
+pub use audio::NetworkOptions;
+pub use error::TranscribeError;
+pub use output::OutputFormat;
+pub use transcriber::{load_source, AudioSource, DiarizationOptions, Transcriber, TranscriberBuilder};
+pub use transcription::{DecodingOptions, ModelSpec, Performance, SegmentFlag, TranscriptionResult, TranscriptionSegment, WhisperModel, WordTiming};
@@ -1,96 +1,1247 @@
 use anyhow::{Context, Result};
-use clap::{Arg, Command};
-use std::path::PathBuf;
-use tracing::{info, warn};
-use tracing_subscriber;
+use clap::{Arg, ArgGroup, Args, Command, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::ffi::OsString;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::info;
+use wayne_transcriber::audio::{self, NetworkOptions};
+use wayne_transcriber::chapters::{self, ChapterOptions};
+use wayne_transcriber::clip::{self, ClipOptions};
+use wayne_transcriber::config::{self, ConfigFile, EffectiveConfig};
+use wayne_transcriber::eval::{self, NormalizeOptions};
+use wayne_transcriber::models::{self, ModelStore, Quantization, Verification};
+use wayne_transcriber::mux::{self, SubtitleCodec};
+use wayne_transcriber::output::CaptionLimits;
+use wayne_transcriber::postprocess::{self, PostProcessing};
+use wayne_transcriber::search::{self, SearchIndex};
+use wayne_transcriber::server::{self, ServerOptions};
+use wayne_transcriber::speakers::{self, SpeakerDatabase};
+use wayne_transcriber::summary::{self, SummaryOptions};
+use wayne_transcriber::streaming::{self, StreamEvent, StreamOptions, StreamSession};
+use wayne_transcriber::vocabulary::Vocabulary;
+use wayne_transcriber::{
+    diarization, output, AudioSource, DecodingOptions, DiarizationOptions, OutputFormat, TranscribeError,
+    ModelSpec, Performance, Transcriber, TranscriptionResult,
+};
 
-mod audio;
-mod transcription;
-mod output;
+const BIN_NAME: &str = "wayne-transcriber";
+
+#[derive(Debug, Parser)]
+#[command(
+    name = BIN_NAME,
+    version,
+    author = "Wayne Dyer Video Transcriber",
+    about = "Transcribes Wayne Dyer videos using OpenAI Whisper",
+    after_help = "A bare input, as in `wayne-transcriber talk.mp4`, is short for `wayne-transcriber transcribe talk.mp4`."
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: CliCommand,
+}
+
+#[derive(Debug, Subcommand)]
+enum CliCommand {
+    /// Transcribe a video or audio file, or an http(s), HLS or rtmp URL
+    Transcribe(Box<TranscribeArgs>),
+    /// Convert a transcript to another format, optionally post-processing it
+    Convert(ConvertArgs),
+    /// List, download, verify, import and remove models in the model store
+    Models {
+        #[command(subcommand)]
+        command: ModelsCommand,
+    },
+    /// Keep the model loaded and transcribe uploads over HTTP
+    Serve(ServeArgs),
+    /// Transcribe live 16kHz mono 16-bit PCM, printing partial and final segments as NDJSON
+    Stream(StreamArgs),
+    /// Search a folder of JSON transcripts, updating its index first
+    Search(SearchArgs),
+    /// Cut a quote out of the original media, with matching SRT subtitles
+    Clip(ClipArgs),
+    /// Measure word and character error rates against a reference transcript
+    Eval(EvalArgs),
+    /// Inspect the settings read from config files and WAYNE_* variables
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print the man page, or write one page per subcommand into a directory
+    Man {
+        /// Directory to write wayne-transcriber.1 and wayne-transcriber-<subcommand>.1 into
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct TranscribeArgs {
+    /// Input video file path, or an http(s), HLS (.m3u8) or rtmp URL
+    input: String,
+
+    /// Fail URL inputs when connecting or reading stalls for this long
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds, default_value = "30")]
+    timeout: f64,
+
+    /// Fail on dropped HTTP(S) connections instead of reconnecting
+    #[arg(long)]
+    no_reconnect: bool,
+
+    /// Stop reading a URL input after this much audio, e.g. for live streams
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds)]
+    max_duration: Option<f64>,
+
+    /// Output file path (optional, defaults to input filename with .txt extension)
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Output format
+    #[arg(short, long, value_enum, default_value = "txt")]
+    format: OutputFormat,
+
+    /// Model alias (tiny, base, small, medium, large, large-v3-turbo, distil-large-v3),
+    /// stored model name, checkpoint path or Hugging Face repository
+    #[arg(short, long, value_name = "MODEL", default_value = "base")]
+    model: ModelSpec,
+
+    /// Label segments with speakers, estimating the number of speakers automatically
+    #[arg(long)]
+    diarize: bool,
+
+    /// Label segments with exactly N speakers (implies --diarize)
+    #[arg(long, value_name = "N")]
+    num_speakers: Option<usize>,
+
+    /// Initial prompt giving the decoder context, e.g. the talk title and speaker names
+    #[arg(long, value_name = "TEXT")]
+    prompt: Option<String>,
+
+    /// Custom vocabulary file: one term per line, or 'misspelling => correction'
+    #[arg(long, value_name = "FILE")]
+    vocab: Option<PathBuf>,
+
+    /// Logit bias towards the tokens of vocabulary terms
+    #[arg(long, value_name = "LOGITS", default_value = "0.0")]
+    vocab_bias: f32,
+
+    /// Remove segments flagged as repetition loops or text over silence
+    #[arg(long)]
+    drop_hallucinations: bool,
+
+    #[command(flatten)]
+    postprocess: PostProcessArgs,
+
+    #[command(flatten)]
+    captions: CaptionArgs,
+
+    /// Write low-confidence passages to a Markdown (or .html) report for human review
+    #[arg(long, value_name = "FILE")]
+    review_report: Option<PathBuf>,
+
+    /// Segments below this confidence are listed in the review report
+    #[arg(long, value_name = "CONFIDENCE", default_value = "0.5")]
+    review_threshold: f32,
+
+    /// Write a copy of the input with a soft subtitle track (default: <input>.subtitled.<ext>)
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "")]
+    embed: Option<String>,
+
+    /// Write a re-encoded copy of the input with subtitles rendered into the video (default: <input>.burned.<ext>)
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "")]
+    burn_in: Option<String>,
+
+    /// ISO 639-2 language tag of the embedded subtitle track
+    #[arg(long, value_name = "CODE", default_value = "eng")]
+    subtitle_language: String,
+
+    /// Embedded subtitle codec: mov_text (MP4/MOV), srt or ass (MKV), webvtt (WebM/MKV); default depends on the container
+    #[arg(long, value_name = "CODEC")]
+    subtitle_codec: Option<SubtitleCodec>,
+
+    /// Write an extractive summary, keywords and keyword index as Markdown (or .json)
+    #[arg(long, value_name = "FILE")]
+    summary: Option<PathBuf>,
+
+    /// Sentences in the summary
+    #[arg(long, value_name = "N", default_value = "5")]
+    summary_sentences: usize,
+
+    /// Detect chapters and write them as WebVTT (.vtt), FFmetadata (.ffmetadata) or YouTube timestamps (other)
+    #[arg(long, value_name = "FILE")]
+    chapters: Option<PathBuf>,
+
+    /// Write a copy of the input with detected chapters (default: <input>.chapters.<ext>)
+    #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "")]
+    embed_chapters: Option<String>,
+
+    /// Shortest chapter to create
+    #[arg(long, value_name = "SECONDS", default_value = "60")]
+    min_chapter: f64,
+
+    /// Enroll the input file as a reference clip of speaker NAME instead of transcribing it
+    #[arg(long, value_name = "NAME")]
+    enroll: Option<String>,
+
+    /// Speaker profile database used for enrollment and speaker naming [default: speaker_profiles.json in the model store]
+    #[arg(long, value_name = "FILE")]
+    speaker_db: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct PostProcessArgs {
+    /// Clean up segment text: comma-separated fillers, digits or words, case, punctuation, profanity
+    #[arg(long, value_name = "STEPS")]
+    postprocess: Option<String>,
+
+    /// Post-processing steps for one output format, e.g. srt=fillers,profanity (repeatable)
+    #[arg(long, value_name = "FORMAT=STEPS")]
+    postprocess_for: Vec<String>,
+}
+
+impl PostProcessArgs {
+    fn processing(&self) -> Result<PostProcessing> {
+        let mut processing = PostProcessing::default();
+        if let Some(steps) = &self.postprocess {
+            processing.default = steps.parse().context("Invalid --postprocess steps")?;
+        }
+        for spec in &self.postprocess_for {
+            processing.add_override(spec).context("Invalid --postprocess-for")?;
+        }
+        Ok(processing)
+    }
+}
+
+#[derive(Debug, Args)]
+struct CaptionArgs {
+    /// Wrap SRT, WebVTT and TTML captions at N characters per line, splitting long segments into several cues
+    #[arg(long, value_name = "N")]
+    max_line_chars: Option<usize>,
+
+    /// Lines per caption cue when wrapping with --max-line-chars
+    #[arg(long, value_name = "N", default_value = "2")]
+    max_lines: usize,
+
+    /// Keep captions on screen until they can be read at this many characters per second
+    #[arg(long, value_name = "CPS")]
+    max_cps: Option<f64>,
+}
+
+impl CaptionArgs {
+    fn limits(&self) -> CaptionLimits {
+        CaptionLimits {
+            max_line_chars: self.max_line_chars,
+            max_lines: self.max_lines,
+            max_cps: self.max_cps,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+#[command(group(ArgGroup::new("target").required(true).multiple(true).args(["output", "to"])))]
+struct ConvertArgs {
+    /// Transcript to convert (.txt, .srt, .vtt, .json, .ttml)
+    input: PathBuf,
+
+    /// Output file [default: INPUT with the extension of --to]
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Output format [default: from the --output extension]
+    #[arg(short, long, value_enum)]
+    to: Option<OutputFormat>,
+
+    #[command(flatten)]
+    postprocess: PostProcessArgs,
+
+    #[command(flatten)]
+    captions: CaptionArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum ModelsCommand {
+    /// Show stored models with their size, format and quantization, and source
+    List,
+    /// Download models from Hugging Face into the store
+    Download {
+        /// Model aliases, or Hugging Face repositories with a Transformers checkpoint
+        #[arg(required = true, value_name = "MODEL")]
+        models: Vec<ModelSpec>,
+    },
+    /// Check stored models against their SHA256 checksums [default: every stored model]
+    Verify {
+        names: Vec<String>,
+
+        /// sha256sum-style file of expected checksums, overriding those recorded in the store
+        #[arg(long, value_name = "FILE")]
+        manifest: Option<PathBuf>,
+    },
+    /// Copy a GGML, GGUF or safetensors model file, or a checkpoint directory, into the store
+    Import {
+        path: PathBuf,
+
+        /// Name to store it under [default: the directory name or file stem]
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+
+        /// Replace a stored model with the same name
+        #[arg(long)]
+        force: bool,
+    },
+    /// Convert a safetensors checkpoint to GGUF with quantized weights, for faster, smaller CPU inference
+    Quantize {
+        /// Stored model name or checkpoint path (a safetensors file beside config.json, or its directory)
+        model: ModelSpec,
+
+        /// Weight type
+        #[arg(short = 't', long = "type", value_enum, default_value = "q5_1")]
+        quantization: Quantization,
+
+        /// Name to store it under [default: <model>-<type>]
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+
+        /// Replace a stored model with the same name
+        #[arg(long)]
+        force: bool,
+    },
+    /// Delete models from the store
+    Rm {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommand {
+    /// Print the effective settings and where each came from
+    Show,
+}
+
+#[derive(Debug, Args)]
+struct ServeArgs {
+    /// Address to listen on
+    #[arg(long, value_name = "HOST:PORT", default_value = "127.0.0.1:8080")]
+    addr: SocketAddr,
+
+    /// Model alias (tiny, base, small, medium, large, large-v3-turbo, distil-large-v3),
+    /// stored model name, checkpoint path or Hugging Face repository
+    #[arg(short, long, value_name = "MODEL", default_value = "base")]
+    model: ModelSpec,
+
+    /// Transcriptions running at the same time
+    #[arg(long, value_name = "N", default_value = "1")]
+    concurrency: usize,
+
+    /// Requests waiting for a free slot before new ones get 503
+    #[arg(long, value_name = "N", default_value = "16")]
+    max_queue: usize,
+}
+
+#[derive(Debug, Args)]
+struct StreamArgs {
+    /// Named pipe or file to read PCM (or WAV) from; '-' reads stdin
+    #[arg(default_value = "-")]
+    input: String,
+
+    /// Model alias (tiny, base, small, medium, large, large-v3-turbo, distil-large-v3),
+    /// stored model name, checkpoint path or Hugging Face repository
+    #[arg(short, long, value_name = "MODEL", default_value = "base")]
+    model: ModelSpec,
+
+    /// New audio between partial results
+    #[arg(long, value_name = "SECONDS", default_value = "1.0")]
+    partial_interval: f64,
+
+    /// Pause after speech that finalizes a segment
+    #[arg(long, value_name = "SECONDS", default_value = "0.6")]
+    commit_silence: f64,
+
+    /// Finalize segments that grow this long without a pause
+    #[arg(long, value_name = "SECONDS", default_value = "15.0")]
+    max_segment: f64,
+}
+
+#[derive(Debug, Args)]
+struct SearchArgs {
+    /// Words to find; quote phrases ("power of intention"), append ~ for fuzzy terms
+    #[arg(required = true, num_args = 1..)]
+    query: Vec<String>,
+
+    /// Folder of JSON transcripts, searched recursively
+    #[arg(short, long, value_name = "DIR", default_value = ".")]
+    dir: PathBuf,
+
+    /// Index file to update and search [default: DIR/.wayne-index.json]
+    #[arg(long, value_name = "FILE")]
+    index: Option<PathBuf>,
+
+    /// Maximum number of hits to print
+    #[arg(short = 'n', long, value_name = "N", default_value = "10")]
+    limit: usize,
+
+    /// Also match words within one or two typos of every query term
+    #[arg(long)]
+    fuzzy: bool,
+}
+
+#[derive(Debug, Args)]
+struct ClipArgs {
+    /// Media file to cut; defaults to the media next to the search hit's transcript
+    media: Option<PathBuf>,
+
+    /// Clip start as SECONDS, MM:SS or HH:MM:SS
+    #[arg(long, value_name = "TIME", value_parser = clip::parse_timestamp, requires_all = ["end", "media"], conflicts_with = "query")]
+    start: Option<f64>,
+
+    /// Clip end as SECONDS, MM:SS or HH:MM:SS
+    #[arg(long, value_name = "TIME", value_parser = clip::parse_timestamp, requires = "start")]
+    end: Option<f64>,
+
+    /// Cut the segment of a search hit instead of a time range
+    #[arg(short, long, value_name = "QUERY", required_unless_present = "start")]
+    query: Option<String>,
+
+    /// Folder of JSON transcripts searched by --query
+    #[arg(short, long, value_name = "DIR", default_value = ".")]
+    dir: PathBuf,
+
+    /// Which search hit to cut, as numbered by `search`
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..), default_value = "1")]
+    hit: u64,
+
+    /// JSON transcript for the clip's SRT [default: MEDIA with .json, if present]
+    #[arg(short, long, value_name = "FILE")]
+    transcript: Option<PathBuf>,
+
+    /// Extra media kept before the start and after the end
+    #[arg(long, value_name = "SECONDS", value_parser = parse_seconds, default_value = "0.5")]
+    padding: f64,
+
+    /// Re-encode for frame-accurate cuts instead of copying streams
+    #[arg(long)]
+    reencode: bool,
+
+    /// Clip path [default: MEDIA.clip-<MM>m<SS>s.<ext>]
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct EvalArgs {
+    /// Transcript to score (.txt, .srt, .vtt, .json, .ttml), or media to transcribe first
+    hypothesis: PathBuf,
+
+    /// Reference transcript (.txt, .srt, .vtt, .json, .ttml); subtitles also give timing deviation
+    reference: PathBuf,
+
+    /// Model used when the hypothesis is media (alias, store name, path or repository)
+    #[arg(short, long, value_name = "MODEL", default_value = "base")]
+    model: ModelSpec,
+
+    /// Also print the word alignment as [-reference-]{+hypothesis+}
+    #[arg(long)]
+    diff: bool,
+
+    /// Print the scores as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Count differences in capitalization as errors
+    #[arg(long)]
+    keep_case: bool,
+
+    /// Count differences in punctuation as errors
+    #[arg(long)]
+    keep_punctuation: bool,
+
+    /// Compare digits as written instead of spelling them out
+    #[arg(long)]
+    keep_numbers: bool,
+}
+
+/// A duration in seconds: finite, non-negative and small enough for a [`Duration`]
+fn parse_seconds(value: &str) -> wayne_transcriber::error::Result<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|seconds| !seconds.is_sign_negative() && Duration::try_from_secs_f64(*seconds).is_ok())
+        .ok_or_else(|| TranscribeError::InvalidInput(format!("'{}' is not a valid number of seconds", value)))
+}
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
     // Initialize tracing
     tracing_subscriber::fmt::init();
 
-    let matches = Command::new("wayne-transcriber")
-        .version("0.1.0")
-        .author("Wayne Dyer Video Transcriber")
-        .about("Transcribes Wayne Dyer videos using OpenAI Whisper")
-        .arg(
-            Arg::new("input")
-                .help("Input video file path")
-                .required(true)
-                .index(1),
-        )
-        .arg(
-            Arg::new("output")
-                .short('o')
-                .long("output")
-                .value_name("FILE")
-                .help("Output file path (optional, defaults to input filename with .txt extension)"),
-        )
-        .arg(
-            Arg::new("format")
-                .short('f')
-                .long("format")
-                .value_name("FORMAT")
-                .help("Output format: txt, srt, vtt")
-                .default_value("txt"),
-        )
-        .arg(
-            Arg::new("model")
-                .short('m')
-                .long("model")
-                .value_name("MODEL")
-                .help("Whisper model size: tiny, base, small, medium, large")
-                .default_value("base"),
-        )
-        .get_matches();
-
-    let input_path = PathBuf::from(matches.get_one::<String>("input").unwrap());
-    let output_path = match matches.get_one::<String>("output") {
-        Some(path) => PathBuf::from(path),
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {:?}", e);
+            exit_code(&e)
+        }
+    }
+}
+
+/// Maps a failure to a distinct exit code so schedulers can decide whether to retry
+fn exit_code(error: &anyhow::Error) -> ExitCode {
+    let code = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<TranscribeError>())
+        .map_or(1, TranscribeError::exit_code);
+    ExitCode::from(code)
+}
+
+async fn run() -> Result<()> {
+    let config_files = ConfigFiles::load().await?;
+    let (profile, args) = take_profile_arg(std::env::args_os().collect());
+    let config = config_files.resolve(profile.as_deref())?;
+
+    let command = cli_command();
+    let args = with_default_subcommand(&command, args);
+    let matches = with_config_defaults(command, &config.cli_defaults()).get_matches_from(args);
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    match cli.command {
+        CliCommand::Transcribe(args) => transcribe(&args).await,
+        CliCommand::Convert(args) => convert(&args).await,
+        CliCommand::Models { command } => manage_models(&command).await,
+        CliCommand::Serve(args) => serve(&args).await,
+        CliCommand::Stream(args) => stream(&args).await,
+        CliCommand::Search(args) => search(&args).await,
+        CliCommand::Clip(args) => clip(&args).await,
+        CliCommand::Eval(args) => evaluate(&args).await,
+        CliCommand::Config { command: ConfigCommand::Show } => {
+            print!("{}", config_files.describe(&config));
+            Ok(())
+        }
+        CliCommand::Completions { shell } => {
+            clap_complete::generate(shell, &mut cli_command(), BIN_NAME, &mut std::io::stdout());
+            Ok(())
+        }
+        CliCommand::Man { dir: Some(dir) } => {
+            std::fs::create_dir_all(&dir)?;
+            clap_mangen::generate_to(cli_command(), &dir).context("Failed to write man pages")?;
+            info!("✅ Man pages written to {:?}", dir);
+            Ok(())
+        }
+        CliCommand::Man { dir: None } => {
+            clap_mangen::Man::new(cli_command()).render(&mut std::io::stdout())?;
+            Ok(())
+        }
+    }
+}
+
+/// The command line, including `--profile`, which is read before parsing
+fn cli_command() -> Command {
+    Cli::command().arg(
+        Arg::new("profile")
+            .long("profile")
+            .value_name("NAME")
+            .global(true)
+            .help("Settings profile from the config files, or built-in: quick, broadcast"),
+    )
+}
+
+/// Transcribes the input and writes every requested output
+async fn transcribe(args: &TranscribeArgs) -> Result<()> {
+    let input = &args.input;
+    let input_path = if audio::is_url(input) {
+        // Name local files after the last URL path segment, in the current directory
+        let path = input.split(['?', '#']).next().unwrap_or(input);
+        PathBuf::from(path.trim_end_matches('/').rsplit('/').next().unwrap_or("stream"))
+    } else {
+        PathBuf::from(input)
+    };
+    let output_path = match &args.output {
+        Some(path) => path.clone(),
         None => {
             let mut path = input_path.clone();
             path.set_extension("txt");
             path
         }
     };
-    let format = matches.get_one::<String>("format").unwrap();
-    let model_size = matches.get_one::<String>("model").unwrap();
+    let source = if audio::is_url(input) {
+        let network = NetworkOptions {
+            reconnect: !args.no_reconnect,
+            timeout: Some(Duration::from_secs_f64(args.timeout)),
+            max_duration: args.max_duration.map(Duration::from_secs_f64),
+            ..NetworkOptions::default()
+        };
+        AudioSource::Url { url: input.clone(), network }
+    } else {
+        AudioSource::File(input_path.clone())
+    };
+    let format = args.format;
+    let num_speakers = args.num_speakers;
+    let diarize = args.diarize || num_speakers.is_some();
+    let speaker_db = args.speaker_db.clone().unwrap_or_else(speakers::default_database_path);
+
+    if let Some(name) = &args.enroll {
+        return enroll_speaker(source, name, &speaker_db).await;
+    }
 
     info!("Starting Wayne Dyer video transcription...");
-    info!("Input: {:?}", input_path);
+    info!("Input: {}", input);
     info!("Output: {:?}", output_path);
-    info!("Format: {}", format);
-    info!("Model: {}", model_size);
+    info!("Format: {}", format.extension());
+    info!("Model: {}", args.model);
 
-    // Step 1: Extract audio from video
-    info!("Extracting audio from video...");
-    let audio_path = audio::extract_audio(&input_path)
-        .await
-        .context("Failed to extract audio from video")?;
+    let vocabulary = match &args.vocab {
+        Some(path) => Vocabulary::load(path)
+            .await
+            .context("Failed to load custom vocabulary")?,
+        None => Vocabulary::default(),
+    };
+    let decoding_options = DecodingOptions {
+        initial_prompt: vocabulary.initial_prompt(args.prompt.as_deref()),
+        logit_bias: args.vocab_bias,
+        vocabulary,
+        drop_hallucinations: args.drop_hallucinations,
+        ..Default::default()
+    };
 
-    // Step 2: Transcribe audio using Whisper
+    let mut builder = Transcriber::builder()
+        .model(args.model.clone())
+        .decoding_options(decoding_options);
+    if diarize {
+        let known_speakers = SpeakerDatabase::load(&speaker_db)
+            .await
+            .context("Failed to load speaker database")?;
+        builder = builder.diarization(DiarizationOptions { num_speakers, known_speakers });
+    }
+    let transcriber = builder.build().context("Failed to load Whisper model")?;
+
+    // Step 1: Extract audio, transcribe it with Whisper and label speakers if requested
     info!("Transcribing audio with Whisper...");
-    let transcription = transcription::transcribe_audio(&audio_path, model_size)
+    let transcription = transcriber
+        .transcribe(source)
         .await
         .context("Failed to transcribe audio")?;
 
-    // Step 3: Save transcription in desired format
+    let processing = args.postprocess.processing()?;
+
+    // Step 2: Save transcription in desired format
     info!("Saving transcription to file...");
-    output::save_transcription(&transcription, &output_path, format)
-        .await
-        .context("Failed to save transcription")?;
+    output::save_transcription(
+        &prepare_output(&transcription, &processing, &args.captions, format),
+        &output_path,
+        format.extension(),
+    )
+    .await
+    .context("Failed to save transcription")?;
+
+    if let Some(report_path) = &args.review_report {
+        output::save_review_report(&transcription, report_path, args.review_threshold)
+            .await
+            .context("Failed to save review report")?;
+    }
+
+    if let Some(path) = &args.embed {
+        let embed_path = match path.as_str() {
+            "" => mux::copy_path(&input_path, "subtitled"),
+            path => PathBuf::from(path),
+        };
+        let codec = args.subtitle_codec;
+        let subtitle_format = match codec.or_else(|| SubtitleCodec::for_container(&embed_path).ok()) {
+            Some(SubtitleCodec::WebVtt) => OutputFormat::Vtt,
+            _ => OutputFormat::Srt,
+        };
+        let subtitles = prepare_output(&transcription, &processing, &args.captions, subtitle_format);
+        mux::embed_subtitles(&subtitles, Path::new(input), &embed_path, &args.subtitle_language, codec)
+            .await
+            .context("Failed to embed subtitles")?;
+        info!("Subtitled video saved to: {:?}", embed_path);
+    }
+
+    if let Some(path) = &args.burn_in {
+        let burn_path = match path.as_str() {
+            "" => mux::copy_path(&input_path, "burned"),
+            path => PathBuf::from(path),
+        };
+        let subtitles = prepare_output(&transcription, &processing, &args.captions, OutputFormat::Srt);
+        mux::burn_subtitles(&subtitles, Path::new(input), &burn_path)
+            .await
+            .context("Failed to burn in subtitles")?;
+        info!("Video with burned-in subtitles saved to: {:?}", burn_path);
+    }
+
+    if let Some(path) = &args.summary {
+        let options = SummaryOptions {
+            sentences: args.summary_sentences,
+            ..SummaryOptions::default()
+        };
+        summary::save_summary(&summary::summarize(&transcription, &options), path)
+            .await
+            .context("Failed to save summary")?;
+        info!("Summary saved to: {:?}", path);
+    }
+
+    if args.chapters.is_some() || args.embed_chapters.is_some() {
+        let options = ChapterOptions {
+            min_chapter: args.min_chapter,
+            ..ChapterOptions::default()
+        };
+        let detected = chapters::detect_chapters(&transcription, &options);
+
+        if let Some(path) = &args.chapters {
+            chapters::save_chapters(&detected, path)
+                .await
+                .context("Failed to save chapters")?;
+            info!("Chapters saved to: {:?}", path);
+        }
+        if let Some(path) = &args.embed_chapters {
+            let chaptered_path = match path.as_str() {
+                "" => mux::copy_path(&input_path, "chapters"),
+                path => PathBuf::from(path),
+            };
+            chapters::embed_chapters(&detected, Path::new(input), &chaptered_path)
+                .await
+                .context("Failed to add chapters to video")?;
+            info!("Video with chapters saved to: {:?}", chaptered_path);
+        }
+    }
 
     info!("✅ Transcription completed successfully!");
     info!("Output saved to: {:?}", output_path);
 
-    // Cleanup temporary audio file
-    if let Err(e) = tokio::fs::remove_file(&audio_path).await {
-        warn!("Could not clean up temporary audio file: {}", e);
+    Ok(())
+}
+
+/// The transcription as written in `format`: post-processed, and laid out
+/// within the caption limits for caption formats
+fn prepare_output(
+    transcription: &TranscriptionResult,
+    processing: &PostProcessing,
+    captions: &CaptionArgs,
+    format: OutputFormat,
+) -> TranscriptionResult {
+    let processed = postprocess::apply(transcription, processing.for_format(format));
+    if format.is_caption() {
+        output::fit_captions(&processed, &captions.limits())
+    } else {
+        processed
+    }
+}
+
+/// Rewrites a transcript in another format, applying post-processing on the way
+async fn convert(args: &ConvertArgs) -> Result<()> {
+    let format = match (args.to, &args.output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => {
+            let extension = path.extension().and_then(|e| e.to_str()).unwrap_or_default();
+            extension
+                .parse()
+                .with_context(|| format!("Cannot tell the format of {:?}; pass --to", path))?
+        }
+        (None, None) => unreachable!("clap requires --output or --to"),
+    };
+    let output_path = args
+        .output
+        .clone()
+        .unwrap_or_else(|| args.input.with_extension(format.extension()));
+
+    let transcription = output::load_transcription(&args.input)
+        .await
+        .with_context(|| format!("Failed to read transcript {:?}", args.input))?;
+    if transcription.segments.is_empty() && format != OutputFormat::Text {
+        return Err(TranscribeError::UnsupportedFormat(format!(
+            "{:?} has no timestamps to write as {}",
+            args.input,
+            format.extension()
+        ))
+        .into());
+    }
+
+    let processing = args.postprocess.processing()?;
+    output::save_transcription(
+        &prepare_output(&transcription, &processing, &args.captions, format),
+        &output_path,
+        format.extension(),
+    )
+    .await
+    .context("Failed to save transcription")?;
+
+    info!("✅ Converted {:?} to {:?}", args.input, output_path);
+    Ok(())
+}
+
+/// Lists, adds, checks and removes models in the model store
+async fn manage_models(command: &ModelsCommand) -> Result<()> {
+    let mut store = ModelStore::open_default().context("Failed to open the model store")?;
+    match command {
+        ModelsCommand::List => {
+            println!("# {}", store.dir().display());
+            let stored = store.list();
+            if stored.is_empty() {
+                info!("No models stored yet; try `models download tiny` or `models import <FILE>`");
+            }
+            for model in stored {
+                println!("{}", model);
+            }
+        }
+        ModelsCommand::Download { models } => {
+            for model in models {
+                let downloaded = match model {
+                    ModelSpec::Known(model) => store.download(*model).await,
+                    ModelSpec::Repo(repo) => store.download_repo(repo).await,
+                    _ => anyhow::bail!("{} is not a known model or a Hugging Face repository (owner/name)", model),
+                };
+                downloaded.with_context(|| format!("Failed to download {}", model))?;
+            }
+        }
+        ModelsCommand::Verify { names, manifest } => {
+            let checksums = match manifest {
+                Some(path) => models::parse_checksums(
+                    &tokio::fs::read_to_string(path)
+                        .await
+                        .with_context(|| format!("Failed to read {:?}", path))?,
+                ),
+                None => Default::default(),
+            };
+            let names = if names.is_empty() {
+                store.list().into_iter().map(|model| model.name).collect()
+            } else {
+                names.clone()
+            };
+
+            let mut failed = 0;
+            for name in &names {
+                let expected = store.get(name).and_then(|model| checksums.get(&model.file));
+                match store.verify(name, expected.map(String::as_str)).await {
+                    Ok(Verification::Verified) => println!("ok         {}", name),
+                    Ok(Verification::LocalHash) => {
+                        println!("unverified {} (matches the local hash taken when it was added)", name)
+                    }
+                    Ok(Verification::Unchecked { actual }) => println!("unchecked  {} (sha256 {})", name, actual),
+                    Ok(Verification::Mismatch { expected, actual }) => {
+                        println!("MISMATCH   {}: expected {}, got {}", name, expected, actual);
+                        failed += 1;
+                    }
+                    Err(e) => {
+                        println!("FAILED     {}: {}", name, e);
+                        failed += 1;
+                    }
+                }
+            }
+            if failed > 0 {
+                anyhow::bail!("{} of {} models failed verification", failed, names.len());
+            }
+        }
+        ModelsCommand::Import { path, name, force } => {
+            store
+                .import(path, name.as_deref(), *force)
+                .await
+                .with_context(|| format!("Failed to import {:?}", path))?;
+        }
+        ModelsCommand::Quantize { model, quantization, name, force } => {
+            store
+                .quantize(model, *quantization, name.as_deref(), *force)
+                .await
+                .with_context(|| format!("Failed to quantize {}", model))?;
+        }
+        ModelsCommand::Rm { names } => {
+            for name in names {
+                store.remove(name).await.with_context(|| format!("Failed to remove {}", name))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The user and project config files, where present
+struct ConfigFiles {
+    user_path: Option<PathBuf>,
+    user: Option<ConfigFile>,
+    project_path: Option<PathBuf>,
+    project: Option<ConfigFile>,
+}
+
+impl ConfigFiles {
+    async fn load() -> Result<Self> {
+        let user_path = config::user_config_path();
+        let project_path = std::env::current_dir().ok().and_then(|dir| config::find_project_config(&dir));
+        let user = match &user_path {
+            Some(path) => ConfigFile::load(path).await?,
+            None => None,
+        };
+        let project = match &project_path {
+            Some(path) => ConfigFile::load(path).await?,
+            None => None,
+        };
+        Ok(Self { user_path, user, project_path, project })
+    }
+
+    fn resolve(&self, profile: Option<&str>) -> Result<EffectiveConfig> {
+        Ok(config::resolve(self.user.as_ref(), self.project.as_ref(), std::env::vars(), profile)?)
+    }
+
+    /// `config show` output: the files consulted, then the merged settings
+    fn describe(&self, config: &EffectiveConfig) -> String {
+        let describe = |name: &str, path: &Option<PathBuf>, file: &Option<ConfigFile>| match (path, file) {
+            (Some(path), Some(_)) => format!("# {}: {}\n", name, path.display()),
+            (Some(path), None) => format!("# {}: {} (not found)\n", name, path.display()),
+            (None, _) => format!("# {}: none (no {} found)\n", name, config::PROJECT_CONFIG_FILE),
+        };
+        describe("user config", &self.user_path, &self.user)
+            + &describe("project config", &self.project_path, &self.project)
+            + &config::format_effective(config)
+    }
+}
+
+/// Removes `--profile NAME` from the arguments: the profile decides the
+/// defaults, so it is needed before parsing, and may appear before or after
+/// a subcommand
+fn take_profile_arg(args: Vec<OsString>) -> (Option<String>, Vec<OsString>) {
+    let mut profile = None;
+    let mut remaining = Vec::with_capacity(args.len());
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            remaining.push(arg);
+            remaining.extend(args.by_ref());
+            break;
+        }
+        if arg == "--profile" {
+            profile = args.next().map(|name| name.to_string_lossy().into_owned());
+            continue;
+        }
+        match arg.to_str().and_then(|arg| arg.strip_prefix("--profile=")) {
+            Some(name) => profile = Some(name.to_string()),
+            None => remaining.push(arg),
+        }
+    }
+    (profile, remaining)
+}
+
+/// `wayne-transcriber talk.mp4 ...` is short for `wayne-transcriber transcribe talk.mp4 ...`
+fn with_default_subcommand(command: &Command, mut args: Vec<OsString>) -> Vec<OsString> {
+    let is_command = |arg: &str| {
+        ["help", "-h", "--help", "-V", "--version"].contains(&arg)
+            || command.get_subcommands().any(|sub| sub.get_name() == arg)
+    };
+    if args.get(1).is_some_and(|arg| !arg.to_str().is_some_and(is_command)) {
+        args.insert(1, "transcribe".into());
     }
+    args
+}
 
+/// Uses configured values as the defaults of the options they name, in every
+/// subcommand, so anything given on the command line still wins
+fn with_config_defaults(mut command: Command, defaults: &[(String, Vec<String>)]) -> Command {
+    for (key, values) in defaults {
+        let id = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key.as_str()))
+            .map(|arg| arg.get_id().clone());
+        if let Some(id) = id {
+            command = command.mut_arg(id, |arg| arg.default_values(values.clone()));
+        }
+    }
+    let subcommands: Vec<String> = command.get_subcommands().map(|sub| sub.get_name().to_string()).collect();
+    for name in subcommands {
+        command = command.mut_subcommand(name, |sub| with_config_defaults(sub, defaults));
+    }
+    command
+}
+
+/// Loads the model once and serves the HTTP API until interrupted
+async fn serve(args: &ServeArgs) -> Result<()> {
+    let transcriber = Transcriber::builder()
+        .model(args.model.clone())
+        .build()
+        .context("Failed to load Whisper model")?;
+
+    let options = ServerOptions {
+        addr: args.addr,
+        concurrency: args.concurrency,
+        max_queue: args.max_queue,
+        ..ServerOptions::default()
+    };
+    server::serve(transcriber, options).await.context("Server failed")?;
     Ok(())
 }
+
+/// Transcribes PCM from stdin or a named pipe, writing one JSON event per line to stdout
+async fn stream(args: &StreamArgs) -> Result<()> {
+    let options = StreamOptions {
+        partial_interval: args.partial_interval,
+        commit_silence: args.commit_silence,
+        max_segment: args.max_segment,
+    };
+    options.validate()?;
+
+    let transcriber = Transcriber::builder()
+        .model(args.model.clone())
+        .build()
+        .context("Failed to load Whisper model")?;
+
+    let mut session = StreamSession::new(Arc::new(transcriber), options);
+
+    let mut stdout = std::io::stdout().lock();
+    let print = |event: &StreamEvent| -> wayne_transcriber::error::Result<()> {
+        let line = serde_json::to_string(event).map_err(anyhow::Error::from)?;
+        writeln!(stdout, "{}", line)?;
+        stdout.flush()?;
+        Ok(())
+    };
+
+    let input = &args.input;
+    info!("🎙️  Streaming from {}", if input == "-" { "stdin" } else { input });
+    let result = if input == "-" {
+        streaming::stream_pcm(&mut session, tokio::io::stdin(), print).await
+    } else {
+        let pipe = tokio::fs::File::open(input)
+            .await
+            .with_context(|| format!("Failed to open {}", input))?;
+        streaming::stream_pcm(&mut session, pipe, print).await
+    };
+    result.context("Streaming transcription failed")?;
+    Ok(())
+}
+
+/// Brings the transcript index up to date and prints ranked hits
+async fn search(args: &SearchArgs) -> Result<()> {
+    let dir = &args.dir;
+    let index_path = args.index.clone().unwrap_or_else(|| dir.join(search::DEFAULT_INDEX_FILE));
+
+    let mut index = SearchIndex::load(&index_path).await.context("Failed to load search index")?;
+    index.update_from_dir(dir).await.context("Failed to index transcripts")?;
+    index.save(&index_path).await.context("Failed to save search index")?;
+
+    let hits = index.search(&args.query.join(" "), args.fuzzy, args.limit);
+    if hits.is_empty() {
+        info!("No matches in {} transcripts", index.len());
+    }
+    for hit in hits {
+        println!("{}", hit);
+    }
+    Ok(())
+}
+
+/// Cuts a time range, or the segment of a search hit, out of the original media
+async fn clip(args: &ClipArgs) -> Result<()> {
+    let media_arg = args.media.clone();
+    let (media, transcript, start, end) = match &args.query {
+        Some(query) => {
+            let dir = &args.dir;
+            let index_path = dir.join(search::DEFAULT_INDEX_FILE);
+            let mut index = SearchIndex::load(&index_path).await.context("Failed to load search index")?;
+            index.update_from_dir(dir).await.context("Failed to index transcripts")?;
+            index.save(&index_path).await.context("Failed to save search index")?;
+
+            let number = args.hit as usize;
+            let hit = index
+                .search(query, false, number)
+                .into_iter()
+                .nth(number - 1)
+                .with_context(|| format!("No search hit #{} for {:?}", number, query))?;
+            info!("Clipping {}", hit);
+            let media = media_arg
+                .or_else(|| hit.media_path())
+                .with_context(|| format!("No media file found next to {:?}; pass it explicitly", hit.transcript))?;
+            (media, Some(hit.transcript), hit.start_time, hit.end_time)
+        }
+        None => {
+            let media = media_arg.unwrap();
+            let transcript = Some(media.with_extension("json")).filter(|path| path.exists());
+            (media, transcript, args.start.unwrap(), args.end.unwrap())
+        }
+    };
+
+    let transcript = args.transcript.clone().or(transcript);
+    let transcription = match &transcript {
+        Some(path) => {
+            let content = tokio::fs::read_to_string(path)
+                .await
+                .with_context(|| format!("Failed to read transcript {:?}", path))?;
+            Some(serde_json::from_str(&content).with_context(|| format!("{:?} is not a JSON transcript", path))?)
+        }
+        None => None,
+    };
+
+    let output_path = args.output.clone().unwrap_or_else(|| clip::clip_path(&media, start));
+    let options = ClipOptions {
+        padding: args.padding,
+        reencode: args.reencode,
+    };
+    let srt_path = clip::extract_clip(&media, &output_path, start, end, transcription.as_ref(), &options)
+        .await
+        .context("Failed to cut clip")?;
+
+    info!("✅ Clip saved to: {:?}", output_path);
+    if let Some(srt_path) = srt_path {
+        info!("Subtitles saved to: {:?}", srt_path);
+    }
+    Ok(())
+}
+
+/// Scores a transcript, or a fresh transcription of a media file, against a reference
+async fn evaluate(args: &EvalArgs) -> Result<()> {
+    let reference_path = &args.reference;
+    let reference = output::load_transcription(reference_path)
+        .await
+        .with_context(|| format!("Failed to read reference {:?}", reference_path))?;
+
+    let hypothesis_path = &args.hypothesis;
+    let is_transcript = hypothesis_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|extension| extension.parse::<OutputFormat>().is_ok());
+    let (hypothesis, performance) = if is_transcript {
+        let hypothesis = output::load_transcription(hypothesis_path)
+            .await
+            .with_context(|| format!("Failed to read hypothesis {:?}", hypothesis_path))?;
+        (hypothesis, None)
+    } else {
+        let transcriber = Transcriber::builder()
+            .model(args.model.clone())
+            .build()
+            .context("Failed to load Whisper model")?;
+        // Extract first, so the speed covers decoding only
+        let audio = wayne_transcriber::load_source(AudioSource::File(hypothesis_path.clone()))
+            .await
+            .context("Failed to load hypothesis audio")?;
+        let audio_seconds = audio.len() as f64 / 16000.0;
+        let started = Instant::now();
+        let hypothesis = transcriber
+            .transcribe(AudioSource::Samples(audio))
+            .await
+            .context("Failed to transcribe hypothesis")?;
+        (hypothesis, Some(Performance::measure(audio_seconds, started, transcriber.weights_bytes())))
+    };
+
+    let options = NormalizeOptions {
+        lowercase: !args.keep_case,
+        strip_punctuation: !args.keep_punctuation,
+        spell_numbers: !args.keep_numbers,
+    };
+    let mut evaluation = eval::evaluate(&reference, &hypothesis, &options);
+    evaluation.performance = performance;
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&evaluation)?);
+    } else {
+        print!("{}", eval::format_report(&evaluation));
+    }
+    if args.diff {
+        println!("\n{}", eval::format_diff(&evaluation));
+    }
+    Ok(())
+}
+
+/// Adds a reference clip to a speaker's profile in the speaker database
+async fn enroll_speaker(source: AudioSource, name: &str, speaker_db_path: &Path) -> Result<()> {
+    info!("Enrolling a reference clip for {}", name);
+
+    let audio_data = wayne_transcriber::load_source(source)
+        .await
+        .context("Failed to load reference clip")?;
+
+    let embedding = diarization::voice_embedding(&audio_data)
+        .context("No speech detected in reference clip")?;
+
+    let mut database = SpeakerDatabase::load(speaker_db_path)
+        .await
+        .context("Failed to load speaker database")?;
+    database.enroll(name, embedding);
+    database.save(speaker_db_path)
+        .await
+        .context("Failed to save speaker database")?;
+
+    info!("✅ Enrolled reference clip for {}", name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wayne_transcriber::WhisperModel;
+
+    fn parse(args: &[&str], defaults: &[(String, Vec<String>)]) -> Result<Cli, clap::Error> {
+        let command = cli_command();
+        let args = with_default_subcommand(&command, args.iter().map(OsString::from).collect());
+        let matches = with_config_defaults(command, defaults).try_get_matches_from(args)?;
+        Cli::from_arg_matches(&matches)
+    }
+
+    #[test]
+    fn test_command_is_valid() {
+        cli_command().debug_assert();
+    }
+
+    #[test]
+    fn test_bare_input_is_transcribe() {
+        let cli = parse(&["wayne-transcriber", "talk.mp4", "-f", "vtt"], &[]).unwrap();
+
+        let CliCommand::Transcribe(args) = cli.command else { panic!("expected transcribe") };
+        assert_eq!(args.input, "talk.mp4");
+        assert_eq!(args.format, OutputFormat::Vtt);
+        assert_eq!(args.model, ModelSpec::Known(WhisperModel::Base));
+        assert!(parse(&["wayne-transcriber", "talk.mp4", "-f", "docx"], &[]).is_err());
+    }
+
+    #[test]
+    fn test_config_defaults_yield_to_arguments() {
+        let defaults = [
+            ("model".to_string(), vec!["tiny".to_string()]),
+            ("format".to_string(), vec!["srt".to_string()]),
+            ("diarize".to_string(), vec!["true".to_string()]),
+            ("postprocess-for".to_string(), vec!["srt=fillers".to_string()]),
+            ("max-line-chars".to_string(), vec!["37".to_string()]),
+            ("max-cps".to_string(), vec!["17.0".to_string()]),
+        ];
+
+        let cli = parse(&["wayne-transcriber", "transcribe", "talk.mp4", "-f", "json"], &defaults).unwrap();
+        let CliCommand::Transcribe(args) = cli.command else { panic!("expected transcribe") };
+        assert_eq!(args.model, ModelSpec::Known(WhisperModel::Tiny));
+        assert_eq!(args.format, OutputFormat::Json);
+        assert!(args.diarize);
+        assert_eq!(args.postprocess.postprocess_for, ["srt=fillers"]);
+        assert_eq!(
+            args.captions.limits(),
+            CaptionLimits { max_line_chars: Some(37), max_lines: 2, max_cps: Some(17.0) }
+        );
+
+        let cli = parse(&["wayne-transcriber", "serve"], &defaults).unwrap();
+        let CliCommand::Serve(args) = cli.command else { panic!("expected serve") };
+        assert_eq!(args.model, ModelSpec::Known(WhisperModel::Tiny));
+    }
+
+    #[test]
+    fn test_durations_are_validated() {
+        let cli = parse(&["wayne-transcriber", "talk.mp4", "--max-duration", "90.5"], &[]).unwrap();
+        let CliCommand::Transcribe(args) = cli.command else { panic!("expected transcribe") };
+        assert_eq!(args.timeout, 30.0);
+        assert_eq!(args.max_duration, Some(90.5));
+
+        for value in ["-1", "nan", "inf", "1e300", "soon"] {
+            assert!(parse(&["wayne-transcriber", "talk.mp4", "--timeout", value], &[]).is_err(), "{}", value);
+            assert!(parse(&["wayne-transcriber", "talk.mp4", "--max-duration", value], &[]).is_err(), "{}", value);
+        }
+        let clip = ["wayne-transcriber", "clip", "talk.mp4", "--start", "1", "--end", "2", "--padding"];
+        assert!(parse(&[&clip[..], &["1.5"]].concat(), &[]).is_ok());
+        assert!(parse(&[&clip[..], &["-1"]].concat(), &[]).is_err());
+        // Config and WAYNE_* defaults go through the same parser
+        let defaults = [("timeout".to_string(), vec!["nan".to_string()])];
+        assert!(parse(&["wayne-transcriber", "talk.mp4"], &defaults).is_err());
+    }
+
+    #[test]
+    fn test_take_profile_arg() {
+        let args = ["wayne-transcriber", "--profile", "quick", "serve", "--profile=broadcast"].map(OsString::from);
+
+        let (profile, remaining) = take_profile_arg(args.to_vec());
+
+        assert_eq!(profile.as_deref(), Some("broadcast"));
+        assert_eq!(remaining, ["wayne-transcriber", "serve"]);
+    }
+}
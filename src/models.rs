@@ -0,0 +1,961 @@
+//! The managed model store: Whisper checkpoints downloaded or imported into a
+//! per-user directory, with a manifest recording where each came from and its
+//! SHA256, so models resolve the same way from any working directory.
+
+use candle_core::quantized::{gguf_file, GgmlDType, QTensor};
+use candle_core::Device;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::io::{Read, Seek};
+use std::path::{Path, PathBuf};
+use tracing::info;
+use crate::error::{Result, TranscribeError};
+use crate::transcription::{ModelSpec, WhisperModel};
+use candle_transformers::models::whisper::Config;
+
+/// Environment variable overriding the store directory
+pub const MODELS_DIR_ENV: &str = "WAYNE_MODELS_DIR";
+
+/// Architecture file kept beside Transformers checkpoints
+pub const CONFIG_FILE: &str = "config.json";
+/// Weights file of a Transformers checkpoint directory
+pub const WEIGHTS_FILE: &str = "model.safetensors";
+/// Hugging Face tokenizer kept beside every checkpoint that is decoded
+pub const TOKENIZER_FILE: &str = "tokenizer.json";
+/// Files copied along with a checkpoint's weights
+const SIDECAR_FILES: [&str; 2] = [CONFIG_FILE, TOKENIZER_FILE];
+
+const MANIFEST_FILE: &str = "manifest.json";
+const GGML_MAGIC: u32 = 0x6767_6d6c;
+/// Architecture fields `models quantize` writes to GGUF metadata, as `whisper.<field>`
+const GGUF_CONFIG_KEYS: [&str; 9] = [
+    "num_mel_bins",
+    "max_source_positions",
+    "d_model",
+    "encoder_attention_heads",
+    "encoder_layers",
+    "vocab_size",
+    "max_target_positions",
+    "decoder_attention_heads",
+    "decoder_layers",
+];
+
+/// A model recorded in the store's manifest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredModel {
+    /// File name inside the model's directory
+    pub file: String,
+    /// `huggingface:<repo>/<file>`, `huggingface:<repo>`, `import:<original path>`
+    /// or `quantize:<original path> <type>`
+    pub source: String,
+    /// Checksum taken when the model was added
+    pub sha256: Option<String>,
+    /// Whether `sha256` matched one published upstream when the model was
+    /// added, rather than only being computed from the local copy
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sha256_upstream: bool,
+    pub added: DateTime<Utc>,
+}
+
+/// A stored model as shown by `models list`
+#[derive(Debug, Clone)]
+pub struct ModelInfo {
+    pub name: String,
+    pub path: PathBuf,
+    /// File size in bytes, or `None` if the file has gone missing
+    pub size: Option<u64>,
+    pub format: ModelFormat,
+    pub source: String,
+}
+
+impl std::fmt::Display for ModelInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let size = match self.size {
+            Some(size) => format!("{:.1} MB", size as f64 / 1_000_000.0),
+            None => "missing".to_string(),
+        };
+        write!(f, "{:<16} {:>10}  {:<18} {}", self.name, size, self.format, self.source)
+    }
+}
+
+/// Checkpoint file formats the store accepts, with their weight precision
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelFormat {
+    /// whisper.cpp GGML; the quantization comes from the header's `ftype`
+    Ggml { quantization: &'static str },
+    /// GGUF; the quantization is the type holding most of the weights
+    Gguf { quantization: &'static str },
+    /// Hugging Face safetensors; `dtype` is the most common tensor type
+    Safetensors { dtype: String },
+    Unknown,
+}
+
+impl std::fmt::Display for ModelFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Padded, so `models list` columns line up
+        f.pad(&match self {
+            Self::Ggml { quantization } => format!("ggml {}", quantization),
+            Self::Gguf { quantization } => format!("gguf {}", quantization),
+            Self::Safetensors { dtype } => format!("safetensors {}", dtype),
+            Self::Unknown => "unknown".to_string(),
+        })
+    }
+}
+
+impl ModelFormat {
+    /// Reads the format from the file header
+    pub fn detect(path: &Path) -> Result<Self> {
+        let mut file = std::fs::File::open(path)?;
+        let mut magic = [0u8; 4];
+        if file.read_exact(&mut magic).is_err() {
+            return Ok(Self::Unknown);
+        }
+
+        if u32::from_le_bytes(magic) == GGML_MAGIC {
+            let Some(hparams) = read_ggml_hparams(&mut file) else {
+                return Ok(Self::Unknown);
+            };
+            let ftype = hparams[10] % 1000;
+            let quantization = match ftype {
+                0 => "f32",
+                1 => "f16",
+                2 => "q4_0",
+                3 => "q4_1",
+                7 => "q8_0",
+                8 => "q5_0",
+                9 => "q5_1",
+                _ => "unknown",
+            };
+            return Ok(Self::Ggml { quantization });
+        }
+        if &magic == b"GGUF" {
+            file.rewind()?;
+            let Ok(content) = gguf_file::Content::read(&mut file) else {
+                return Ok(Self::Unknown);
+            };
+            let mut elements: BTreeMap<&'static str, usize> = BTreeMap::new();
+            for info in content.tensor_infos.values() {
+                *elements.entry(dtype_name(info.ggml_dtype)).or_default() += info.shape.elem_count();
+            }
+            let quantization = elements.into_iter().max_by_key(|(_, count)| *count).map_or("unknown", |(name, _)| name);
+            return Ok(Self::Gguf { quantization });
+        }
+
+        // safetensors: a little-endian u64 header length, then a JSON header
+        let mut length = [0u8; 8];
+        length[..4].copy_from_slice(&magic);
+        if file.read_exact(&mut length[4..]).is_err() {
+            return Ok(Self::Unknown);
+        }
+        let length = u64::from_le_bytes(length);
+        if length == 0 || length > 100_000_000 {
+            return Ok(Self::Unknown);
+        }
+        let mut header = vec![0u8; length as usize];
+        if file.read_exact(&mut header).is_err() {
+            return Ok(Self::Unknown);
+        }
+        let Ok(serde_json::Value::Object(tensors)) = serde_json::from_slice(&header) else {
+            return Ok(Self::Unknown);
+        };
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for tensor in tensors.values() {
+            if let Some(dtype) = tensor.get("dtype").and_then(|dtype| dtype.as_str()) {
+                *counts.entry(dtype.to_lowercase()).or_default() += 1;
+            }
+        }
+        Ok(match counts.into_iter().max_by_key(|(_, count)| *count) {
+            Some((dtype, _)) => Self::Safetensors { dtype },
+            None => Self::Unknown,
+        })
+    }
+}
+
+/// Weight types `models quantize` converts to
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Quantization {
+    /// 4.5 bits per weight
+    #[value(name = "q4_0")]
+    Q4_0,
+    /// 6 bits per weight
+    #[value(name = "q5_1")]
+    Q5_1,
+    /// 8.5 bits per weight, closest to the original
+    #[value(name = "q8_0")]
+    Q8_0,
+}
+
+impl Quantization {
+    pub fn name(&self) -> &'static str {
+        dtype_name(self.dtype())
+    }
+
+    fn dtype(&self) -> GgmlDType {
+        match self {
+            Self::Q4_0 => GgmlDType::Q4_0,
+            Self::Q5_1 => GgmlDType::Q5_1,
+            Self::Q8_0 => GgmlDType::Q8_0,
+        }
+    }
+}
+
+fn dtype_name(dtype: GgmlDType) -> &'static str {
+    match dtype {
+        GgmlDType::F32 => "f32",
+        GgmlDType::F16 => "f16",
+        GgmlDType::Q4_0 => "q4_0",
+        GgmlDType::Q4_1 => "q4_1",
+        GgmlDType::Q5_0 => "q5_0",
+        GgmlDType::Q5_1 => "q5_1",
+        GgmlDType::Q8_0 => "q8_0",
+        GgmlDType::Q8_1 => "q8_1",
+        GgmlDType::Q2K => "q2_k",
+        GgmlDType::Q3K => "q3_k",
+        GgmlDType::Q4K => "q4_k",
+        GgmlDType::Q5K => "q5_k",
+        GgmlDType::Q6K => "q6_k",
+        GgmlDType::Q8K => "q8_k",
+    }
+}
+
+/// Reads a checkpoint's architecture from its GGML header or GGUF metadata,
+/// or from the `config.json` beside it (inside it, for a checkpoint directory).
+/// `None` if the checkpoint does not describe its architecture.
+pub fn read_config(path: &Path) -> Result<Option<Config>> {
+    let mut magic = [0u8; 4];
+    if path.is_file() {
+        let mut file = std::fs::File::open(path)?;
+        file.read_exact(&mut magic).ok();
+        if u32::from_le_bytes(magic) == GGML_MAGIC
+            && let Some(hparams) = read_ggml_hparams(&mut file)
+            && hparams[..10].iter().all(|value| *value > 0)
+        {
+            let [n_vocab, n_audio_ctx, n_audio_state, n_audio_head, n_audio_layer, n_text_ctx, _, n_text_head, n_text_layer, n_mels, _] =
+                hparams.map(|value| value as usize);
+            return Ok(Some(Config {
+                num_mel_bins: n_mels,
+                max_source_positions: n_audio_ctx,
+                d_model: n_audio_state,
+                encoder_attention_heads: n_audio_head,
+                encoder_layers: n_audio_layer,
+                vocab_size: n_vocab,
+                max_target_positions: n_text_ctx,
+                decoder_attention_heads: n_text_head,
+                decoder_layers: n_text_layer,
+                suppress_tokens: vec![],
+            }));
+        }
+        if &magic == b"GGUF" {
+            file.rewind()?;
+            let content = gguf_file::Content::read(&mut file)
+                .map_err(|e| TranscribeError::UnsupportedFormat(format!("{:?} is not a valid GGUF file: {}", path, e)))?;
+            if let Some(config) = gguf_config(&content) {
+                return Ok(Some(config));
+            }
+        }
+    }
+
+    let config_path = sidecar(path, CONFIG_FILE);
+    match std::fs::read_to_string(&config_path) {
+        Ok(content) => serde_json::from_str(&content).map(Some).map_err(|e| {
+            TranscribeError::UnsupportedFormat(format!("{:?} is not a Whisper config: {}", config_path, e))
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// `file` beside a checkpoint file, or inside a checkpoint directory
+pub fn sidecar(path: &Path, file: &str) -> PathBuf {
+    if path.is_dir() { path.join(file) } else { path.with_file_name(file) }
+}
+
+/// The 11 hyperparameters after the GGML magic: n_vocab, n_audio_ctx,
+/// n_audio_state, n_audio_head, n_audio_layer, n_text_ctx, n_text_state,
+/// n_text_head, n_text_layer, n_mels and ftype
+fn read_ggml_hparams(file: &mut impl Read) -> Option<[i32; 11]> {
+    let mut bytes = [0u8; 44];
+    file.read_exact(&mut bytes).ok()?;
+    Some(std::array::from_fn(|i| i32::from_le_bytes(bytes[i * 4..i * 4 + 4].try_into().unwrap())))
+}
+
+/// The architecture `models quantize` recorded, if the file has one
+fn gguf_config(content: &gguf_file::Content) -> Option<Config> {
+    let mut values = [0usize; 9];
+    for (value, key) in values.iter_mut().zip(GGUF_CONFIG_KEYS) {
+        *value = content.metadata.get(&format!("whisper.{}", key))?.to_u32().ok()? as usize;
+    }
+    let [num_mel_bins, max_source_positions, d_model, encoder_attention_heads, encoder_layers, vocab_size, max_target_positions, decoder_attention_heads, decoder_layers] =
+        values;
+    let suppress_tokens = match content.metadata.get("whisper.suppress_tokens") {
+        Some(tokens) => tokens.to_vec().ok()?.iter().filter_map(|token| token.to_u32().ok()).collect(),
+        None => vec![],
+    };
+    Some(Config {
+        num_mel_bins,
+        max_source_positions,
+        d_model,
+        encoder_attention_heads,
+        encoder_layers,
+        vocab_size,
+        max_target_positions,
+        decoder_attention_heads,
+        decoder_layers,
+        suppress_tokens,
+    })
+}
+
+/// Quantizes every matrix whose rows split into whole blocks of `dtype`; the
+/// convolutions, biases and norms stay f32, as Candle's quantized Whisper expects
+fn write_gguf(weights: &Path, path: &Path, config: &Config, dtype: GgmlDType) -> Result<()> {
+    let tensors = candle_core::safetensors::load(weights, &Device::Cpu).map_err(anyhow::Error::from)?;
+    let mut quantized = Vec::with_capacity(tensors.len());
+    for (name, tensor) in tensors {
+        let quantize = tensor.rank() == 2 && tensor.dims()[1] % dtype.block_size() == 0;
+        let tensor = QTensor::quantize(&tensor, if quantize { dtype } else { GgmlDType::F32 })
+            .map_err(anyhow::Error::from)?;
+        quantized.push((name, tensor));
+    }
+    quantized.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let values = [
+        config.num_mel_bins,
+        config.max_source_positions,
+        config.d_model,
+        config.encoder_attention_heads,
+        config.encoder_layers,
+        config.vocab_size,
+        config.max_target_positions,
+        config.decoder_attention_heads,
+        config.decoder_layers,
+    ];
+    let mut metadata: Vec<(String, gguf_file::Value)> = vec![
+        ("general.architecture".into(), gguf_file::Value::String("whisper".into())),
+        ("general.quantization".into(), gguf_file::Value::String(dtype_name(dtype).into())),
+    ];
+    for (key, value) in GGUF_CONFIG_KEYS.iter().zip(values) {
+        metadata.push((format!("whisper.{}", key), gguf_file::Value::U32(value as u32)));
+    }
+    let suppress_tokens = config.suppress_tokens.iter().map(|token| gguf_file::Value::U32(*token)).collect();
+    metadata.push(("whisper.suppress_tokens".into(), gguf_file::Value::Array(suppress_tokens)));
+
+    let metadata: Vec<_> = metadata.iter().map(|(key, value)| (key.as_str(), value)).collect();
+    let tensors: Vec<_> = quantized.iter().map(|(name, tensor)| (name.as_str(), tensor)).collect();
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    gguf_file::write(&mut file, &metadata, &tensors).map_err(anyhow::Error::from)?;
+    Ok(())
+}
+
+/// Outcome of checking a stored model's SHA256
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verification {
+    /// Matches an upstream checksum, given or recorded at download
+    Verified,
+    /// Matches the hash taken locally when the model was added, which only
+    /// shows the file has not changed since
+    LocalHash,
+    Mismatch { expected: String, actual: String },
+    /// No checksum is known for the model
+    Unchecked { actual: String },
+}
+
+/// A directory of models, one subdirectory per name, plus `manifest.json`
+#[derive(Debug, Clone)]
+pub struct ModelStore {
+    dir: PathBuf,
+    manifest: BTreeMap<String, StoredModel>,
+}
+
+impl ModelStore {
+    /// `$WAYNE_MODELS_DIR`, else `$XDG_DATA_HOME/wayne-transcriber/models`,
+    /// falling back to `~/.local/share`
+    pub fn default_dir() -> PathBuf {
+        if let Some(dir) = std::env::var_os(MODELS_DIR_ENV).filter(|dir| !dir.is_empty()) {
+            return PathBuf::from(dir);
+        }
+        let data_home = std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share")))
+            .unwrap_or_default();
+        data_home.join("wayne-transcriber").join("models")
+    }
+
+    /// Opens the store at [`ModelStore::default_dir`]
+    pub fn open_default() -> Result<Self> {
+        Self::open(Self::default_dir())
+    }
+
+    /// Opens a store; a missing directory is an empty store
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        let manifest = match std::fs::read_to_string(dir.join(MANIFEST_FILE)) {
+            Ok(content) => serde_json::from_str(&content).map_err(|e| {
+                TranscribeError::Other(anyhow::anyhow!("Corrupt model manifest in {:?}: {}", dir, e))
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self { dir, manifest })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StoredModel> {
+        self.manifest.get(name)
+    }
+
+    /// Path of a stored model's file
+    pub fn model_path(&self, name: &str) -> Result<PathBuf> {
+        let dir = self.model_dir(name)?;
+        match self.manifest.get(name) {
+            Some(model) => Ok(dir.join(&model.file)),
+            None => Err(TranscribeError::ModelNotFound(dir)),
+        }
+    }
+
+    /// Directory of the model `name`. Names are a single plain path
+    /// component, so nothing outside the store is ever written or deleted.
+    fn model_dir(&self, name: &str) -> Result<PathBuf> {
+        let mut components = Path::new(name).components();
+        let plain = matches!(
+            (components.next(), components.next()),
+            (Some(std::path::Component::Normal(component)), None) if component == name
+        );
+        if !plain || name.contains(['/', '\\']) || name == MANIFEST_FILE {
+            return Err(TranscribeError::InvalidInput(format!(
+                "invalid model name '{}'; use a plain name without path separators",
+                name
+            )));
+        }
+        let dir = self.dir.join(name);
+        if dir.parent() != Some(self.dir.as_path()) {
+            return Err(TranscribeError::InvalidInput(format!("model name '{}' leaves the store", name)));
+        }
+        Ok(dir)
+    }
+
+    /// Every stored model, by name
+    pub fn list(&self) -> Vec<ModelInfo> {
+        self.manifest
+            .iter()
+            .map(|(name, model)| {
+                let path = self.dir.join(name).join(&model.file);
+                ModelInfo {
+                    name: name.clone(),
+                    size: std::fs::metadata(&path).ok().map(|metadata| metadata.len()),
+                    format: ModelFormat::detect(&path).unwrap_or(ModelFormat::Unknown),
+                    source: model.source.clone(),
+                    path,
+                }
+            })
+            .collect()
+    }
+
+    /// Fetches a known model's checkpoint into the store, unless it is already there
+    pub async fn download(&mut self, model: WhisperModel) -> Result<PathBuf> {
+        self.download_checkpoint(model.model_name(), model.hub_repo()).await
+    }
+
+    /// Fetches a Transformers checkpoint from a Hugging Face repository, stored
+    /// under the repository name
+    pub async fn download_repo(&mut self, repo: &str) -> Result<PathBuf> {
+        let name = ModelSpec::Repo(repo.to_string()).store_name().unwrap_or(repo).to_string();
+        self.download_checkpoint(&name, repo).await
+    }
+
+    /// Fetches `config.json`, `tokenizer.json` and `model.safetensors`. The
+    /// weights are checked against the SHA256 Hugging Face reports for them.
+    async fn download_checkpoint(&mut self, name: &str, repo: &str) -> Result<PathBuf> {
+        if let Some(path) = self.existing(name) {
+            return Ok(path);
+        }
+
+        // All three land in the same snapshot directory, so `add` picks up the config and tokenizer
+        for file in SIDECAR_FILES {
+            fetch(repo, file).await?;
+        }
+        let (cached, expected) = fetch(repo, WEIGHTS_FILE).await?;
+        let source = format!("huggingface:{}", repo);
+        self.add(name, &cached, WEIGHTS_FILE, source, expected.as_deref()).await
+    }
+
+    /// Path of the checkpoint `spec` names; paths are used as given
+    pub fn locate(&self, spec: &ModelSpec) -> Result<PathBuf> {
+        match spec {
+            ModelSpec::Path(path) => Ok(path.clone()),
+            _ => self.model_path(spec.store_name().unwrap_or_default()),
+        }
+    }
+
+    fn existing(&self, name: &str) -> Option<PathBuf> {
+        let path = self.model_path(name).ok().filter(|path| path.exists())?;
+        info!("✅ {} is already in the store", name);
+        Some(path)
+    }
+
+    /// Copies a GGML, GGUF or safetensors file, or a checkpoint directory, into
+    /// the store. The name defaults to the directory name or the file stem.
+    pub async fn import(&mut self, path: &Path, name: Option<&str>, force: bool) -> Result<String> {
+        // A Transformers checkpoint directory is stored under the directory's name
+        let (path, default) = if path.is_dir() {
+            (path.join(WEIGHTS_FILE), path.file_name().map(|dir| dir.to_string_lossy().into_owned()))
+        } else {
+            (path.to_path_buf(), None)
+        };
+        let path = path.as_path();
+        if !path.is_file() {
+            return Err(TranscribeError::MissingInput(path.to_path_buf()));
+        }
+        if ModelFormat::detect(path)? == ModelFormat::Unknown {
+            return Err(TranscribeError::UnsupportedFormat(format!(
+                "{:?} is not a GGML, GGUF or safetensors model",
+                path
+            )));
+        }
+
+        let file = path.file_name().unwrap().to_string_lossy().into_owned();
+        let name = name
+            .map(str::to_string)
+            .or(default)
+            .unwrap_or_else(|| path.file_stem().unwrap_or_default().to_string_lossy().into_owned());
+        self.model_dir(&name)?;
+        if self.manifest.contains_key(&name) && !force {
+            return Err(TranscribeError::Other(anyhow::anyhow!(
+                "A model named '{}' is already stored; remove it first or pass --force",
+                name
+            )));
+        }
+        let source = format!("import:{}", std::path::absolute(path)?.display());
+        self.add(&name, path, &file, source, None).await?;
+        Ok(name)
+    }
+
+    /// Converts a safetensors checkpoint (stored or on disk) to GGUF with
+    /// quantized weights, stored as `<name>-<type>` unless `name` is given.
+    /// The architecture is written into the GGUF metadata.
+    pub async fn quantize(
+        &mut self,
+        spec: &ModelSpec,
+        quantization: Quantization,
+        name: Option<&str>,
+        force: bool,
+    ) -> Result<String> {
+        if let Some(name) = name {
+            self.model_dir(name)?;
+        }
+        let source = self.locate(spec)?;
+        let weights = if source.is_dir() { source.join(WEIGHTS_FILE) } else { source.clone() };
+        if !weights.is_file() {
+            return Err(TranscribeError::ModelNotFound(weights));
+        }
+        if !matches!(ModelFormat::detect(&weights)?, ModelFormat::Safetensors { .. }) {
+            return Err(TranscribeError::UnsupportedFormat(format!(
+                "{:?} is not a safetensors checkpoint; only safetensors can be quantized",
+                weights
+            )));
+        }
+        let config = read_config(&source)?.ok_or_else(|| {
+            TranscribeError::UnsupportedFormat(format!("{:?} has no {} describing its architecture", weights, CONFIG_FILE))
+        })?;
+
+        let base = match spec.store_name() {
+            Some(name) => name.to_string(),
+            None => source.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+        };
+        let name = name.map(str::to_string).unwrap_or_else(|| format!("{}-{}", base, quantization.name()));
+        let dir = self.model_dir(&name)?;
+        if self.manifest.contains_key(&name) && !force {
+            return Err(TranscribeError::Other(anyhow::anyhow!(
+                "A model named '{}' is already stored; remove it first or pass --force",
+                name
+            )));
+        }
+
+        if dir.exists() {
+            tokio::fs::remove_dir_all(&dir).await?;
+        }
+        tokio::fs::create_dir_all(&dir).await?;
+        let file = format!("model-{}.gguf", quantization.name());
+        let path = dir.join(&file);
+
+        info!("🗜️  Quantizing {:?} to {}...", weights, quantization.name());
+        let (from, to, dtype) = (weights.clone(), path.clone(), quantization.dtype());
+        let written = tokio::task::spawn_blocking(move || write_gguf(&from, &to, &config, dtype))
+            .await
+            .map_err(anyhow::Error::from)?;
+        if let Err(e) = written {
+            tokio::fs::remove_dir_all(&dir).await?;
+            return Err(e);
+        }
+        copy_sidecars(&weights, &dir).await?;
+
+        let before = tokio::fs::metadata(&weights).await?.len();
+        let after = tokio::fs::metadata(&path).await?.len();
+        info!(
+            "📉 {:.1} MB → {:.1} MB ({:.0}% of the original)",
+            before as f64 / 1_000_000.0,
+            after as f64 / 1_000_000.0,
+            after as f64 / before as f64 * 100.0
+        );
+        let source = format!("quantize:{} {}", std::path::absolute(&weights)?.display(), quantization.name());
+        let sha256 = sha256_file(&path).await?;
+        self.insert(&name, &file, source, sha256, false).await?;
+        Ok(name)
+    }
+
+    /// Deletes a model's directory and manifest entry
+    pub async fn remove(&mut self, name: &str) -> Result<()> {
+        let dir = self.model_dir(name)?;
+        if self.manifest.remove(name).is_none() {
+            return Err(TranscribeError::ModelNotFound(dir));
+        }
+        if dir.exists() {
+            tokio::fs::remove_dir_all(&dir).await?;
+        }
+        self.save().await?;
+        info!("🗑️  Removed {}", name);
+        Ok(())
+    }
+
+    /// Hashes a stored model and compares it with `expected`, or with the
+    /// checksum recorded when it was added. A recorded checksum that was never
+    /// checked upstream can only yield [`Verification::LocalHash`].
+    pub async fn verify(&self, name: &str, expected: Option<&str>) -> Result<Verification> {
+        let path = self.model_path(name)?;
+        if !path.exists() {
+            return Err(TranscribeError::ModelNotFound(path));
+        }
+        let actual = sha256_file(&path).await?;
+        let model = &self.manifest[name];
+        let upstream = expected.is_some() || model.sha256_upstream;
+        let expected = expected.or(model.sha256.as_deref());
+        Ok(match expected {
+            Some(expected) if expected.eq_ignore_ascii_case(&actual) && upstream => Verification::Verified,
+            Some(expected) if expected.eq_ignore_ascii_case(&actual) => Verification::LocalHash,
+            Some(expected) => Verification::Mismatch { expected: expected.to_string(), actual },
+            None => Verification::Unchecked { actual },
+        })
+    }
+
+    async fn add(&mut self, name: &str, from: &Path, file: &str, source: String, expected: Option<&str>) -> Result<PathBuf> {
+        let dir = self.model_dir(name)?;
+        if dir.exists() {
+            tokio::fs::remove_dir_all(&dir).await?;
+        }
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(file);
+        tokio::fs::copy(from, &path).await?;
+        copy_sidecars(from, &dir).await?;
+
+        let sha256 = sha256_file(&path).await?;
+        if let Some(expected) = expected
+            && !expected.eq_ignore_ascii_case(&sha256)
+        {
+            tokio::fs::remove_dir_all(&dir).await?;
+            return Err(TranscribeError::Other(anyhow::anyhow!(
+                "Checksum mismatch for {}: expected {}, got {}",
+                file,
+                expected,
+                sha256
+            )));
+        }
+        self.insert(name, file, source, sha256, expected.is_some()).await
+    }
+
+    async fn insert(&mut self, name: &str, file: &str, source: String, sha256: String, upstream: bool) -> Result<PathBuf> {
+        let path = self.model_dir(name)?.join(file);
+        let model = StoredModel {
+            file: file.to_string(),
+            source,
+            sha256: Some(sha256),
+            sha256_upstream: upstream,
+            added: Utc::now(),
+        };
+        self.manifest.insert(name.to_string(), model);
+        self.save().await?;
+        info!("✅ Stored {} at {:?}", name, path);
+        Ok(path)
+    }
+
+    async fn save(&self) -> Result<()> {
+        tokio::fs::create_dir_all(&self.dir).await?;
+        let content = serde_json::to_string_pretty(&self.manifest).map_err(anyhow::Error::from)?;
+        tokio::fs::write(self.dir.join(MANIFEST_FILE), content).await?;
+        Ok(())
+    }
+}
+
+/// Copies the config and tokenizer found beside `weights` into `dir`
+async fn copy_sidecars(weights: &Path, dir: &Path) -> Result<()> {
+    for file in SIDECAR_FILES {
+        let sidecar = weights.with_file_name(file);
+        if sidecar.is_file() {
+            tokio::fs::copy(&sidecar, dir.join(file)).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Downloads a file into the Hugging Face cache, returning its path and the
+/// SHA256 the cache names LFS blobs after (`None` for small git files)
+async fn fetch(repo: &str, file: &str) -> Result<(PathBuf, Option<String>)> {
+    info!("⬇️  Downloading {} from {}", file, repo);
+    let (repo, file) = (repo.to_string(), file.to_string());
+    let cached = tokio::task::spawn_blocking(move || {
+        hf_hub::api::sync::Api::new()
+            .and_then(|api| api.model(repo.clone()).get(&file))
+            .map_err(|e| TranscribeError::Other(anyhow::anyhow!("Failed to download {} from {}: {}", file, repo, e)))
+    })
+    .await
+    .map_err(anyhow::Error::from)??;
+
+    let expected = std::fs::canonicalize(&cached)
+        .ok()
+        .and_then(|blob| blob.file_name().map(|name| name.to_string_lossy().into_owned()))
+        .filter(|name| name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit()));
+    Ok((cached, expected))
+}
+
+/// Parses a `sha256sum`-style manifest: `<hash>  <file name>` per line
+pub fn parse_checksums(content: &str) -> BTreeMap<String, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let (hash, file) = line.trim().split_once(char::is_whitespace)?;
+            let file = file.trim().trim_start_matches('*');
+            let file = Path::new(file).file_name()?.to_string_lossy().into_owned();
+            Some((file, hash.to_lowercase()))
+        })
+        .collect()
+}
+
+async fn sha256_file(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path)?;
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1 << 20];
+        loop {
+            let read = file.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(format!("{:x}", hasher.finalize()))
+    })
+    .await
+    .map_err(anyhow::Error::from)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A GGML header with tiny hyperparameters and the given ftype
+    fn ggml_file(path: &Path, ftype: i32) {
+        let mut bytes = GGML_MAGIC.to_le_bytes().to_vec();
+        for value in [51865, 1500, 384, 6, 4, 448, 384, 6, 4, 80, ftype] {
+            bytes.extend_from_slice(&i32::to_le_bytes(value));
+        }
+        bytes.extend_from_slice(b"weights");
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let ggml = dir.path().join("ggml-tiny-q5_1.bin");
+        ggml_file(&ggml, 1009);
+        assert_eq!(ModelFormat::detect(&ggml).unwrap(), ModelFormat::Ggml { quantization: "q5_1" });
+
+        let header = br#"{"a":{"dtype":"F16","shape":[1],"data_offsets":[0,2]},"__metadata__":{"format":"pt"}}"#;
+        let mut safetensors = (header.len() as u64).to_le_bytes().to_vec();
+        safetensors.extend_from_slice(header);
+        let path = dir.path().join("model.safetensors");
+        std::fs::write(&path, safetensors).unwrap();
+        assert_eq!(ModelFormat::detect(&path).unwrap(), ModelFormat::Safetensors { dtype: "f16".into() });
+
+        let placeholder = dir.path().join("placeholder.bin");
+        std::fs::write(&placeholder, b"placeholder").unwrap();
+        assert_eq!(ModelFormat::detect(&placeholder).unwrap(), ModelFormat::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_import_verify_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("ggml-base.bin");
+        ggml_file(&original, 1);
+        let mut store = ModelStore::open(dir.path().join("store")).unwrap();
+
+        let name = store.import(&original, Some("base"), false).await.unwrap();
+        assert_eq!(name, "base");
+        assert!(store.import(&original, Some("base"), false).await.is_err());
+
+        // The manifest is read back from any working directory
+        let reopened = ModelStore::open(store.dir()).unwrap();
+        let path = reopened.model_path("base").unwrap();
+        assert_eq!(path, dir.path().join("store/base/ggml-base.bin"));
+        let listed = reopened.list();
+        assert_eq!(listed[0].format.to_string(), "ggml f16");
+        assert!(listed[0].source.starts_with("import:"));
+
+        // An import has no upstream checksum, only the one taken when it was stored
+        assert_eq!(reopened.verify("base", None).await.unwrap(), Verification::LocalHash);
+        let sha256 = reopened.get("base").unwrap().sha256.clone().unwrap();
+        assert_eq!(reopened.verify("base", Some(&sha256)).await.unwrap(), Verification::Verified);
+        std::fs::write(&path, b"corrupted").unwrap();
+        assert!(matches!(reopened.verify("base", None).await.unwrap(), Verification::Mismatch { .. }));
+
+        store.remove("base").await.unwrap();
+        assert!(!dir.path().join("store/base").exists());
+        assert!(matches!(store.model_path("base"), Err(TranscribeError::ModelNotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_names_stay_inside_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("ggml-base.bin");
+        ggml_file(&original, 1);
+        let victim = dir.path().join("victim");
+        std::fs::create_dir(&victim).unwrap();
+        std::fs::write(victim.join("important.txt"), "keep me").unwrap();
+        let mut store = ModelStore::open(dir.path().join("store")).unwrap();
+        store.import(&original, Some("base"), false).await.unwrap();
+
+        let outside = victim.to_str().unwrap();
+        for name in ["", ".", "..", "../victim", outside, "a/b", "a\\b", MANIFEST_FILE] {
+            let imported = store.import(&original, Some(name), true).await;
+            assert!(matches!(imported, Err(TranscribeError::InvalidInput(_))), "{:?}", name);
+            let quantized = store.quantize(&ModelSpec::Stored("base".into()), Quantization::Q8_0, Some(name), true).await;
+            assert!(matches!(quantized, Err(TranscribeError::InvalidInput(_))), "{:?}", name);
+            assert!(matches!(store.remove(name).await, Err(TranscribeError::InvalidInput(_))), "{:?}", name);
+        }
+        assert!(victim.join("important.txt").exists());
+        assert!(store.model_path("base").unwrap().exists());
+    }
+
+    #[tokio::test]
+    async fn test_import_rejects_non_models() {
+        let dir = tempfile::tempdir().unwrap();
+        let notes = dir.path().join("notes.txt");
+        std::fs::write(&notes, "not a model").unwrap();
+        let mut store = ModelStore::open(dir.path().join("store")).unwrap();
+
+        let result = store.import(&notes, None, false).await;
+
+        assert!(matches!(result, Err(TranscribeError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn test_read_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let ggml = dir.path().join("ggml-tiny.bin");
+        ggml_file(&ggml, 1);
+        let config = read_config(&ggml).unwrap().unwrap();
+        assert_eq!((config.num_mel_bins, config.d_model, config.decoder_layers), (80, 384, 4));
+
+        // A Transformers checkpoint directory, laid out like large-v3-turbo
+        let checkpoint = dir.path().join("whisper-large-v3-turbo");
+        std::fs::create_dir(&checkpoint).unwrap();
+        std::fs::write(
+            checkpoint.join(CONFIG_FILE),
+            r#"{"num_mel_bins": 128, "max_source_positions": 1500, "d_model": 1280,
+                "encoder_attention_heads": 20, "encoder_layers": 32, "vocab_size": 51866,
+                "max_target_positions": 448, "decoder_attention_heads": 20, "decoder_layers": 4,
+                "model_type": "whisper"}"#,
+        )
+        .unwrap();
+        let config = read_config(&checkpoint).unwrap().unwrap();
+        assert_eq!((config.num_mel_bins, config.encoder_layers, config.decoder_layers), (128, 32, 4));
+        assert_eq!(read_config(&checkpoint.join(WEIGHTS_FILE)).unwrap(), Some(config));
+
+        let placeholder = dir.path().join("placeholder.bin");
+        std::fs::write(&placeholder, b"placeholder").unwrap();
+        assert_eq!(read_config(&placeholder).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_import_checkpoint_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("my-finetune");
+        std::fs::create_dir(&checkpoint).unwrap();
+        let header = br#"{"a":{"dtype":"F32","shape":[1],"data_offsets":[0,4]}}"#;
+        let mut weights = (header.len() as u64).to_le_bytes().to_vec();
+        weights.extend_from_slice(header);
+        std::fs::write(checkpoint.join(WEIGHTS_FILE), weights).unwrap();
+        std::fs::write(checkpoint.join(CONFIG_FILE), "{}").unwrap();
+        std::fs::write(checkpoint.join(TOKENIZER_FILE), "{}").unwrap();
+        let mut store = ModelStore::open(dir.path().join("store")).unwrap();
+
+        let name = store.import(&checkpoint, None, false).await.unwrap();
+
+        assert_eq!(name, "my-finetune");
+        let path = store.locate(&ModelSpec::Stored(name)).unwrap();
+        assert_eq!(path, dir.path().join("store/my-finetune/model.safetensors"));
+        assert!(path.with_file_name(CONFIG_FILE).is_file());
+        assert!(path.with_file_name(TOKENIZER_FILE).is_file());
+    }
+
+    /// A randomly initialised Whisper checkpoint directory with a tiny architecture
+    fn random_checkpoint(dir: &Path) -> Config {
+        let config_json = r#"{"num_mel_bins": 80, "max_source_positions": 1500, "d_model": 64,
+            "encoder_attention_heads": 2, "encoder_layers": 1, "vocab_size": 64, "max_target_positions": 32,
+            "decoder_attention_heads": 2, "decoder_layers": 1}"#;
+        let config: Config = serde_json::from_str(config_json).unwrap();
+        let varmap = candle_nn::VarMap::new();
+        let vb = candle_nn::VarBuilder::from_varmap(&varmap, candle_core::DType::F32, &Device::Cpu);
+        candle_transformers::models::whisper::model::Whisper::load(&vb, config.clone()).unwrap();
+        std::fs::create_dir_all(dir).unwrap();
+        varmap.save(dir.join(WEIGHTS_FILE)).unwrap();
+        std::fs::write(dir.join(CONFIG_FILE), config_json).unwrap();
+        // Only the control tokens the decoder needs to start a window
+        let tokenizer = r#"{"version": "1.0", "truncation": null, "padding": null, "added_tokens": [],
+            "normalizer": null, "pre_tokenizer": null, "post_processor": null, "decoder": null,
+            "model": {"type": "WordLevel", "unk_token": "<|endoftext|>", "vocab": {"<|endoftext|>": 0,
+                "<|startoftranscript|>": 1, "<|transcribe|>": 2, "<|notimestamps|>": 3}}}"#;
+        std::fs::write(dir.join(TOKENIZER_FILE), tokenizer).unwrap();
+        config
+    }
+
+    #[tokio::test]
+    async fn test_quantize_to_gguf() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("tiny-random");
+        let config = random_checkpoint(&checkpoint);
+        let mut store = ModelStore::open(dir.path().join("store")).unwrap();
+
+        let spec = ModelSpec::Path(checkpoint.clone());
+        let name = store.quantize(&spec, Quantization::Q8_0, None, false).await.unwrap();
+
+        assert_eq!(name, "tiny-random-q8_0");
+        let path = store.model_path(&name).unwrap();
+        assert_eq!(ModelFormat::detect(&path).unwrap(), ModelFormat::Gguf { quantization: "q8_0" });
+        assert_eq!(read_config(&path).unwrap(), Some(config));
+        let original = std::fs::metadata(checkpoint.join(WEIGHTS_FILE)).unwrap().len();
+        assert!(std::fs::metadata(&path).unwrap().len() < original);
+        assert!(store.quantize(&spec, Quantization::Q8_0, None, false).await.is_err());
+
+        let model = crate::transcription::load_model(&path, None, Device::Cpu).unwrap();
+        assert!(matches!(model.weights, crate::decoder::WhisperWeights::Quantized(_)));
+
+        // Only safetensors checkpoints are converted
+        let gguf = ModelSpec::Stored(name);
+        let result = store.quantize(&gguf, Quantization::Q4_0, None, false).await;
+        assert!(matches!(result, Err(TranscribeError::UnsupportedFormat(_))));
+    }
+
+    #[test]
+    fn test_parse_checksums() {
+        let checksums = parse_checksums("ABC123  models/ggml-tiny.bin\ndef456 *ggml-base.bin\n\n");
+
+        assert_eq!(checksums["ggml-tiny.bin"], "abc123");
+        assert_eq!(checksums["ggml-base.bin"], "def456");
+    }
+}
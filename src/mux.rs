@@ -0,0 +1,226 @@
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{info, warn};
+use crate::audio;
+use crate::error::{Result, TranscribeError};
+use crate::output;
+use crate::transcription::TranscriptionResult;
+
+/// Subtitle stream codecs FFmpeg can write into the supported containers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubtitleCodec {
+    /// MP4 / MOV timed text
+    MovText,
+    Srt,
+    Ass,
+    WebVtt,
+}
+
+impl FromStr for SubtitleCodec {
+    type Err = TranscribeError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "mov_text" => Ok(Self::MovText),
+            "srt" | "subrip" => Ok(Self::Srt),
+            "ass" => Ok(Self::Ass),
+            "webvtt" | "vtt" => Ok(Self::WebVtt),
+            _ => Err(TranscribeError::UnsupportedFormat(format!(
+                "subtitle codec '{}'. Use mov_text, srt, ass or webvtt",
+                s
+            ))),
+        }
+    }
+}
+
+impl SubtitleCodec {
+    /// Default codec for a container, chosen by file extension
+    pub fn for_container(path: &Path) -> Result<Self> {
+        match container(path).as_str() {
+            "mp4" | "m4v" | "mov" => Ok(Self::MovText),
+            "mkv" => Ok(Self::Srt),
+            "webm" => Ok(Self::WebVtt),
+            other => Err(TranscribeError::UnsupportedFormat(format!(
+                "cannot embed subtitles in '{}' files; use mp4, mov, mkv or webm",
+                other
+            ))),
+        }
+    }
+
+    /// Whether the container at `path` can hold this codec
+    fn fits(self, path: &Path) -> bool {
+        match container(path).as_str() {
+            "mp4" | "m4v" | "mov" => self == Self::MovText,
+            "mkv" => self != Self::MovText,
+            "webm" => self == Self::WebVtt,
+            _ => false,
+        }
+    }
+
+    fn ffmpeg_name(self) -> &'static str {
+        match self {
+            Self::MovText => "mov_text",
+            Self::Srt => "srt",
+            Self::Ass => "ass",
+            Self::WebVtt => "webvtt",
+        }
+    }
+}
+
+fn container(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+/// Writes a copy of `video` with the transcription as a soft subtitle track.
+/// Video and audio are copied without re-encoding; existing subtitle tracks are dropped.
+pub async fn embed_subtitles(
+    transcription: &TranscriptionResult,
+    video: &Path,
+    output_path: &Path,
+    language: &str,
+    codec: Option<SubtitleCodec>,
+) -> Result<()> {
+    let codec = match codec {
+        Some(codec) if codec.fits(output_path) => codec,
+        Some(codec) => {
+            return Err(TranscribeError::UnsupportedFormat(format!(
+                "{} subtitles in {:?}",
+                codec.ffmpeg_name(),
+                output_path
+            )));
+        }
+        None => SubtitleCodec::for_container(output_path)?,
+    };
+
+    let subtitles = write_subtitles(transcription, codec).await?;
+    info!("🎞️  Embedding {} subtitles into {:?}", codec.ffmpeg_name(), output_path);
+    let result = audio::run_ffmpeg(embed_args(video, &subtitles, output_path, language, codec)).await;
+    remove_temp(&subtitles).await;
+    result
+}
+
+/// Writes a re-encoded copy of `video` with the transcription rendered into the picture
+pub async fn burn_subtitles(transcription: &TranscriptionResult, video: &Path, output_path: &Path) -> Result<()> {
+    let subtitles = write_subtitles(transcription, SubtitleCodec::Srt).await?;
+    info!("🔥 Burning subtitles into {:?}", output_path);
+    let result = audio::run_ffmpeg(burn_args(video, &subtitles, output_path)).await;
+    remove_temp(&subtitles).await;
+    result
+}
+
+/// Default path for a processed copy of a video: `talk.mp4` → `talk.<suffix>.mp4`
+pub fn copy_path(video: &Path, suffix: &str) -> PathBuf {
+    let stem = video.file_stem().and_then(|s| s.to_str()).unwrap_or("video");
+    let name = match video.extension().and_then(|e| e.to_str()) {
+        Some(extension) => format!("{}.{}.{}", stem, suffix, extension),
+        None => format!("{}.{}", stem, suffix),
+    };
+    video.with_file_name(name)
+}
+
+/// Writes the subtitles with the `output` writers to a temporary file FFmpeg can read
+async fn write_subtitles(transcription: &TranscriptionResult, codec: SubtitleCodec) -> Result<PathBuf> {
+    let (content, extension) = match codec {
+        SubtitleCodec::WebVtt => (output::format_as_vtt(transcription), "vtt"),
+        // FFmpeg converts SRT input to mov_text and ASS itself
+        _ => (output::format_as_srt(transcription), "srt"),
+    };
+    let path = std::env::temp_dir().join(format!(
+        "wayne_subtitles_{}_{}.{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_millis(),
+        extension
+    ));
+    tokio::fs::write(&path, content).await?;
+    Ok(path)
+}
+
+async fn remove_temp(path: &Path) {
+    if let Err(e) = tokio::fs::remove_file(path).await {
+        warn!("Could not clean up temporary subtitle file: {}", e);
+    }
+}
+
+fn embed_args(
+    video: &Path,
+    subtitles: &Path,
+    output_path: &Path,
+    language: &str,
+    codec: SubtitleCodec,
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["-i".into(), video.into(), "-i".into(), subtitles.into()];
+    args.extend(
+        ["-map", "0:v?", "-map", "0:a?", "-map", "1:0", "-c", "copy", "-c:s", codec.ffmpeg_name()].map(OsString::from),
+    );
+    args.push("-metadata:s:s:0".into());
+    args.push(format!("language={}", language).into());
+    args.push(output_path.into());
+    args
+}
+
+fn burn_args(video: &Path, subtitles: &Path, output_path: &Path) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["-i".into(), video.into(), "-vf".into()];
+    args.push(format!("subtitles={}", escape_filter_path(subtitles)).into());
+    args.extend(["-c:a", "copy"].map(OsString::from));
+    args.push(output_path.into());
+    args
+}
+
+/// Quotes a path for the `subtitles` filter. Quoting protects it from the
+/// filtergraph parser; colons are escaped once more for the option parser.
+fn escape_filter_path(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    format!("'{}'", path.replace(':', r"\:"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codec_for_container() {
+        assert_eq!(SubtitleCodec::for_container(Path::new("talk.MP4")).unwrap(), SubtitleCodec::MovText);
+        assert_eq!(SubtitleCodec::for_container(Path::new("talk.mkv")).unwrap(), SubtitleCodec::Srt);
+        assert_eq!(SubtitleCodec::for_container(Path::new("talk.webm")).unwrap(), SubtitleCodec::WebVtt);
+        assert!(matches!(
+            SubtitleCodec::for_container(Path::new("talk.avi")),
+            Err(TranscribeError::UnsupportedFormat(_))
+        ));
+
+        assert!(SubtitleCodec::Ass.fits(Path::new("talk.mkv")));
+        assert!(!SubtitleCodec::Ass.fits(Path::new("talk.mp4")));
+    }
+
+    #[test]
+    fn test_embed_args() {
+        let args = embed_args(
+            Path::new("talk.mkv"),
+            Path::new("/tmp/subs.srt"),
+            Path::new("talk.subtitled.mkv"),
+            "eng",
+            SubtitleCodec::Ass,
+        );
+        let args: Vec<_> = args.iter().map(|arg| arg.to_str().unwrap()).collect();
+
+        assert_eq!(&args[..4], ["-i", "talk.mkv", "-i", "/tmp/subs.srt"]);
+        assert!(args.windows(2).any(|pair| pair == ["-c:s", "ass"]));
+        assert!(args.windows(2).any(|pair| pair == ["-metadata:s:s:0", "language=eng"]));
+        assert_eq!(args.last(), Some(&"talk.subtitled.mkv"));
+    }
+
+    #[test]
+    fn test_burn_args_escape_path() {
+        let args = burn_args(Path::new("talk.mp4"), Path::new("C:\\tmp\\subs.srt"), Path::new("out.mp4"));
+
+        assert_eq!(args[3], OsString::from(r"subtitles='C\:/tmp/subs.srt'"));
+    }
+
+    #[test]
+    fn test_copy_path() {
+        assert_eq!(copy_path(Path::new("/videos/talk.mp4"), "subtitled"), Path::new("/videos/talk.subtitled.mp4"));
+    }
+}
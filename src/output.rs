@@ -1,24 +1,88 @@
-use anyhow::{Context, Result};
+use anyhow::Context;
 use std::path::Path;
+use std::str::FromStr;
 use tokio::fs;
 use tracing::{info, debug};
-use crate::transcription::TranscriptionResult;
+use crate::error::{Result, TranscribeError};
+use crate::transcription::{SegmentFlag, TranscriptionResult, TranscriptionSegment, LOGPROB_THRESHOLD};
 
 /// Supported output formats
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, clap::ValueEnum)]
 pub enum OutputFormat {
+    #[value(name = "txt", alias = "text")]
     Text,
     Srt,
+    #[value(alias = "webvtt")]
     Vtt,
+    Json,
+    Ttml,
 }
 
-impl OutputFormat {
-    pub fn from_str(s: &str) -> Result<Self> {
+impl FromStr for OutputFormat {
+    type Err = TranscribeError;
+
+    fn from_str(s: &str) -> Result<Self> {
         match s.to_lowercase().as_str() {
             "txt" | "text" => Ok(Self::Text),
             "srt" => Ok(Self::Srt),
             "vtt" | "webvtt" => Ok(Self::Vtt),
-            _ => anyhow::bail!("Unsupported output format: {}. Use: txt, srt, vtt", s),
+            "json" => Ok(Self::Json),
+            "ttml" => Ok(Self::Ttml),
+            _ => Err(TranscribeError::UnsupportedFormat(format!(
+                "output format {}. Use: txt, srt, vtt, json, ttml",
+                s
+            ))),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// File extension, also accepted by [`FromStr`]
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Text => "txt",
+            Self::Srt => "srt",
+            Self::Vtt => "vtt",
+            Self::Json => "json",
+            Self::Ttml => "ttml",
+        }
+    }
+
+    /// MIME type used when serving this format over HTTP
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Self::Text => "text/plain; charset=utf-8",
+            Self::Srt => "application/x-subrip",
+            Self::Vtt => "text/vtt; charset=utf-8",
+            Self::Json => "application/json",
+            Self::Ttml => "application/ttml+xml",
+        }
+    }
+
+    /// Timed caption formats, which [`CaptionLimits`] apply to
+    pub fn is_caption(&self) -> bool {
+        matches!(self, Self::Srt | Self::Vtt | Self::Ttml)
+    }
+}
+
+/// Layout limits for caption cues; `None` leaves that limit off
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CaptionLimits {
+    /// Characters per caption line; longer segments are wrapped and split into several cues
+    pub max_line_chars: Option<usize>,
+    /// Lines per cue when wrapping
+    pub max_lines: usize,
+    /// Reading speed in characters per second; faster cues stay on screen
+    /// longer, up to the start of the next cue
+    pub max_cps: Option<f64>,
+}
+
+impl Default for CaptionLimits {
+    fn default() -> Self {
+        Self {
+            max_line_chars: None,
+            max_lines: 2,
+            max_cps: None,
         }
     }
 }
@@ -29,27 +93,37 @@ pub async fn save_transcription(
     output_path: &Path,
     format: &str,
 ) -> Result<()> {
-    let format = OutputFormat::from_str(format)
-        .context("Invalid output format specified")?;
+    let format = OutputFormat::from_str(format)?;
 
-    let content = match format {
-        OutputFormat::Text => format_as_text(transcription),
-        OutputFormat::Srt => format_as_srt(transcription),
-        OutputFormat::Vtt => format_as_vtt(transcription),
-    };
+    let content = format_transcription(transcription, &format)?;
 
     info!("💾 Saving transcription to {:?} in {:?} format", output_path, format);
     
-    fs::write(output_path, content)
-        .await
-        .context("Failed to write transcription file")?;
+    fs::write(output_path, content).await?;
 
     debug!("Transcription saved successfully");
     Ok(())
 }
 
+/// Render a transcription in the given format
+pub fn format_transcription(transcription: &TranscriptionResult, format: &OutputFormat) -> Result<String> {
+    Ok(match format {
+        OutputFormat::Text => format_as_text(transcription),
+        OutputFormat::Srt => format_as_srt(transcription),
+        OutputFormat::Vtt => format_as_vtt(transcription),
+        OutputFormat::Json => format_as_json(transcription)?,
+        OutputFormat::Ttml => format_as_ttml(transcription),
+    })
+}
+
 /// Format transcription as plain text
-fn format_as_text(transcription: &TranscriptionResult) -> String {
+pub fn format_as_text(transcription: &TranscriptionResult) -> String {
+    let body = if transcription.segments.iter().any(|seg| seg.speaker.is_some()) {
+        format_speaker_turns(transcription)
+    } else {
+        transcription.full_text.clone()
+    };
+
     format!(
         "Wayne Dyer Video Transcription\n\
         ==============================\n\n\
@@ -57,13 +131,35 @@ fn format_as_text(transcription: &TranscriptionResult) -> String {
         ---\n\
         Generated by Wayne Transcriber\n\
         Transcribed on: {}\n",
-        transcription.full_text,
+        body,
         chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
     )
 }
 
+/// Group consecutive segments by speaker into "Speaker 1: ..." paragraphs
+fn format_speaker_turns(transcription: &TranscriptionResult) -> String {
+    let mut turns: Vec<(Option<&str>, Vec<&str>)> = Vec::new();
+
+    for segment in &transcription.segments {
+        let speaker = segment.speaker.as_deref();
+        match turns.last_mut() {
+            Some((current, texts)) if *current == speaker => texts.push(segment.text.trim()),
+            _ => turns.push((speaker, vec![segment.text.trim()])),
+        }
+    }
+
+    turns
+        .iter()
+        .map(|(speaker, texts)| match speaker {
+            Some(name) => format!("{}: {}", name, texts.join(" ")),
+            None => texts.join(" "),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
 /// Format transcription as SRT subtitle file
-fn format_as_srt(transcription: &TranscriptionResult) -> String {
+pub fn format_as_srt(transcription: &TranscriptionResult) -> String {
     let mut srt_content = String::new();
     
     for (index, segment) in transcription.segments.iter().enumerate() {
@@ -72,7 +168,10 @@ fn format_as_srt(transcription: &TranscriptionResult) -> String {
             index + 1,
             format_srt_timestamp(segment.start_time),
             format_srt_timestamp(segment.end_time),
-            segment.text.trim()
+            match &segment.speaker {
+                Some(speaker) => format!("{}: {}", speaker, segment.text.trim()),
+                None => segment.text.trim().to_string(),
+            }
         ));
     }
     
@@ -80,7 +179,7 @@ fn format_as_srt(transcription: &TranscriptionResult) -> String {
 }
 
 /// Format transcription as WebVTT subtitle file
-fn format_as_vtt(transcription: &TranscriptionResult) -> String {
+pub fn format_as_vtt(transcription: &TranscriptionResult) -> String {
     let mut vtt_content = String::from("WEBVTT\n\n");
     
     for segment in &transcription.segments {
@@ -88,15 +187,328 @@ fn format_as_vtt(transcription: &TranscriptionResult) -> String {
             "{} --> {}\n{}\n\n",
             format_vtt_timestamp(segment.start_time),
             format_vtt_timestamp(segment.end_time),
-            segment.text.trim()
+            match &segment.speaker {
+                Some(speaker) => format!("<v {}>{}", speaker, segment.text.trim()),
+                None => segment.text.trim().to_string(),
+            }
         ));
     }
     
     vtt_content
 }
 
+/// Format transcription as a TTML document, one paragraph per cue
+pub fn format_as_ttml(transcription: &TranscriptionResult) -> String {
+    let mut ttml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <tt xmlns=\"http://www.w3.org/ns/ttml\" xml:lang=\"{}\">\n  <body>\n    <div>\n",
+        escape_html(transcription.language.as_deref().unwrap_or_default())
+    );
+
+    for segment in &transcription.segments {
+        let text = match &segment.speaker {
+            Some(speaker) => format!("{}: {}", speaker, segment.text.trim()),
+            None => segment.text.trim().to_string(),
+        };
+        let lines: Vec<String> = text.lines().map(escape_html).collect();
+        ttml.push_str(&format!(
+            "      <p begin=\"{}\" end=\"{}\">{}</p>\n",
+            format_vtt_timestamp(segment.start_time),
+            format_vtt_timestamp(segment.end_time),
+            lines.join("<br/>")
+        ));
+    }
+
+    ttml.push_str("    </div>\n  </body>\n</tt>\n");
+    ttml
+}
+
+/// Format transcription as JSON, including per-segment confidence and flags
+pub fn format_as_json(transcription: &TranscriptionResult) -> Result<String> {
+    Ok(serde_json::to_string_pretty(transcription).context("Failed to serialize transcription as JSON")?)
+}
+
+/// A copy of the transcription laid out as captions: segments are wrapped
+/// into lines of at most `max_line_chars` (a single longer word keeps its own
+/// line) and split into cues of at most `max_lines` lines, then cues read
+/// faster than `max_cps` are held until they can be read or the next begins.
+pub fn fit_captions(transcription: &TranscriptionResult, limits: &CaptionLimits) -> TranscriptionResult {
+    let mut result = transcription.clone();
+    if let Some(max_chars) = limits.max_line_chars {
+        result.segments = transcription
+            .segments
+            .iter()
+            .flat_map(|segment| wrap_segment(segment, max_chars.max(1), limits.max_lines.max(1)))
+            .collect();
+    }
+
+    if let Some(max_cps) = limits.max_cps.filter(|cps| *cps > 0.0) {
+        for index in 0..result.segments.len() {
+            let next_start = result.segments.get(index + 1).map(|segment| segment.start_time);
+            let segment = &mut result.segments[index];
+            let chars = segment.text.chars().filter(|c| *c != '\n').count() as f64;
+            let readable_end = segment.start_time + chars / max_cps;
+            if readable_end > segment.end_time {
+                let end = next_start.map_or(readable_end, |next| readable_end.min(next));
+                segment.end_time = end.max(segment.end_time);
+            }
+        }
+    }
+    result
+}
+
+/// Splits one segment into cues of wrapped lines. Cue times come from the
+/// word timings when they line up with the text, otherwise the segment's
+/// duration is shared out by word length.
+fn wrap_segment(segment: &TranscriptionSegment, max_chars: usize, max_lines: usize) -> Vec<TranscriptionSegment> {
+    let words: Vec<&str> = segment.text.split_whitespace().collect();
+    if words.is_empty() {
+        return vec![segment.clone()];
+    }
+    let aligned = segment.words.len() == words.len();
+    let times: Vec<(f64, f64)> = if aligned {
+        segment.words.iter().map(|word| (word.start_time, word.end_time)).collect()
+    } else {
+        let total: usize = words.iter().map(|word| word.chars().count()).sum();
+        let duration = segment.end_time - segment.start_time;
+        let mut start = segment.start_time;
+        words
+            .iter()
+            .map(|word| {
+                let end = start + duration * word.chars().count() as f64 / total.max(1) as f64;
+                let time = (start, end);
+                start = end;
+                time
+            })
+            .collect()
+    };
+
+    // Word ranges of each cue, each cue a list of lines
+    let mut cues: Vec<(usize, usize, Vec<String>)> = Vec::new();
+    let mut first = 0;
+    let mut lines: Vec<String> = vec![String::new()];
+    for (index, word) in words.iter().enumerate() {
+        let line = lines.last_mut().unwrap();
+        if line.is_empty() {
+            line.push_str(word);
+            continue;
+        }
+        if line.chars().count() + 1 + word.chars().count() <= max_chars {
+            line.push(' ');
+            line.push_str(word);
+            continue;
+        }
+        if lines.len() == max_lines {
+            cues.push((first, index, std::mem::take(&mut lines)));
+            first = index;
+        }
+        lines.push(word.to_string());
+    }
+    cues.push((first, words.len(), lines));
+
+    let last = cues.len() - 1;
+    cues.into_iter()
+        .enumerate()
+        .map(|(cue, (first, end, lines))| TranscriptionSegment {
+            start_time: if cue == 0 { segment.start_time } else { times[first].0 },
+            end_time: if cue == last { segment.end_time } else { times[end - 1].1 },
+            text: lines.join("\n"),
+            words: if aligned { segment.words[first..end].to_vec() } else { Vec::new() },
+            ..segment.clone()
+        })
+        .collect()
+}
+
+/// A run of consecutive low-confidence segments that needs human review
+#[derive(Debug, Clone)]
+struct ReviewPassage<'a> {
+    segments: Vec<&'a TranscriptionSegment>,
+}
+
+impl ReviewPassage<'_> {
+    fn start_time(&self) -> f64 {
+        self.segments.first().map_or(0.0, |seg| seg.start_time)
+    }
+
+    fn end_time(&self) -> f64 {
+        self.segments.last().map_or(0.0, |seg| seg.end_time)
+    }
+
+    fn confidence(&self) -> f32 {
+        self.segments.iter().map(|seg| seg.confidence).fold(1.0, f32::min)
+    }
+
+    fn flags(&self) -> Vec<SegmentFlag> {
+        let mut flags: Vec<SegmentFlag> = Vec::new();
+        for flag in self.segments.iter().flat_map(|seg| &seg.flags) {
+            if !flags.contains(flag) {
+                flags.push(*flag);
+            }
+        }
+        flags
+    }
+
+    /// Lowest average log-probability and highest no-speech probability in the passage
+    fn scores(&self) -> (Option<f32>, Option<f32>) {
+        let logprob = self.segments.iter().filter_map(|seg| seg.avg_logprob).reduce(f32::min);
+        let no_speech = self.segments.iter().filter_map(|seg| seg.no_speech_prob).reduce(f32::max);
+        (logprob, no_speech)
+    }
+}
+
+/// Write a Markdown (or HTML, for `.html` paths) list of passages whose
+/// confidence is below `threshold` or that carry validation flags
+pub async fn save_review_report(
+    transcription: &TranscriptionResult,
+    report_path: &Path,
+    threshold: f32,
+) -> Result<()> {
+    let passages = review_passages(transcription, threshold);
+    let html = report_path
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("html") || e.eq_ignore_ascii_case("htm"));
+
+    let content = if html {
+        format_review_html(transcription, &passages, threshold)
+    } else {
+        format_review_markdown(transcription, &passages, threshold)
+    };
+
+    info!("📝 Writing review report with {} passage(s) to {:?}", passages.len(), report_path);
+    fs::write(report_path, content).await?;
+    Ok(())
+}
+
+/// Group consecutive segments needing review into passages
+fn review_passages(transcription: &TranscriptionResult, threshold: f32) -> Vec<ReviewPassage<'_>> {
+    let mut passages: Vec<ReviewPassage> = Vec::new();
+    let mut previous_flagged = false;
+
+    for segment in &transcription.segments {
+        let flagged = segment.confidence < threshold || !segment.flags.is_empty();
+        if flagged {
+            match passages.last_mut() {
+                Some(passage) if previous_flagged => passage.segments.push(segment),
+                _ => passages.push(ReviewPassage { segments: vec![segment] }),
+            }
+        }
+        previous_flagged = flagged;
+    }
+
+    passages
+}
+
+/// Text of a segment, with `mark` applied to low-probability words when word scores exist
+fn review_text(segment: &TranscriptionSegment, escape: fn(&str) -> String, mark: fn(&str) -> String) -> String {
+    if segment.words.is_empty() {
+        return escape(segment.text.trim());
+    }
+
+    segment
+        .words
+        .iter()
+        .map(|word| {
+            let text = escape(word.word.trim());
+            match word.avg_logprob {
+                Some(logprob) if logprob < LOGPROB_THRESHOLD => mark(&text),
+                _ => text,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn describe_scores(passage: &ReviewPassage) -> String {
+    let mut details = vec![format!("confidence {:.2}", passage.confidence())];
+    let (logprob, no_speech) = passage.scores();
+    if let Some(logprob) = logprob {
+        details.push(format!("avg log-prob {:.2}", logprob));
+    }
+    if let Some(no_speech) = no_speech {
+        details.push(format!("no-speech {:.2}", no_speech));
+    }
+    let flags = passage.flags();
+    if !flags.is_empty() {
+        let names: Vec<String> = flags
+            .iter()
+            .map(|flag| serde_json::to_value(flag).ok().and_then(|v| v.as_str().map(String::from)).unwrap_or_default())
+            .collect();
+        details.push(names.join(", "));
+    }
+    details.join(" · ")
+}
+
+fn format_review_markdown(transcription: &TranscriptionResult, passages: &[ReviewPassage], threshold: f32) -> String {
+    let mut report = format!(
+        "# Transcription Review\n\n\
+        {} passage(s) below confidence {:.2} or flagged, out of {} segments.\n\n",
+        passages.len(),
+        threshold,
+        transcription.segments.len()
+    );
+
+    for passage in passages {
+        report.push_str(&format!(
+            "- **{} → {}** — {}\n",
+            format_vtt_timestamp(passage.start_time()),
+            format_vtt_timestamp(passage.end_time()),
+            describe_scores(passage)
+        ));
+        for segment in &passage.segments {
+            let text = review_text(segment, |text| text.to_string(), |word| format!("**{}**", word));
+            match &segment.speaker {
+                Some(speaker) => report.push_str(&format!("  > {}: {}\n", speaker, text)),
+                None => report.push_str(&format!("  > {}\n", text)),
+            }
+        }
+        report.push('\n');
+    }
+
+    report
+}
+
+fn format_review_html(transcription: &TranscriptionResult, passages: &[ReviewPassage], threshold: f32) -> String {
+    let mut report = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Transcription Review</title>\n\
+        <style>mark {{ background: #ffd54f; }} .scores {{ color: #666; }}</style>\n</head>\n<body>\n\
+        <h1>Transcription Review</h1>\n\
+        <p>{} passage(s) below confidence {:.2} or flagged, out of {} segments.</p>\n<ul>\n",
+        passages.len(),
+        threshold,
+        transcription.segments.len()
+    );
+
+    for passage in passages {
+        report.push_str(&format!(
+            "<li><strong>{} → {}</strong> <span class=\"scores\">{}</span>\n",
+            format_vtt_timestamp(passage.start_time()),
+            format_vtt_timestamp(passage.end_time()),
+            escape_html(&describe_scores(passage))
+        ));
+        for segment in &passage.segments {
+            let text = review_text(segment, escape_html, |word| format!("<mark>{}</mark>", word));
+            match &segment.speaker {
+                Some(speaker) => report.push_str(&format!("<blockquote>{}: {}</blockquote>\n", escape_html(speaker), text)),
+                None => report.push_str(&format!("<blockquote>{}</blockquote>\n", text)),
+            }
+        }
+        report.push_str("</li>\n");
+    }
+
+    report.push_str("</ul>\n</body>\n</html>\n");
+    report
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
 /// Format timestamp for SRT format (HH:MM:SS,mmm)
-fn format_srt_timestamp(seconds: f64) -> String {
+pub fn format_srt_timestamp(seconds: f64) -> String {
     let total_seconds = seconds as u64;
     let hours = total_seconds / 3600;
     let minutes = (total_seconds % 3600) / 60;
@@ -107,7 +519,7 @@ fn format_srt_timestamp(seconds: f64) -> String {
 }
 
 /// Format timestamp for WebVTT format (HH:MM:SS.mmm)
-fn format_vtt_timestamp(seconds: f64) -> String {
+pub fn format_vtt_timestamp(seconds: f64) -> String {
     let total_seconds = seconds as u64;
     let hours = total_seconds / 3600;
     let minutes = (total_seconds % 3600) / 60;
@@ -117,10 +529,136 @@ fn format_vtt_timestamp(seconds: f64) -> String {
     format!("{:02}:{:02}:{:02}.{:03}", hours, minutes, secs, milliseconds)
 }
 
+/// Read back a transcript written in any output format, chosen by file extension.
+/// Plain text carries no timing, so it yields no segments.
+pub async fn load_transcription(path: &Path) -> Result<TranscriptionResult> {
+    if !path.exists() {
+        return Err(TranscribeError::MissingInput(path.to_path_buf()));
+    }
+    let format = match path.extension().and_then(|e| e.to_str()) {
+        Some(extension) => OutputFormat::from_str(extension)?,
+        None => OutputFormat::Text,
+    };
+    let content = fs::read_to_string(path).await?;
+    parse_transcription(&content, &format)
+}
+
+/// Parse a transcript rendered by [`format_transcription`] (or any SRT/WebVTT file)
+pub fn parse_transcription(content: &str, format: &OutputFormat) -> Result<TranscriptionResult> {
+    match format {
+        OutputFormat::Json => Ok(serde_json::from_str(content).context("Invalid JSON transcript")?),
+        OutputFormat::Text => {
+            let body = content
+                .strip_prefix("Wayne Dyer Video Transcription\n==============================\n")
+                .unwrap_or(content);
+            let body = body.split("\n---\nGenerated by Wayne Transcriber").next().unwrap_or(body);
+            Ok(TranscriptionResult {
+                segments: Vec::new(),
+                full_text: body.trim().to_string(),
+                language: None,
+            })
+        }
+        OutputFormat::Srt | OutputFormat::Vtt => parse_cues(content),
+        OutputFormat::Ttml => parse_ttml(content),
+    }
+}
+
+/// Timed paragraphs of a TTML document, with line breaks read as spaces
+fn parse_ttml(content: &str) -> Result<TranscriptionResult> {
+    let mut segments = Vec::new();
+    for paragraph in content.split("<p ").skip(1) {
+        let Some((attributes, rest)) = paragraph.split_once('>') else {
+            return Err(anyhow::anyhow!("Unterminated TTML paragraph: <p {}", paragraph).into());
+        };
+        let attributes = format!(" {}", attributes);
+        let attribute = |name: &str| {
+            let (_, value) = attributes.split_once(&format!(" {}=\"", name))?;
+            value.split_once('"').map(|(value, _)| value)
+        };
+        let times = (attribute("begin").and_then(parse_cue_timestamp), attribute("end").and_then(parse_cue_timestamp));
+        let (Some(start_time), Some(end_time)) = times else {
+            return Err(anyhow::anyhow!("Invalid TTML paragraph timing: <p{}>", attributes).into());
+        };
+
+        let body = rest.split("</p>").next().unwrap_or_default().replace("<br/>", " ");
+        let mut text = String::new();
+        let mut in_tag = false;
+        for c in body.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' => in_tag = false,
+                c if !in_tag => text.push(c),
+                _ => {}
+            }
+        }
+        segments.push(TranscriptionSegment {
+            start_time,
+            end_time,
+            text: unescape_xml(text.split_whitespace().collect::<Vec<_>>().join(" ").as_str()),
+            ..Default::default()
+        });
+    }
+
+    let full_text = segments.iter().map(|segment| segment.text.as_str()).collect::<Vec<_>>().join(" ");
+    Ok(TranscriptionResult { segments, full_text, language: None })
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Cues of an SRT or WebVTT file; blocks without a timing line (headers, notes) are skipped
+fn parse_cues(content: &str) -> Result<TranscriptionResult> {
+    let content = content.replace("\r\n", "\n");
+    let mut segments = Vec::new();
+    for block in content.split("\n\n") {
+        let mut lines = block.lines().skip_while(|line| !line.contains("-->"));
+        let Some(timing) = lines.next() else {
+            continue;
+        };
+        let (start, end) = timing.split_once("-->").unwrap();
+        // WebVTT cue settings may follow the end time
+        let end = end.split_whitespace().next().unwrap_or_default();
+        let (Some(start_time), Some(end_time)) = (parse_cue_timestamp(start), parse_cue_timestamp(end)) else {
+            return Err(anyhow::anyhow!("Invalid subtitle timing line: {}", timing).into());
+        };
+
+        let text = lines.collect::<Vec<_>>().join(" ");
+        let (speaker, text) = match text.strip_prefix("<v ").and_then(|rest| rest.split_once('>')) {
+            Some((speaker, text)) => (Some(speaker.to_string()), text.replace("</v>", "")),
+            None => (None, text),
+        };
+        segments.push(TranscriptionSegment {
+            start_time,
+            end_time,
+            text: text.trim().to_string(),
+            speaker,
+            ..Default::default()
+        });
+    }
+
+    let full_text = segments.iter().map(|segment| segment.text.as_str()).collect::<Vec<_>>().join(" ");
+    Ok(TranscriptionResult { segments, full_text, language: None })
+}
+
+/// Parses `HH:MM:SS,mmm` (SRT), `HH:MM:SS.mmm` or `MM:SS.mmm` (WebVTT)
+fn parse_cue_timestamp(value: &str) -> Option<f64> {
+    let value = value.trim().replace(',', ".");
+    let parts: Vec<&str> = value.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    parts.iter().try_fold(0.0, |total, part| Some(total * 60.0 + part.parse::<f64>().ok()?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::transcription::TranscriptionSegment;
+    use crate::transcription::WordTiming;
 
     fn create_test_transcription() -> TranscriptionResult {
         TranscriptionResult {
@@ -129,14 +667,17 @@ mod tests {
                     start_time: 0.0,
                     end_time: 5.5,
                     text: "Welcome to this Wayne Dyer presentation.".to_string(),
+                    ..Default::default()
                 },
                 TranscriptionSegment {
                     start_time: 6.0,
                     end_time: 12.3,
                     text: "Today we'll explore the power of intention.".to_string(),
+                    ..Default::default()
                 },
             ],
             full_text: "Welcome to this Wayne Dyer presentation. Today we'll explore the power of intention.".to_string(),
+            language: None,
         }
     }
 
@@ -175,4 +716,169 @@ mod tests {
         assert!(vtt.contains("00:00:00.000 --> 00:00:05.500"));
         assert!(vtt.contains("Welcome to this Wayne Dyer presentation."));
     }
+
+    fn create_test_diarized_transcription() -> TranscriptionResult {
+        let mut transcription = create_test_transcription();
+        transcription.segments[0].speaker = Some("Speaker 1".to_string());
+        transcription.segments[1].speaker = Some("Speaker 2".to_string());
+        transcription
+    }
+
+    #[test]
+    fn test_speaker_labels() {
+        let transcription = create_test_diarized_transcription();
+
+        let text = format_as_text(&transcription);
+        assert!(text.contains("Speaker 1: Welcome to this Wayne Dyer presentation.\n\nSpeaker 2: Today"));
+
+        let srt = format_as_srt(&transcription);
+        assert!(srt.contains("00:00:05,500\nSpeaker 1: Welcome to this Wayne Dyer presentation."));
+
+        let vtt = format_as_vtt(&transcription);
+        assert!(vtt.contains("00:00:12.300\n<v Speaker 2>Today we'll explore the power of intention."));
+    }
+
+    #[test]
+    fn test_json_formatting() {
+        let mut transcription = create_test_diarized_transcription();
+        transcription.segments[1].confidence = 0.5;
+        transcription.segments[1].flags = vec![SegmentFlag::Repetition];
+
+        let json: serde_json::Value = serde_json::from_str(&format_as_json(&transcription).unwrap()).unwrap();
+        let segments = json["segments"].as_array().unwrap();
+
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[0]["speaker"], "Speaker 1");
+        assert_eq!(segments[1]["confidence"], 0.5);
+        assert_eq!(segments[1]["flags"][0], "repetition");
+        assert!(segments[0].get("avg_logprob").is_none());
+    }
+
+    fn create_test_review_transcription() -> TranscriptionResult {
+        let mut transcription = create_test_transcription();
+        transcription.segments[0].confidence = 0.9;
+        transcription.segments[1].confidence = 0.3;
+        transcription.segments[1].avg_logprob = Some(-1.2);
+        transcription.segments[1].words = vec![
+            WordTiming { start_time: 6.0, end_time: 6.4, word: "Today".to_string(), avg_logprob: Some(-0.1), ..Default::default() },
+            WordTiming { start_time: 6.4, end_time: 7.0, word: "<intention>".to_string(), avg_logprob: Some(-2.5), ..Default::default() },
+        ];
+        transcription.segments.push(TranscriptionSegment {
+            start_time: 12.3,
+            end_time: 15.0,
+            text: "Thank you. Thank you. Thank you.".to_string(),
+            confidence: 0.5,
+            flags: vec![SegmentFlag::Repetition],
+            ..Default::default()
+        });
+        transcription
+    }
+
+    #[test]
+    fn test_review_passages_merge_consecutive_segments() {
+        let transcription = create_test_review_transcription();
+        let passages = review_passages(&transcription, 0.6);
+
+        assert_eq!(passages.len(), 1);
+        assert_eq!(passages[0].segments.len(), 2);
+        assert_eq!(passages[0].start_time(), 6.0);
+        assert_eq!(passages[0].end_time(), 15.0);
+        assert_eq!(passages[0].confidence(), 0.3);
+        assert_eq!(passages[0].flags(), vec![SegmentFlag::Repetition]);
+    }
+
+    #[test]
+    fn test_review_report_formats() {
+        let transcription = create_test_review_transcription();
+        let passages = review_passages(&transcription, 0.6);
+
+        let markdown = format_review_markdown(&transcription, &passages, 0.6);
+        assert!(markdown.contains("- **00:00:06.000 → 00:00:15.000** — confidence 0.30 · avg log-prob -1.20 · repetition"));
+        assert!(markdown.contains("  > Today **<intention>**\n"));
+
+        let html = format_review_html(&transcription, &passages, 0.6);
+        assert!(html.contains("<blockquote>Today <mark>&lt;intention&gt;</mark></blockquote>"));
+        assert!(html.contains("1 passage(s) below confidence 0.60"));
+    }
+
+    #[test]
+    fn test_parse_round_trip() {
+        let transcription = create_test_transcription();
+
+        let srt = parse_transcription(&format_as_srt(&transcription), &OutputFormat::Srt).unwrap();
+        assert_eq!(srt.segments.len(), 2);
+        assert_eq!((srt.segments[1].start_time, srt.segments[1].end_time), (6.0, 12.3));
+        assert_eq!(srt.full_text, transcription.full_text);
+
+        let vtt = parse_transcription(&format_as_vtt(&create_test_diarized_transcription()), &OutputFormat::Vtt).unwrap();
+        assert_eq!(vtt.segments[0].speaker.as_deref(), Some("Speaker 1"));
+        assert_eq!(vtt.segments[0].text, "Welcome to this Wayne Dyer presentation.");
+
+        let ttml = parse_transcription(&format_as_ttml(&transcription), &OutputFormat::Ttml).unwrap();
+        assert_eq!(ttml.segments.len(), 2);
+        assert_eq!((ttml.segments[1].start_time, ttml.segments[1].end_time), (6.0, 12.3));
+        assert_eq!(ttml.full_text, transcription.full_text);
+
+        let text = parse_transcription(&format_as_text(&transcription), &OutputFormat::Text).unwrap();
+        assert!(text.segments.is_empty());
+        assert_eq!(text.full_text, transcription.full_text);
+
+        assert_eq!(parse_cue_timestamp("01:02.500"), Some(62.5));
+        assert!(parse_transcription("1\n00:00:01,000 --> soon\nHi\n", &OutputFormat::Srt).is_err());
+    }
+
+    #[test]
+    fn test_ttml_formatting() {
+        let mut transcription = create_test_diarized_transcription();
+        transcription.language = Some("en".to_string());
+        transcription.segments[0].text = "Fish & chips\nfor <two>".to_string();
+
+        let ttml = format_as_ttml(&transcription);
+
+        assert!(ttml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<tt xmlns=\"http://www.w3.org/ns/ttml\" xml:lang=\"en\">"));
+        assert!(ttml.contains(
+            "<p begin=\"00:00:00.000\" end=\"00:00:05.500\">Speaker 1: Fish &amp; chips<br/>for &lt;two&gt;</p>"
+        ));
+        assert!(ttml.ends_with("</div>\n  </body>\n</tt>\n"));
+        let parsed = parse_transcription(&ttml, &OutputFormat::Ttml).unwrap();
+        assert_eq!(parsed.segments[0].text, "Speaker 1: Fish & chips for <two>");
+    }
+
+    #[test]
+    fn test_fit_captions() {
+        let words = ["Today", "we'll", "explore", "the", "power", "of", "intention."];
+        let mut transcription = create_test_transcription();
+        transcription.segments[1].words = words
+            .iter()
+            .enumerate()
+            .map(|(index, word)| WordTiming {
+                start_time: 6.0 + index as f64 * 0.9,
+                end_time: 6.0 + (index + 1) as f64 * 0.9,
+                word: word.to_string(),
+                ..Default::default()
+            })
+            .collect();
+
+        let limits = CaptionLimits { max_line_chars: Some(16), max_lines: 2, max_cps: None };
+        let fitted = fit_captions(&transcription, &limits);
+        let cues: Vec<&str> = fitted.segments.iter().map(|segment| segment.text.as_str()).collect();
+        assert_eq!(
+            cues,
+            ["Welcome to this\nWayne Dyer", "presentation.", "Today we'll\nexplore the", "power of\nintention."]
+        );
+        // Word timings split the cues; without them, time is shared by length
+        assert_eq!((fitted.segments[2].start_time, fitted.segments[2].end_time), (6.0, 6.0 + 4.0 * 0.9));
+        assert_eq!(fitted.segments[2].words.len(), 4);
+        assert_eq!(fitted.segments[3].end_time, 12.3);
+        assert!(fitted.segments[1].start_time > 3.0 && fitted.segments[1].end_time == 5.5);
+        assert!(fitted.segments.iter().all(|segment| segment.text.lines().all(|line| line.chars().count() <= 16)));
+
+        // Too fast to read: held on screen, but never into the next cue
+        let limits = CaptionLimits { max_cps: Some(5.0), ..CaptionLimits::default() };
+        let fitted = fit_captions(&transcription, &limits);
+        assert_eq!(fitted.segments[0].end_time, 6.0);
+        assert_eq!(fitted.segments[1].end_time, 6.0 + 43.0 / 5.0);
+        let unchanged = fit_captions(&transcription, &CaptionLimits::default());
+        assert_eq!(format_as_srt(&unchanged), format_as_srt(&transcription));
+    }
 }
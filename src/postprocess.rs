@@ -0,0 +1,730 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use crate::error::{Result, TranscribeError};
+use crate::output::OutputFormat;
+use crate::text::{self, ONES, TENS};
+use crate::transcription::TranscriptionResult;
+
+/// Hesitations dropped by the filler step
+const FILLERS: &[&str] = &["ah", "eh", "er", "erm", "hm", "hmm", "mhm", "mm", "uh", "uhm", "um", "umm"];
+
+/// Masked by the profanity step; words starting with the first few are masked too
+const PROFANITY_STEMS: &[&str] = &["fuck", "shit", "motherfuck"];
+const PROFANITY: &[&str] = &[
+    "arse", "arsehole", "ass", "asshole", "bastard", "bitch", "bitches", "bollocks", "bullshit", "crap", "cunt",
+    "dammit", "damn", "goddamn", "piss", "pissed", "slut", "twat", "wanker", "whore",
+];
+
+const MONTHS: &[&str] = &[
+    "january", "february", "march", "april", "may", "june", "july", "august", "september", "october",
+    "november", "december",
+];
+
+/// Words that start a question, for punctuation restoration
+const QUESTION_WORDS: &[&str] = &[
+    "are", "can", "could", "did", "do", "does", "how", "is", "should", "was", "were", "what", "when", "where",
+    "which", "who", "why", "will", "would",
+];
+
+/// How numbers are written after post-processing
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumberStyle {
+    /// Leave numbers as the decoder wrote them
+    #[default]
+    Keep,
+    /// Spoken numbers, money, percentages and dates as digits: "twenty dollars" → "$20"
+    Digits,
+    /// Digits spelled out: "$20" → "twenty dollars"
+    Words,
+}
+
+/// Steps applied to segment text before it is written out, all off by default
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PostProcessOptions {
+    pub remove_fillers: bool,
+    pub numbers: NumberStyle,
+    pub sentence_case: bool,
+    /// End segments without closing punctuation with "." (or "?" for questions)
+    pub restore_punctuation: bool,
+    pub mask_profanity: bool,
+}
+
+impl FromStr for PostProcessOptions {
+    type Err = TranscribeError;
+
+    /// Comma-separated steps: `fillers`, `digits`, `words`, `case`, `punctuation`, `profanity`, or `none`
+    fn from_str(s: &str) -> Result<Self> {
+        let mut options = Self::default();
+        for step in s.split(',').map(str::trim).filter(|step| !step.is_empty()) {
+            match step.to_lowercase().as_str() {
+                "none" => options = Self::default(),
+                "fillers" => options.remove_fillers = true,
+                "digits" => options.numbers = NumberStyle::Digits,
+                "words" => options.numbers = NumberStyle::Words,
+                "case" => options.sentence_case = true,
+                "punctuation" => options.restore_punctuation = true,
+                "profanity" => options.mask_profanity = true,
+                _ => {
+                    return Err(TranscribeError::UnsupportedFormat(format!(
+                        "post-processing step '{}'. Use: fillers, digits, words, case, punctuation, profanity, none",
+                        step
+                    )));
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Post-processing steps for each output format, e.g. masked profanity in
+/// captions only
+#[derive(Debug, Clone, Default)]
+pub struct PostProcessing {
+    pub default: PostProcessOptions,
+    pub formats: HashMap<OutputFormat, PostProcessOptions>,
+}
+
+impl PostProcessing {
+    pub fn for_format(&self, format: OutputFormat) -> &PostProcessOptions {
+        self.formats.get(&format).unwrap_or(&self.default)
+    }
+
+    /// Adds an override written as `FORMAT=STEPS`, e.g. `srt=fillers,profanity`
+    pub fn add_override(&mut self, spec: &str) -> Result<()> {
+        let (format, steps) = spec.split_once('=').ok_or_else(|| {
+            TranscribeError::UnsupportedFormat(format!("post-processing override '{}'; use FORMAT=STEPS", spec))
+        })?;
+        self.formats.insert(OutputFormat::from_str(format.trim())?, steps.parse()?);
+        Ok(())
+    }
+}
+
+/// A copy of the transcription with the enabled steps applied to every segment.
+/// Word timings keep the decoder's words, minus fillers and masked profanity.
+pub fn apply(transcription: &TranscriptionResult, options: &PostProcessOptions) -> TranscriptionResult {
+    if *options == PostProcessOptions::default() {
+        return transcription.clone();
+    }
+
+    let mut result = transcription.clone();
+    for segment in &mut result.segments {
+        segment.text = process_text(&segment.text, options);
+        if options.remove_fillers {
+            segment.words.retain(|word| !is_filler(&core_word(&word.word)));
+        }
+        if options.mask_profanity {
+            for word in &mut segment.words {
+                word.word = process_text(&word.word, &PostProcessOptions { mask_profanity: true, ..Default::default() });
+            }
+        }
+    }
+    result.full_text = if result.segments.is_empty() {
+        process_text(&transcription.full_text, options)
+    } else {
+        result.segments.iter().map(|segment| segment.text.as_str()).collect::<Vec<_>>().join(" ")
+    };
+    result
+}
+
+/// Runs the enabled steps over one piece of text
+pub fn process_text(text: &str, options: &PostProcessOptions) -> String {
+    let mut tokens = tokenize(text);
+    if options.remove_fillers {
+        tokens = remove_fillers(tokens);
+    }
+    match options.numbers {
+        NumberStyle::Keep => {}
+        NumberStyle::Digits => tokens = numbers_to_digits(tokens),
+        NumberStyle::Words => tokens = tokens.into_iter().map(number_to_words).collect(),
+    }
+    if options.mask_profanity {
+        for token in &mut tokens {
+            if is_profane(&token.core.to_lowercase()) {
+                token.core = mask(&token.core);
+            }
+        }
+    }
+    if options.restore_punctuation {
+        restore_punctuation(&mut tokens);
+    }
+    if options.sentence_case {
+        sentence_case(&mut tokens);
+    }
+    tokens.iter().map(Token::to_string).collect::<Vec<_>>().join(" ")
+}
+
+/// A whitespace-separated word split into surrounding punctuation and its core
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    lead: String,
+    core: String,
+    trail: String,
+}
+
+impl Token {
+    fn new(text: &str) -> Self {
+        let start = text.find(char::is_alphanumeric).unwrap_or(text.len());
+        let end = text.rfind(char::is_alphanumeric).map_or(start, |index| {
+            index + text[index..].chars().next().map_or(0, char::len_utf8)
+        });
+        Self {
+            lead: text[..start].to_string(),
+            core: text[start..end.max(start)].to_string(),
+            trail: text[end.max(start)..].to_string(),
+        }
+    }
+
+    fn ends_sentence(&self) -> bool {
+        self.trail.contains(['.', '?', '!'])
+    }
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}{}", self.lead, self.core, self.trail)
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+    text.split_whitespace().map(Token::new).collect()
+}
+
+fn core_word(text: &str) -> String {
+    Token::new(text).core.to_lowercase()
+}
+
+fn is_filler(word: &str) -> bool {
+    FILLERS.binary_search(&word).is_ok()
+}
+
+/// Drops fillers; sentence-ending punctuation on a filler moves to the word before it
+fn remove_fillers(tokens: Vec<Token>) -> Vec<Token> {
+    let mut kept: Vec<Token> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        if !is_filler(&token.core.to_lowercase()) {
+            kept.push(token);
+            continue;
+        }
+        if token.ends_sentence()
+            && let Some(previous) = kept.last_mut()
+        {
+            let ending: String = token.trail.chars().filter(|c| matches!(c, '.' | '?' | '!')).collect();
+            previous.trail = previous.trail.trim_end_matches([',', ';', ':']).to_string() + &ending;
+        }
+    }
+    kept
+}
+
+fn is_profane(word: &str) -> bool {
+    PROFANITY.binary_search(&word).is_ok() || PROFANITY_STEMS.iter().any(|stem| word.starts_with(stem))
+}
+
+/// Keeps the first letter: "damn" → "d***"
+fn mask(word: &str) -> String {
+    let mut chars = word.chars();
+    let first = chars.next().map(String::from).unwrap_or_default();
+    first + &"*".repeat(chars.count())
+}
+
+fn restore_punctuation(tokens: &mut [Token]) {
+    let Some(first) = tokens.first() else {
+        return;
+    };
+    let question = QUESTION_WORDS.contains(&first.core.to_lowercase().as_str());
+    let last = tokens.last_mut().unwrap();
+    if !last.trail.contains(['.', '?', '!', ',', ';', ':', '…']) {
+        last.trail.push(if question { '?' } else { '.' });
+    }
+}
+
+/// Capitalizes the first word of every sentence and the pronoun "I"
+fn sentence_case(tokens: &mut [Token]) {
+    let mut sentence_start = true;
+    for token in tokens {
+        let lower = token.core.to_lowercase();
+        if sentence_start || lower == "i" || lower.starts_with("i'") {
+            let mut chars = token.core.chars();
+            if let Some(first) = chars.next() {
+                token.core = first.to_uppercase().chain(chars).collect();
+            }
+        }
+        if !token.core.is_empty() {
+            sentence_start = token.ends_sentence();
+        }
+    }
+}
+
+/// A number word and how it combines with the words before it
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NumberWord {
+    /// zero to nineteen
+    Unit(u64),
+    /// twenty, thirty, ...
+    Tens(u64),
+    Hundred,
+    /// thousand, million, ...
+    Scale(u64),
+    /// first, twenty-first, hundredth: ends the number
+    Ordinal(u64),
+}
+
+fn number_word(word: &str) -> Option<NumberWord> {
+    if let Some(unit) = ONES.iter().position(|&one| one == word) {
+        return Some(NumberWord::Unit(unit as u64));
+    }
+    if let Some(tens) = TENS.iter().skip(2).position(|&ten| ten == word) {
+        return Some(NumberWord::Tens((tens as u64 + 2) * 10));
+    }
+    if word == "hundred" {
+        return Some(NumberWord::Hundred);
+    }
+    if let Some(&(scale, _)) = text::SCALES.iter().find(|(_, name)| *name == word) {
+        return Some(NumberWord::Scale(scale));
+    }
+    ordinal_value(word).map(NumberWord::Ordinal)
+}
+
+fn ordinal_value(word: &str) -> Option<u64> {
+    let irregular = [
+        ("first", 1), ("second", 2), ("third", 3), ("fifth", 5), ("eighth", 8), ("ninth", 9), ("twelfth", 12),
+    ];
+    if let Some(&(_, value)) = irregular.iter().find(|(name, _)| *name == word) {
+        return Some(value);
+    }
+    let cardinal = match word.strip_suffix("ieth") {
+        Some(stem) => format!("{}y", stem),
+        None => word.strip_suffix("th")?.to_string(),
+    };
+    match number_word(&cardinal)? {
+        NumberWord::Unit(value) | NumberWord::Tens(value) => Some(value),
+        NumberWord::Hundred => Some(100),
+        NumberWord::Scale(value) => Some(value),
+        NumberWord::Ordinal(_) => None,
+    }
+}
+
+/// A number read from words
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct SpokenNumber {
+    value: u64,
+    ordinal: bool,
+    /// Read as a year: "nineteen eighty four"
+    year: bool,
+    /// Number of words it was spelled with
+    words: usize,
+}
+
+/// Reads the longest number spelled out at the start of `tokens`, returning it
+/// and the number of tokens used. Hyphenated tokens ("twenty-one") count once.
+fn parse_number(tokens: &[Token]) -> Option<(SpokenNumber, usize)> {
+    if let Some(year) = parse_year(tokens) {
+        return Some(year);
+    }
+
+    let (mut total, mut current) = (0u64, 0u64);
+    let mut last: Option<NumberWord> = None;
+    let mut ordinal = false;
+    let mut words = 0;
+    let mut used = 0;
+
+    'tokens: for (index, token) in tokens.iter().enumerate() {
+        if index > 0 && (!tokens[index - 1].trail.is_empty() || !token.lead.is_empty()) {
+            break;
+        }
+        let lower = token.core.to_lowercase();
+        let parts: Vec<&str> = lower.split('-').collect();
+
+        // "and" only joins "hundred and five" or "thousand and one"
+        if parts == ["and"] {
+            let joins = matches!(last, Some(NumberWord::Hundred | NumberWord::Scale(_)))
+                && token.trail.is_empty()
+                && tokens.get(index + 1).is_some_and(|next| {
+                    matches!(number_word(&next.core.to_lowercase()), Some(NumberWord::Unit(1..) | NumberWord::Tens(_)))
+                });
+            if joins {
+                continue;
+            }
+            break;
+        }
+
+        let (mut next_total, mut next_current, mut next_last) = (total, current, last);
+        for part in &parts {
+            let Some(word) = number_word(part) else {
+                break 'tokens;
+            };
+            let follows_small = matches!(next_last, Some(NumberWord::Unit(_) | NumberWord::Tens(_)));
+            match word {
+                NumberWord::Unit(0) if next_last.is_some() => break 'tokens,
+                NumberWord::Unit(value) | NumberWord::Ordinal(value) if value < 10 => {
+                    if matches!(next_last, Some(NumberWord::Unit(_))) {
+                        break 'tokens;
+                    }
+                    next_current += value;
+                }
+                NumberWord::Unit(value) | NumberWord::Tens(value) | NumberWord::Ordinal(value) if value < 100 => {
+                    if follows_small {
+                        break 'tokens;
+                    }
+                    next_current += value;
+                }
+                NumberWord::Hundred | NumberWord::Ordinal(100) => {
+                    if next_current >= 100 {
+                        break 'tokens;
+                    }
+                    next_current = next_current.max(1) * 100;
+                }
+                NumberWord::Scale(scale) | NumberWord::Ordinal(scale) => {
+                    next_total += next_current.max(1) * scale;
+                    next_current = 0;
+                }
+                _ => break 'tokens,
+            }
+            next_last = Some(match word {
+                NumberWord::Ordinal(value) if value < 20 => NumberWord::Unit(value),
+                NumberWord::Ordinal(value) if value < 100 => NumberWord::Tens(value),
+                NumberWord::Ordinal(100) => NumberWord::Hundred,
+                NumberWord::Ordinal(scale) => NumberWord::Scale(scale),
+                other => other,
+            });
+            if matches!(word, NumberWord::Ordinal(_)) {
+                ordinal = true;
+            }
+        }
+
+        total = next_total;
+        current = next_current;
+        last = next_last;
+        words += parts.len();
+        used = index + 1;
+        if ordinal {
+            break;
+        }
+    }
+
+    (used > 0).then_some((
+        SpokenNumber {
+            value: total + current,
+            ordinal,
+            year: false,
+            words,
+        },
+        used,
+    ))
+}
+
+/// Years spoken in two halves: "nineteen eighty four", "twenty twenty"
+fn parse_year(tokens: &[Token]) -> Option<(SpokenNumber, usize)> {
+    let century = match number_word(&tokens.first()?.core.to_lowercase())? {
+        NumberWord::Unit(value @ 11..=19) | NumberWord::Tens(value @ 20) => value,
+        _ => return None,
+    };
+    if !tokens[0].trail.is_empty() {
+        return None;
+    }
+
+    let words: Vec<(String, &Token)> = tokens[1..]
+        .iter()
+        .take(2)
+        .flat_map(|token| token.core.to_lowercase().split('-').map(|part| (part.to_string(), token)).collect::<Vec<_>>())
+        .collect();
+    let first = words.first()?;
+    let (value, used) = match (first.0.as_str(), number_word(&first.0)?) {
+        ("oh", _) | (_, NumberWord::Unit(0)) => return None,
+        (_, NumberWord::Unit(value @ 10..=19)) => (value, 1),
+        (_, NumberWord::Tens(tens)) => match words.get(1).map(|(word, _)| number_word(word)) {
+            Some(Some(NumberWord::Unit(unit @ 1..=9))) if first.1.trail.is_empty() => (tens + unit, 2),
+            _ => (tens, 1),
+        },
+        _ => return None,
+    };
+    // Count tokens, not hyphen parts
+    let tokens_used = if used == 2 && std::ptr::eq(words[0].1, words[1].1) { 2 } else { used + 1 };
+    Some((
+        SpokenNumber {
+            value: century * 100 + value,
+            ordinal: false,
+            year: true,
+            words: used + 1,
+        },
+        tokens_used,
+    ))
+}
+
+fn ordinal_suffix(value: u64) -> &'static str {
+    match (value % 10, value % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+/// Inverse text normalization: spoken numbers to digits, with money, percentages and dates
+fn numbers_to_digits(tokens: Vec<Token>) -> Vec<Token> {
+    let mut result: Vec<Token> = Vec::with_capacity(tokens.len());
+    let mut index = 0;
+    while index < tokens.len() {
+        let Some((number, used)) = parse_number(&tokens[index..]) else {
+            result.push(tokens[index].clone());
+            index += 1;
+            continue;
+        };
+
+        let lead = tokens[index].lead.clone();
+        let mut trail = tokens[index + used - 1].trail.clone();
+        let unit = tokens
+            .get(index + used)
+            .filter(|_| trail.is_empty())
+            .map(|token| token.core.to_lowercase());
+        // Month names are capitalized, which keeps "you may one day" out
+        let after_month = result.last().is_some_and(|previous| {
+            previous.trail.is_empty()
+                && previous.core.starts_with(char::is_uppercase)
+                && MONTHS.contains(&previous.core.to_lowercase().as_str())
+        });
+
+        let mut consumed = used;
+        let digits = match unit.as_deref() {
+            Some("percent") if !number.ordinal => {
+                trail = tokens[index + used].trail.clone();
+                consumed += 1;
+                format!("{}%", number.value)
+            }
+            Some(currency @ ("dollars" | "dollar" | "euros" | "euro" | "pounds" | "pound")) if !number.ordinal => {
+                let symbol = match currency {
+                    "dollars" | "dollar" => "$",
+                    "euros" | "euro" => "€",
+                    _ => "£",
+                };
+                trail = tokens[index + used].trail.clone();
+                consumed += 1;
+                // "and fifty cents"
+                let cents = (trail.is_empty() && tokens.get(index + consumed).is_some_and(|t| t.core == "and" && t.trail.is_empty()))
+                    .then(|| parse_number(&tokens[index + consumed + 1..]))
+                    .flatten()
+                    .filter(|(cents, used)| {
+                        cents.value < 100
+                            && tokens.get(index + consumed + 1 + used).is_some_and(|t| {
+                                matches!(t.core.to_lowercase().as_str(), "cents" | "cent" | "pence" | "p")
+                            })
+                    });
+                match cents {
+                    Some((cents, cents_used)) => {
+                        trail = tokens[index + consumed + 1 + cents_used].trail.clone();
+                        consumed += cents_used + 2;
+                        format!("{}{}.{:02}", symbol, number.value, cents.value)
+                    }
+                    None => format!("{}{}", symbol, number.value),
+                }
+            }
+            _ if after_month && (1..=31).contains(&number.value) && !number.year => {
+                // "March third, twenty twenty four" → "March 3, 2024"
+                let year = tokens
+                    .get(index + used)
+                    .filter(|_| trail.is_empty() || trail == ",")
+                    .and(parse_number(&tokens[index + used..]))
+                    .filter(|(year, _)| year.year || (1000..=2999).contains(&year.value));
+                match year {
+                    Some((year, year_used)) => {
+                        trail = tokens[index + used + year_used - 1].trail.clone();
+                        consumed += year_used;
+                        format!("{}, {}", number.value, year.value)
+                    }
+                    None => number.value.to_string(),
+                }
+            }
+            // Single small words read better spelled out: "one of the", "the first",
+            // and so do bare scales: "a thousand thanks"
+            _ if number.words == 1 && (number.value < 10 || number.value >= 100) && !number.year => {
+                result.extend(tokens[index..index + used].iter().cloned());
+                index += used;
+                continue;
+            }
+            _ if number.ordinal => format!("{}{}", number.value, ordinal_suffix(number.value)),
+            _ => number.value.to_string(),
+        };
+
+        result.push(Token { lead, core: digits, trail });
+        index += consumed;
+    }
+    result
+}
+
+/// Spells out a token written with digits: "$5.50" → "five dollars and fifty cents"
+fn number_to_words(mut token: Token) -> Token {
+    let core = token.core.replace(',', "");
+    let (digits, suffix) = core.split_at(core.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(core.len()));
+    if digits.is_empty() || !digits.starts_with(|c: char| c.is_ascii_digit()) {
+        return token;
+    }
+
+    if matches!(suffix, "st" | "nd" | "rd" | "th") {
+        if let Ok(value) = digits.parse::<u64>() {
+            token.core = ordinal_words(&text::number_words(value));
+        }
+        return token;
+    }
+    if !suffix.is_empty() {
+        return token;
+    }
+
+    let currency = match token.lead.chars().last() {
+        Some('$') => Some(("dollars", "cents")),
+        Some('€') => Some(("euros", "cents")),
+        Some('£') => Some(("pounds", "pence")),
+        _ => None,
+    };
+    token.core = match currency {
+        Some((major, minor)) => {
+            token.lead.pop();
+            match digits.split_once('.') {
+                Some((whole, cents)) if cents.len() == 2 && cents != "00" => format!(
+                    "{} {} and {} {}",
+                    text::spell_numbers(whole),
+                    major,
+                    text::spell_numbers(cents.trim_start_matches('0')),
+                    minor
+                ),
+                _ => format!("{} {}", text::spell_numbers(digits.trim_end_matches(".00")), major),
+            }
+        }
+        None => text::spell_numbers(digits),
+    };
+    if let Some(rest) = token.trail.strip_prefix('%') {
+        token.core.push_str(" percent");
+        token.trail = rest.to_string();
+    }
+    token
+}
+
+/// "twenty one" → "twenty first"
+fn ordinal_words(cardinal: &str) -> String {
+    let (head, last) = cardinal.rsplit_once(' ').map_or(("", cardinal), |(head, last)| (head, last));
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        tens if tens.ends_with('y') => format!("{}ieth", &tens[..tens.len() - 1]),
+        other => format!("{}th", other),
+    };
+    if head.is_empty() { last } else { format!("{} {}", head, last) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::{TranscriptionSegment, WordTiming};
+
+    fn digits() -> PostProcessOptions {
+        PostProcessOptions { numbers: NumberStyle::Digits, ..Default::default() }
+    }
+
+    #[test]
+    fn test_word_lists_are_sorted() {
+        assert!(FILLERS.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(PROFANITY.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_spoken_numbers_to_digits() {
+        let options = digits();
+        assert_eq!(process_text("I have twenty-one reasons and one hundred and five ideas.", &options), "I have 21 reasons and 105 ideas.");
+        assert_eq!(process_text("one of the three ways", &options), "one of the three ways");
+        assert_eq!(process_text("two thousand twenty four people", &options), "2024 people");
+        assert_eq!(process_text("back in nineteen eighty four", &options), "back in 1984");
+        assert_eq!(process_text("the twenty first century", &options), "the 21st century");
+        assert_eq!(process_text("ten, twenty, thirty", &options), "10, 20, 30");
+        assert_eq!(process_text("five six", &options), "five six");
+    }
+
+    #[test]
+    fn test_money_percent_and_dates() {
+        let options = digits();
+        assert_eq!(process_text("It costs twenty dollars and fifty cents.", &options), "It costs $20.50.");
+        assert_eq!(process_text("five euros", &options), "€5");
+        assert_eq!(process_text("ninety nine percent of us", &options), "99% of us");
+        assert_eq!(process_text("On March third, twenty twenty four we met", &options), "On March 3, 2024 we met");
+        assert_eq!(process_text("since May first", &options), "since May 1");
+        assert_eq!(process_text("you may one day", &options), "you may one day");
+    }
+
+    #[test]
+    fn test_digits_to_words() {
+        let options = PostProcessOptions { numbers: NumberStyle::Words, ..Default::default() };
+        assert_eq!(process_text("It costs $5.50, or 20% off.", &options), "It costs five dollars and fifty cents, or twenty percent off.");
+        assert_eq!(process_text("the 21st century had 1,500 mp3s", &options), "the twenty first century had one thousand five hundred mp3s");
+    }
+
+    #[test]
+    fn test_fillers_case_and_punctuation() {
+        let options: PostProcessOptions = "fillers,case,punctuation".parse().unwrap();
+
+        assert_eq!(process_text("um, so i think, uh, we change", &options), "So I think, we change.");
+        assert_eq!(process_text("what do you want uh?", &options), "What do you want?");
+        assert_eq!(process_text("is this real", &options), "Is this real?");
+        assert_eq!(process_text("yes. and then i'm done!", &options), "Yes. And then I'm done!");
+    }
+
+    #[test]
+    fn test_profanity_mask() {
+        let options: PostProcessOptions = "profanity".parse().unwrap();
+
+        assert_eq!(process_text("Damn, that's fucking great.", &options), "D***, that's f****** great.");
+        assert_eq!(process_text("a class assessment", &options), "a class assessment");
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        let options: PostProcessOptions = "fillers,digits,case,punctuation,profanity".parse().unwrap();
+        for text in ["", "and", "um", "dollars", "$", "twenty-", "thousand and", "- —", "nineteen", "March", "a hundred and"] {
+            process_text(text, &options);
+            process_text(text, &PostProcessOptions { numbers: NumberStyle::Words, ..Default::default() });
+        }
+        assert_eq!(process_text("a thousand thanks", &digits()), "a thousand thanks");
+        assert_eq!(process_text("$5.00 now", &PostProcessOptions { numbers: NumberStyle::Words, ..Default::default() }), "five dollars now");
+    }
+
+    #[test]
+    fn test_parse_steps_and_overrides() {
+        assert!("fillers,sparkles".parse::<PostProcessOptions>().is_err());
+        assert_eq!("case,none".parse::<PostProcessOptions>().unwrap(), PostProcessOptions::default());
+
+        let mut processing = PostProcessing { default: "case".parse().unwrap(), ..Default::default() };
+        processing.add_override("srt=profanity,fillers").unwrap();
+        assert!(processing.for_format(OutputFormat::Srt).mask_profanity);
+        assert!(!processing.for_format(OutputFormat::Text).mask_profanity);
+        assert!(processing.add_override("srt").is_err());
+        assert!(processing.add_override("docx=case").is_err());
+    }
+
+    #[test]
+    fn test_apply_to_transcription() {
+        let transcription = TranscriptionResult {
+            segments: vec![TranscriptionSegment {
+                start_time: 0.0,
+                end_time: 2.0,
+                text: " um, twenty dollars".to_string(),
+                words: vec![
+                    WordTiming { word: "um,".into(), ..Default::default() },
+                    WordTiming { word: "twenty".into(), ..Default::default() },
+                    WordTiming { word: "dollars".into(), ..Default::default() },
+                ],
+                ..Default::default()
+            }],
+            full_text: "um, twenty dollars".to_string(),
+            language: None,
+        };
+        let options: PostProcessOptions = "fillers,digits,case,punctuation".parse().unwrap();
+
+        let processed = apply(&transcription, &options);
+
+        assert_eq!(processed.segments[0].text, "$20.");
+        assert_eq!(processed.full_text, "$20.");
+        assert_eq!(processed.segments[0].words.len(), 2);
+    }
+}
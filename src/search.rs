@@ -0,0 +1,649 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::{debug, info};
+use crate::audio;
+use crate::error::Result;
+use crate::text;
+use crate::transcription::TranscriptionResult;
+
+/// Default index file, created in the searched directory
+pub const DEFAULT_INDEX_FILE: &str = ".wayne-index.json";
+
+/// Bumped whenever the stored layout changes; older indexes are rebuilt
+const INDEX_VERSION: u64 = 2;
+
+/// BM25 parameters
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Characters of context shown around the first match
+const SNIPPET_CHARS: usize = 160;
+
+/// Inverted index over the segments of a transcript archive, stored as JSON.
+///
+/// Every segment is a searchable unit; postings record the word positions
+/// within the segment so phrase queries can be answered from the index.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u64,
+    documents: BTreeMap<u32, IndexedDocument>,
+    postings: BTreeMap<String, Vec<Posting>>,
+    next_id: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedDocument {
+    transcript: PathBuf,
+    /// Name shown in hits: the media file next to the transcript, if any
+    media: String,
+    /// Modification time and size, to skip unchanged transcripts on update
+    modified: u64,
+    size: u64,
+    segments: Vec<IndexedSegment>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct IndexedSegment {
+    start_time: f64,
+    end_time: f64,
+    text: String,
+    words: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Posting {
+    document: u32,
+    segment: u32,
+    positions: Vec<u32>,
+}
+
+/// What an incremental update changed
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UpdateStats {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// A matching segment
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub media: String,
+    pub transcript: PathBuf,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub score: f64,
+    pub snippet: String,
+}
+
+impl SearchHit {
+    /// Path of the media file the hit was spoken in, if one sits next to the transcript
+    pub fn media_path(&self) -> Option<PathBuf> {
+        let path = self.transcript.with_file_name(&self.media);
+        (path != self.transcript).then_some(path)
+    }
+}
+
+impl fmt::Display for SearchHit {
+    /// `talk.mp4 @ 00:14:32 — …snippet…`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.start_time as u64;
+        write!(
+            f,
+            "{} @ {:02}:{:02}:{:02} — {}",
+            self.media,
+            total / 3600,
+            total % 3600 / 60,
+            total % 60,
+            self.snippet
+        )
+    }
+}
+
+/// Segments matched by a clause, keyed by (document, segment), with their
+/// score and the indexed words that matched
+type Matches = HashMap<(u32, u32), (f64, Vec<String>)>;
+
+/// One part of a query; a segment must match every clause
+#[derive(Debug, Clone, PartialEq)]
+enum Clause {
+    Term { word: String, max_edits: usize },
+    Phrase(Vec<String>),
+}
+
+impl Default for SearchIndex {
+    fn default() -> Self {
+        Self {
+            version: INDEX_VERSION,
+            documents: BTreeMap::new(),
+            postings: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl SearchIndex {
+    /// Load an index, or start an empty one if the file does not exist yet
+    /// or was written by an older version
+    pub async fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = tokio::fs::read_to_string(path).await?;
+        let value: serde_json::Value = serde_json::from_str(&content).map_err(anyhow::Error::from)?;
+        if value.get("version").and_then(serde_json::Value::as_u64) != Some(INDEX_VERSION) {
+            info!("🔎 Rebuilding search index {:?} written by an older version", path);
+            return Ok(Self::default());
+        }
+        Ok(serde_json::from_value(value).map_err(anyhow::Error::from)?)
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let content = serde_json::to_string(self).map_err(anyhow::Error::from)?;
+        tokio::fs::write(path, content).await?;
+        Ok(())
+    }
+
+    /// Number of indexed transcripts
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+
+    /// Index every JSON transcript under `dir`, re-indexing changed ones and
+    /// dropping ones that were deleted. Other JSON files are ignored.
+    pub async fn update_from_dir(&mut self, dir: &Path) -> Result<UpdateStats> {
+        let mut stats = UpdateStats::default();
+        let mut found = HashSet::new();
+        let mut pending = vec![dir.to_path_buf()];
+
+        while let Some(current) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&current).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                let file_type = entry.file_type().await?;
+                if file_type.is_dir() && !hidden {
+                    pending.push(path);
+                    continue;
+                }
+                if hidden || path.extension().is_none_or(|e| !e.eq_ignore_ascii_case("json")) {
+                    continue;
+                }
+
+                let metadata = entry.metadata().await?;
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                    .map_or(0, |elapsed| elapsed.as_millis() as u64);
+                let existing = self.find(&path);
+                if let Some(id) = existing
+                    && self.documents[&id].modified == modified
+                    && self.documents[&id].size == metadata.len()
+                {
+                    found.insert(path);
+                    stats.unchanged += 1;
+                    continue;
+                }
+
+                let content = tokio::fs::read_to_string(&path).await?;
+                let Ok(transcription) = serde_json::from_str::<TranscriptionResult>(&content) else {
+                    debug!("Skipping {:?}: not a transcript", path);
+                    continue;
+                };
+                if let Some(id) = existing {
+                    self.remove_document(id);
+                    stats.updated += 1;
+                } else {
+                    stats.added += 1;
+                }
+                self.add(&path, &transcription, modified, metadata.len());
+                found.insert(path);
+            }
+        }
+
+        let deleted: Vec<u32> = self
+            .documents
+            .iter()
+            .filter(|(_, document)| document.transcript.starts_with(dir) && !found.contains(&document.transcript))
+            .map(|(&id, _)| id)
+            .collect();
+        for id in deleted {
+            self.remove_document(id);
+            stats.removed += 1;
+        }
+
+        info!(
+            "🔎 Index updated: {} added, {} updated, {} removed, {} unchanged",
+            stats.added, stats.updated, stats.removed, stats.unchanged
+        );
+        Ok(stats)
+    }
+
+    fn find(&self, transcript: &Path) -> Option<u32> {
+        self.documents
+            .iter()
+            .find(|(_, document)| document.transcript == transcript)
+            .map(|(&id, _)| id)
+    }
+
+    fn add(&mut self, transcript: &Path, transcription: &TranscriptionResult, modified: u64, size: u64) {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let mut segments = Vec::new();
+        for (index, segment) in transcription.segments.iter().enumerate() {
+            let words = text::words(&segment.text);
+            let mut positions: HashMap<&str, Vec<u32>> = HashMap::new();
+            for (position, word) in words.iter().enumerate() {
+                positions.entry(word).or_default().push(position as u32);
+            }
+            for (word, positions) in positions {
+                self.postings.entry(word.to_string()).or_default().push(Posting {
+                    document: id,
+                    segment: index as u32,
+                    positions,
+                });
+            }
+            segments.push(IndexedSegment {
+                start_time: segment.start_time,
+                end_time: segment.end_time,
+                text: segment.text.trim().to_string(),
+                words: words.len() as u32,
+            });
+        }
+
+        self.documents.insert(
+            id,
+            IndexedDocument {
+                transcript: transcript.to_path_buf(),
+                media: media_name(transcript),
+                modified,
+                size,
+                segments,
+            },
+        );
+    }
+
+    fn remove_document(&mut self, id: u32) {
+        self.documents.remove(&id);
+        self.postings.retain(|_, postings| {
+            postings.retain(|posting| posting.document != id);
+            !postings.is_empty()
+        });
+    }
+
+    /// Ranked segments matching every term and quoted phrase of `query`.
+    /// `term~` (or `term~2`) matches words within that many edits;
+    /// `fuzzy` applies this to every term.
+    pub fn search(&self, query: &str, fuzzy: bool, limit: usize) -> Vec<SearchHit> {
+        let clauses = parse_query(query, fuzzy);
+        if clauses.is_empty() {
+            return Vec::new();
+        }
+
+        let segment_count: usize = self.documents.values().map(|document| document.segments.len()).sum();
+        let total_words: u64 = self
+            .documents
+            .values()
+            .flat_map(|document| &document.segments)
+            .map(|segment| segment.words as u64)
+            .sum();
+        let average_length = total_words as f64 / segment_count.max(1) as f64;
+
+        let mut matches: Option<Matches> = None;
+        for clause in &clauses {
+            let clause_matches = self.match_clause(clause, segment_count, average_length);
+            matches = Some(match matches {
+                None => clause_matches,
+                Some(previous) => previous
+                    .into_iter()
+                    .filter_map(|(key, (score, mut words))| {
+                        let (clause_score, clause_words) = clause_matches.get(&key)?;
+                        words.extend(clause_words.iter().cloned());
+                        Some((key, (score + clause_score, words)))
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut hits: Vec<SearchHit> = matches
+            .unwrap_or_default()
+            .into_iter()
+            .map(|((document_id, segment_index), (score, words))| {
+                let document = &self.documents[&document_id];
+                let segment = &document.segments[segment_index as usize];
+                SearchHit {
+                    media: document.media.clone(),
+                    transcript: document.transcript.clone(),
+                    start_time: segment.start_time,
+                    end_time: segment.end_time,
+                    score,
+                    snippet: snippet(&segment.text, &words),
+                }
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.media.cmp(&b.media))
+                .then_with(|| a.start_time.total_cmp(&b.start_time))
+        });
+        hits.truncate(limit);
+        hits
+    }
+
+    fn match_clause(
+        &self,
+        clause: &Clause,
+        segment_count: usize,
+        average_length: f64,
+    ) -> Matches {
+        let idf = |postings: &[Posting]| {
+            let frequency = postings.len() as f64;
+            (1.0 + (segment_count as f64 - frequency + 0.5) / (frequency + 0.5)).ln()
+        };
+        let bm25 = |document: u32, segment: u32, frequency: f64| {
+            let length = self.documents[&document].segments[segment as usize].words as f64;
+            frequency * (K1 + 1.0) / (frequency + K1 * (1.0 - B + B * length / average_length.max(1.0)))
+        };
+
+        let mut matches = Matches::new();
+        match clause {
+            Clause::Term { word, max_edits } => {
+                for (term, edits) in self.expand(word, *max_edits) {
+                    let postings = &self.postings[term];
+                    let weight = idf(postings) / (1.0 + edits as f64);
+                    for posting in postings {
+                        let score = weight * bm25(posting.document, posting.segment, posting.positions.len() as f64);
+                        let entry = matches.entry((posting.document, posting.segment)).or_default();
+                        entry.0 = entry.0.max(score);
+                        entry.1.push(term.clone());
+                    }
+                }
+            }
+            Clause::Phrase(words) => {
+                let Some(postings) = words
+                    .iter()
+                    .map(|word| self.postings.get(word))
+                    .collect::<Option<Vec<_>>>()
+                else {
+                    return matches;
+                };
+                let weight: f64 = postings.iter().map(|postings| idf(postings)).sum();
+
+                let mut by_segment: HashMap<(u32, u32), Vec<&Vec<u32>>> = HashMap::new();
+                for word_postings in &postings {
+                    for posting in word_postings.iter() {
+                        by_segment
+                            .entry((posting.document, posting.segment))
+                            .or_default()
+                            .push(&posting.positions);
+                    }
+                }
+                for ((document, segment), positions) in by_segment {
+                    if positions.len() != words.len() {
+                        continue;
+                    }
+                    let occurrences = positions[0]
+                        .iter()
+                        .filter(|&&start| {
+                            positions[1..]
+                                .iter()
+                                .enumerate()
+                                .all(|(offset, later)| later.contains(&(start + offset as u32 + 1)))
+                        })
+                        .count();
+                    if occurrences > 0 {
+                        let score = weight * bm25(document, segment, occurrences as f64);
+                        matches.insert((document, segment), (score, words.clone()));
+                    }
+                }
+            }
+        }
+        matches
+    }
+
+    /// Indexed words within `max_edits` of `word`, with their distance
+    fn expand<'a>(&'a self, word: &str, max_edits: usize) -> Vec<(&'a String, usize)> {
+        if max_edits == 0 {
+            return self.postings.get_key_value(word).map(|(term, _)| (term, 0)).into_iter().collect();
+        }
+        let word: Vec<char> = word.chars().collect();
+        self.postings
+            .keys()
+            .filter(|term| term.chars().count().abs_diff(word.len()) <= max_edits)
+            .filter_map(|term| {
+                let distance = text::edit_distance(&word, &term.chars().collect::<Vec<_>>());
+                (distance <= max_edits).then_some((term, distance))
+            })
+            .collect()
+    }
+}
+
+/// Splits a query into quoted phrases and (optionally fuzzy) terms
+fn parse_query(query: &str, fuzzy: bool) -> Vec<Clause> {
+    let mut clauses = Vec::new();
+    for (index, part) in query.split('"').enumerate() {
+        // Odd parts were inside quotes
+        if index % 2 == 1 {
+            let words = text::words(part);
+            match words.len() {
+                0 => {}
+                1 => clauses.push(Clause::Term { word: words[0].clone(), max_edits: 0 }),
+                _ => clauses.push(Clause::Phrase(words)),
+            }
+            continue;
+        }
+
+        for token in part.split_whitespace() {
+            let (token, explicit_edits) = match token.rsplit_once('~') {
+                Some((token, edits)) => (token, Some(edits.parse().unwrap_or(1))),
+                None => (token, None),
+            };
+            let words = text::words(token);
+            if words.len() > 1 {
+                clauses.push(Clause::Phrase(words));
+                continue;
+            }
+            for word in words {
+                let max_edits = explicit_edits.unwrap_or_else(|| match word.chars().count() {
+                    _ if !fuzzy => 0,
+                    0..=3 => 0,
+                    4..=7 => 1,
+                    _ => 2,
+                });
+                clauses.push(Clause::Term { word, max_edits });
+            }
+        }
+    }
+    clauses
+}
+
+/// Up to `SNIPPET_CHARS` of `text` around the first matched word
+fn snippet(text: &str, matched: &[String]) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= SNIPPET_CHARS {
+        return text.to_string();
+    }
+
+    let lower = text.to_lowercase();
+    let first_match = matched
+        .iter()
+        .filter_map(|word| lower.find(word.as_str()))
+        .min()
+        .map_or(0, |byte| lower[..byte].chars().count());
+    let start = first_match.saturating_sub(SNIPPET_CHARS / 3);
+    let end = (start + SNIPPET_CHARS).min(chars.len());
+    let start = end.saturating_sub(SNIPPET_CHARS);
+
+    let mut snippet: String = chars[start..end].iter().collect();
+    if start > 0 {
+        snippet = format!("…{}", snippet.trim_start());
+    }
+    if end < chars.len() {
+        snippet = format!("{}…", snippet.trim_end());
+    }
+    snippet
+}
+
+/// The media file a transcript was made from: a sibling with the same stem
+/// and a media extension, or the transcript's own name
+fn media_name(transcript: &Path) -> String {
+    audio::SUPPORTED_FORMATS
+        .iter()
+        .map(|extension| transcript.with_extension(extension))
+        .find(|candidate| candidate.exists())
+        .unwrap_or_else(|| transcript.to_path_buf())
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::TranscriptionSegment;
+
+    fn transcript(texts: &[(f64, &str)]) -> TranscriptionResult {
+        let segments: Vec<TranscriptionSegment> = texts
+            .iter()
+            .map(|&(start_time, text)| TranscriptionSegment {
+                start_time,
+                end_time: start_time + 5.0,
+                text: text.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let full_text = texts.iter().map(|(_, text)| *text).collect::<Vec<_>>().join(" ");
+        TranscriptionResult { segments, full_text, language: None }
+    }
+
+    fn archive() -> SearchIndex {
+        let mut index = SearchIndex::default();
+        index.add(
+            Path::new("/talks/intention.json"),
+            &transcript(&[
+                (0.0, "Welcome to a talk on the power of intention."),
+                (872.0, "Intention is a field of energy you can connect to."),
+                (900.0, "Change the way you look at things and the things you look at change."),
+            ]),
+            0,
+            0,
+        );
+        index.add(
+            Path::new("/talks/service.json"),
+            &transcript(&[(60.0, "Serve others and the power flows back to you."), (120.0, "Intention matters.")]),
+            0,
+            0,
+        );
+        index
+    }
+
+    #[test]
+    fn test_term_search_and_display() {
+        let hits = archive().search("intention", false, 10);
+
+        assert_eq!(hits.len(), 3);
+        // The short segment where the word carries the most weight ranks first
+        assert_eq!(hits[0].media, "service.json");
+        assert_eq!(hits[0].to_string(), "service.json @ 00:02:00 — Intention matters.");
+        assert!(hits.iter().any(|hit| hit.to_string().starts_with("intention.json @ 00:14:32 — ")));
+    }
+
+    #[test]
+    fn test_all_terms_must_match() {
+        let hits = archive().search("power intention", false, 10);
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].start_time, 0.0);
+    }
+
+    #[test]
+    fn test_phrase_search() {
+        let index = archive();
+
+        assert_eq!(index.search("\"look at things\"", false, 10).len(), 1);
+        assert!(index.search("\"things look at\"", false, 10).is_empty());
+        assert_eq!(index.search("\"power of\"", false, 10).len(), 1);
+    }
+
+    #[test]
+    fn test_fuzzy_search() {
+        let index = archive();
+
+        assert!(index.search("intension", false, 10).is_empty());
+        assert_eq!(index.search("intension~", false, 10).len(), 3);
+        assert_eq!(index.search("intension energi", true, 10).len(), 1);
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            parse_query("\"power of intention\" energy~2 self-esteem", false),
+            vec![
+                Clause::Phrase(vec!["power".into(), "of".into(), "intention".into()]),
+                Clause::Term { word: "energy".into(), max_edits: 2 },
+                Clause::Phrase(vec!["self".into(), "esteem".into()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snippet_window() {
+        let text = format!("{} the needle is here {}", "word ".repeat(60), "word ".repeat(60));
+        let snippet = snippet(&text, &["needle".to_string()]);
+
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert!(snippet.contains("needle"));
+    }
+
+    #[tokio::test]
+    async fn test_incremental_update() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, texts: &[(f64, &str)]| {
+            std::fs::write(dir.path().join(name), serde_json::to_string(&transcript(texts)).unwrap()).unwrap();
+        };
+        write("talk.json", &[(0.0, "The power of intention.")]);
+        std::fs::write(dir.path().join("talk.mp4"), b"").unwrap();
+        std::fs::write(dir.path().join("settings.json"), r#"{"theme": "dark"}"#).unwrap();
+
+        let mut index = SearchIndex::default();
+        let stats = index.update_from_dir(dir.path()).await.unwrap();
+        assert_eq!(stats, UpdateStats { added: 1, ..Default::default() });
+        let hit = &index.search("intention", false, 10)[0];
+        assert_eq!(hit.media, "talk.mp4");
+        assert_eq!(hit.media_path(), Some(dir.path().join("talk.mp4")));
+        assert_eq!(hit.end_time, 5.0);
+
+        let stats = index.update_from_dir(dir.path()).await.unwrap();
+        assert_eq!(stats, UpdateStats { unchanged: 1, ..Default::default() });
+
+        write("talk.json", &[(0.0, "Kindness is everything, and it stays."), (5.0, "Serve others.")]);
+        write("second.json", &[(3.0, "More kindness.")]);
+        let stats = index.update_from_dir(dir.path()).await.unwrap();
+        assert_eq!(stats, UpdateStats { added: 1, updated: 1, ..Default::default() });
+        assert!(index.search("intention", false, 10).is_empty());
+        assert_eq!(index.search("kindness", false, 10).len(), 2);
+
+        std::fs::remove_file(dir.path().join("second.json")).unwrap();
+        let stats = index.update_from_dir(dir.path()).await.unwrap();
+        assert_eq!(stats, UpdateStats { removed: 1, unchanged: 1, ..Default::default() });
+
+        let index_path = dir.path().join(DEFAULT_INDEX_FILE);
+        index.save(&index_path).await.unwrap();
+        let reloaded = SearchIndex::load(&index_path).await.unwrap();
+        assert_eq!(reloaded.len(), 1);
+        assert_eq!(reloaded.search("\"serve others\"", false, 10).len(), 1);
+
+        std::fs::write(&index_path, r#"{"documents": {}, "postings": {}, "next_id": 7}"#).unwrap();
+        assert!(SearchIndex::load(&index_path).await.unwrap().is_empty());
+    }
+}
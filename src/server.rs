@@ -0,0 +1,395 @@
+use axum::body::Bytes;
+use axum::extract::{DefaultBodyLimit, Path as UrlPath, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tracing::{info, warn};
+use crate::error::{Result, TranscribeError};
+use crate::output::{self, OutputFormat};
+use crate::transcriber::{AudioSource, Transcriber};
+use crate::transcription::{DecodingOptions, TranscriptionResult};
+
+mod captions;
+mod openai;
+
+/// Finished jobs kept for polling before the oldest are forgotten
+const MAX_FINISHED_JOBS: usize = 256;
+
+/// Settings for `serve`
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    pub addr: SocketAddr,
+    /// Transcriptions running at the same time
+    pub concurrency: usize,
+    /// Requests allowed to wait for a free slot before new ones are rejected
+    pub max_queue: usize,
+    /// Largest accepted upload in bytes
+    pub max_upload_bytes: usize,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            addr: SocketAddr::from(([127, 0, 0, 1], 8080)),
+            concurrency: 1,
+            max_queue: 16,
+            max_upload_bytes: 2 * 1024 * 1024 * 1024,
+        }
+    }
+}
+
+/// Lifecycle of a queued transcription job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+struct Job {
+    status: JobStatus,
+    format: OutputFormat,
+    output: Option<String>,
+    error: Option<String>,
+}
+
+/// Shared state behind every request: the loaded model and the job queue
+pub struct ServerState {
+    transcriber: Arc<Transcriber>,
+    options: ServerOptions,
+    permits: Semaphore,
+    queued: AtomicUsize,
+    next_id: AtomicU64,
+    jobs: Mutex<BTreeMap<u64, Job>>,
+}
+
+impl ServerState {
+    pub fn new(transcriber: Transcriber, options: ServerOptions) -> Arc<Self> {
+        Arc::new(Self {
+            permits: Semaphore::new(options.concurrency.max(1)),
+            transcriber: Arc::new(transcriber),
+            options,
+            queued: AtomicUsize::new(0),
+            next_id: AtomicU64::new(1),
+            jobs: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Wait for a free slot, then transcribe the uploaded media. The job, if
+    /// any, is marked running once a slot is free.
+    pub async fn transcribe_upload(
+        self: &Arc<Self>,
+        ticket: QueueTicket,
+        job_id: Option<u64>,
+        media: Bytes,
+        filename: Option<&str>,
+        options: &DecodingOptions,
+    ) -> Result<TranscriptionResult> {
+        let _permit = self.run(ticket).await?;
+
+        let id = match job_id {
+            Some(id) => {
+                self.update_job(id, |job| job.status = JobStatus::Running);
+                id
+            }
+            None => self.next_id.fetch_add(1, Ordering::SeqCst),
+        };
+        let (upload_path, source) = store_upload(id, &media, filename).await?;
+        let result = match crate::transcriber::load_source(source).await {
+            Ok(audio) => self.transcribe_blocking(audio, options.clone()).await,
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = tokio::fs::remove_file(&upload_path).await {
+            warn!("Could not clean up uploaded file: {}", e);
+        }
+        result
+    }
+
+    /// Reserve a place in the queue, or refuse if it is full
+    pub fn enqueue(self: &Arc<Self>) -> Option<QueueTicket> {
+        let waiting = self.queued.fetch_add(1, Ordering::SeqCst);
+        let ticket = QueueTicket { state: self.clone() };
+        (waiting < self.options.max_queue).then_some(ticket)
+    }
+
+    /// Wait for a free slot, leaving the queue once one is taken
+    async fn run(&self, ticket: QueueTicket) -> Result<SemaphorePermit<'_>> {
+        let permit = self.permits.acquire().await.map_err(|e| TranscribeError::Other(e.into()))?;
+        drop(ticket);
+        Ok(permit)
+    }
+
+    /// Decode samples on the blocking thread pool, so that inference does not
+    /// stall the runtime's workers
+    async fn transcribe_blocking(&self, audio: Vec<f32>, options: DecodingOptions) -> Result<TranscriptionResult> {
+        let transcriber = Arc::clone(&self.transcriber);
+        tokio::task::spawn_blocking(move || transcriber.transcribe_samples(&audio, &options))
+            .await
+            .map_err(|e| TranscribeError::Other(e.into()))?
+    }
+
+    fn update_job(&self, id: u64, update: impl FnOnce(&mut Job)) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if let Some(job) = jobs.get_mut(&id) {
+            update(job);
+        }
+
+        let finished: Vec<u64> = jobs
+            .iter()
+            .filter(|(_, job)| matches!(job.status, JobStatus::Completed | JobStatus::Failed))
+            .map(|(id, _)| *id)
+            .collect();
+        for old in finished.iter().take(finished.len().saturating_sub(MAX_FINISHED_JOBS)) {
+            jobs.remove(old);
+        }
+    }
+}
+
+/// A place in the transcription queue. It is given back when dropped, so a
+/// client that disconnects while waiting for a slot leaves the queue.
+pub struct QueueTicket {
+    state: Arc<ServerState>,
+}
+
+impl Drop for QueueTicket {
+    fn drop(&mut self) {
+        self.state.queued.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Write an upload to a temporary file. 16kHz mono WAV is read directly,
+/// everything else goes through FFmpeg.
+async fn store_upload(id: u64, media: &[u8], filename: Option<&str>) -> Result<(PathBuf, AudioSource)> {
+    let is_wav = media.len() > 12 && &media[0..4] == b"RIFF" && &media[8..12] == b"WAVE";
+    let extension = upload_extension(filename, is_wav);
+    let path = std::env::temp_dir().join(format!("wayne_upload_{}_{}.{}", std::process::id(), id, extension));
+    tokio::fs::write(&path, media).await?;
+
+    let direct_wav = is_wav
+        && hound::WavReader::open(&path)
+            .map(|reader| reader.spec())
+            .is_ok_and(|spec| spec.sample_rate == 16000 && spec.channels == 1);
+
+    let source = if direct_wav {
+        AudioSource::Wav(path.clone())
+    } else {
+        AudioSource::File(path.clone())
+    };
+    Ok((path, source))
+}
+
+/// Extension for a stored upload. The client's one helps FFmpeg guess the
+/// format, but is only used when it is 1 to 8 ASCII letters or digits.
+fn upload_extension(filename: Option<&str>, is_wav: bool) -> &str {
+    match filename.and_then(|name| name.rsplit_once('.')) {
+        Some((_, extension))
+            if (1..=8).contains(&extension.len()) && extension.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            extension
+        }
+        _ if is_wav => "wav",
+        _ => "bin",
+    }
+}
+
+/// HTTP status for a failed transcription
+pub fn status_for(error: &TranscribeError) -> StatusCode {
+    match error {
+        TranscribeError::UnsupportedFormat(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        TranscribeError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        TranscribeError::Decode(_) | TranscribeError::FfmpegFailed { .. } | TranscribeError::MissingInput(_) => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub fn error_response(status: StatusCode, message: impl Into<String>) -> Response {
+    (status, Json(serde_json::json!({ "error": message.into() }))).into_response()
+}
+
+fn output_response(format: &OutputFormat, body: String) -> Response {
+    ([(header::CONTENT_TYPE, format.mime_type())], body).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+struct UploadQuery {
+    format: Option<String>,
+    filename: Option<String>,
+}
+
+impl UploadQuery {
+    fn output_format(&self) -> Result<OutputFormat> {
+        OutputFormat::from_str(self.format.as_deref().unwrap_or("json"))
+    }
+}
+
+/// Build the HTTP API around shared server state
+pub fn router(state: Arc<ServerState>) -> Router {
+    let upload_limit = state.options.max_upload_bytes;
+    Router::new()
+        .route("/health", get(health))
+        .route("/transcribe", post(transcribe))
+        .route("/jobs", post(create_job))
+        .route("/jobs/{id}", get(get_job))
+        .route("/jobs/{id}/result", get(get_job_result))
+        .route("/stream", get(captions::captions))
+        .route("/v1/audio/transcriptions", post(openai::transcriptions))
+        .route("/v1/audio/translations", post(openai::translations))
+        .layer(DefaultBodyLimit::max(upload_limit))
+        .with_state(state)
+}
+
+/// Serve the HTTP API until Ctrl+C
+pub async fn serve(transcriber: Transcriber, options: ServerOptions) -> Result<()> {
+    let addr = options.addr;
+    let state = ServerState::new(transcriber, options);
+
+    let listener = TcpListener::bind(addr).await?;
+    info!("🌐 Listening on http://{}", listener.local_addr()?);
+
+    axum::serve(listener, router(state))
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+            info!("Shutting down server");
+        })
+        .await?;
+    Ok(())
+}
+
+async fn health(State(state): State<Arc<ServerState>>) -> Response {
+    let running = state.options.concurrency.max(1) - state.permits.available_permits();
+    Json(serde_json::json!({
+        "status": "ok",
+        "model": state.transcriber.model_path(),
+        "model_format": state.transcriber.model_format().to_string(),
+        "running": running,
+        "queued": state.queued.load(Ordering::SeqCst),
+        "concurrency": state.options.concurrency,
+    }))
+    .into_response()
+}
+
+/// Transcribe the request body and return the result in the requested format
+async fn transcribe(State(state): State<Arc<ServerState>>, Query(query): Query<UploadQuery>, media: Bytes) -> Response {
+    let format = match query.output_format() {
+        Ok(format) => format,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    if media.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, "Request body must contain a media file");
+    }
+    let Some(ticket) = state.enqueue() else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "Transcription queue is full");
+    };
+
+    let result = state
+        .transcribe_upload(ticket, None, media, query.filename.as_deref(), state.transcriber.decoding_options())
+        .await
+        .and_then(|transcription| output::format_transcription(&transcription, &format));
+    match result {
+        Ok(body) => output_response(&format, body),
+        Err(e) => error_response(status_for(&e), e.to_string()),
+    }
+}
+
+/// Queue the request body for transcription and return a job ID to poll
+async fn create_job(State(state): State<Arc<ServerState>>, Query(query): Query<UploadQuery>, media: Bytes) -> Response {
+    let format = match query.output_format() {
+        Ok(format) => format,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, e.to_string()),
+    };
+    if media.is_empty() {
+        return error_response(StatusCode::BAD_REQUEST, "Request body must contain a media file");
+    }
+    let Some(ticket) = state.enqueue() else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "Transcription queue is full");
+    };
+
+    let id = state.next_id.fetch_add(1, Ordering::SeqCst);
+    state.jobs.lock().unwrap().insert(
+        id,
+        Job {
+            status: JobStatus::Queued,
+            format,
+            output: None,
+            error: None,
+        },
+    );
+
+    let worker = state.clone();
+    tokio::spawn(async move {
+        let result = worker
+            .transcribe_upload(ticket, Some(id), media, query.filename.as_deref(), worker.transcriber.decoding_options())
+            .await
+            .and_then(|transcription| output::format_transcription(&transcription, &format));
+
+        worker.update_job(id, |job| match result {
+            Ok(output) => {
+                job.status = JobStatus::Completed;
+                job.output = Some(output);
+            }
+            Err(e) => {
+                job.status = JobStatus::Failed;
+                job.error = Some(e.to_string());
+            }
+        });
+    });
+
+    (StatusCode::ACCEPTED, Json(serde_json::json!({ "id": id, "status": JobStatus::Queued }))).into_response()
+}
+
+async fn get_job(State(state): State<Arc<ServerState>>, UrlPath(id): UrlPath<u64>) -> Response {
+    match state.jobs.lock().unwrap().get(&id) {
+        Some(job) => Json(serde_json::json!({
+            "id": id,
+            "status": job.status,
+            "error": job.error,
+        }))
+        .into_response(),
+        None => error_response(StatusCode::NOT_FOUND, format!("No job with id {}", id)),
+    }
+}
+
+async fn get_job_result(State(state): State<Arc<ServerState>>, UrlPath(id): UrlPath<u64>) -> Response {
+    let job = state.jobs.lock().unwrap().get(&id).cloned();
+    match job {
+        Some(Job { status: JobStatus::Completed, format, output: Some(output), .. }) => output_response(&format, output),
+        Some(Job { status: JobStatus::Failed, error, .. }) => {
+            error_response(StatusCode::UNPROCESSABLE_ENTITY, error.unwrap_or_default())
+        }
+        Some(job) => error_response(StatusCode::CONFLICT, format!("Job {} is still {:?}", id, job.status)),
+        None => error_response(StatusCode::NOT_FOUND, format!("No job with id {}", id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upload_extension() {
+        assert_eq!(upload_extension(Some("talk.MP4"), false), "MP4");
+        assert_eq!(upload_extension(Some("talk.wav"), true), "wav");
+        assert_eq!(upload_extension(Some("talk"), true), "wav");
+        assert_eq!(upload_extension(None, false), "bin");
+        assert_eq!(upload_extension(Some("x./../../etc/passwd"), false), "bin");
+        assert_eq!(upload_extension(Some("talk.m p4"), true), "wav");
+        assert_eq!(upload_extension(Some("talk.verylongext"), false), "bin");
+        assert_eq!(upload_extension(Some("talk."), false), "bin");
+    }
+}
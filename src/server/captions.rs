@@ -0,0 +1,122 @@
+//! WebSocket live captioning: binary 16-bit PCM frames in, partial and final
+//! caption messages out.
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::Response;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{debug, warn};
+use crate::streaming::{PcmDecoder, StreamEvent, StreamOptions, StreamSession};
+use super::{error_response, QueueTicket, ServerState};
+
+/// Optional overrides of the streaming defaults, e.g. `/stream?commit_silence=0.4`
+#[derive(Debug, Default, Deserialize)]
+pub(super) struct CaptionQuery {
+    partial_interval: Option<f64>,
+    commit_silence: Option<f64>,
+    max_segment: Option<f64>,
+}
+
+impl CaptionQuery {
+    fn stream_options(&self) -> StreamOptions {
+        let defaults = StreamOptions::default();
+        StreamOptions {
+            partial_interval: self.partial_interval.unwrap_or(defaults.partial_interval),
+            commit_silence: self.commit_silence.unwrap_or(defaults.commit_silence),
+            max_segment: self.max_segment.unwrap_or(defaults.max_segment),
+        }
+    }
+}
+
+/// Text messages a client may send besides audio
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ControlMessage {
+    /// No more audio: finalize the current segment and close
+    End,
+}
+
+pub(super) async fn captions(
+    State(state): State<Arc<ServerState>>,
+    Query(query): Query<CaptionQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let options = query.stream_options();
+    if let Err(e) = options.validate() {
+        return error_response(StatusCode::BAD_REQUEST, e.to_string());
+    }
+    let Some(ticket) = state.enqueue() else {
+        return error_response(StatusCode::SERVICE_UNAVAILABLE, "Transcription queue is full");
+    };
+    upgrade.on_upgrade(move |socket| caption_session(state, ticket, socket, options))
+}
+
+/// A session takes a transcription slot for as long as it is connected, like
+/// an upload does for one decode. Audio sent while it waits for the slot is
+/// buffered by the socket.
+async fn caption_session(state: Arc<ServerState>, ticket: QueueTicket, mut socket: WebSocket, options: StreamOptions) {
+    let _permit = match state.run(ticket).await {
+        Ok(permit) => permit,
+        Err(e) => {
+            let _ = send_error(&mut socket, &e.to_string()).await;
+            return;
+        }
+    };
+    let mut session = StreamSession::new(Arc::clone(&state.transcriber), options);
+    let mut decoder = PcmDecoder::default();
+
+    while let Some(message) = socket.recv().await {
+        let events = match message {
+            Ok(Message::Binary(frame)) => session.push(&decoder.decode(&frame)).await,
+            Ok(Message::Text(text)) => match serde_json::from_str::<ControlMessage>(&text) {
+                Ok(ControlMessage::End) => break,
+                Err(e) => {
+                    let _ = send_error(&mut socket, &format!("Unknown control message: {}", e)).await;
+                    continue;
+                }
+            },
+            // The client closed the connection; nothing more can be sent
+            Ok(Message::Close(_)) | Err(_) => return,
+            Ok(_) => continue,
+        };
+
+        match events {
+            Ok(events) => {
+                if send_events(&mut socket, &events).await.is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!("Caption stream failed: {}", e);
+                let _ = send_error(&mut socket, &e.to_string()).await;
+                return;
+            }
+        }
+    }
+
+    match session.finish().await {
+        Ok(events) => {
+            let _ = send_events(&mut socket, &events).await;
+        }
+        Err(e) => {
+            let _ = send_error(&mut socket, &e.to_string()).await;
+        }
+    }
+    let _ = socket.send(Message::Close(None)).await;
+    debug!("Caption stream finished");
+}
+
+async fn send_events(socket: &mut WebSocket, events: &[StreamEvent]) -> Result<(), axum::Error> {
+    for event in events {
+        let json = serde_json::to_string(event).expect("stream events serialize to JSON");
+        socket.send(Message::Text(json.into())).await?;
+    }
+    Ok(())
+}
+
+async fn send_error(socket: &mut WebSocket, message: &str) -> Result<(), axum::Error> {
+    let json = serde_json::json!({ "type": "error", "message": message }).to_string();
+    socket.send(Message::Text(json.into())).await
+}
@@ -0,0 +1,356 @@
+//! OpenAI-compatible `/v1/audio/transcriptions` and `/v1/audio/translations`,
+//! so existing OpenAI SDK clients can point their base URL at this server.
+
+use axum::body::Bytes;
+use axum::extract::{Multipart, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::Arc;
+use crate::output::{self, OutputFormat};
+use crate::transcription::{self, TranscriptionResult};
+use super::{status_for, ServerState};
+
+/// `response_format` values accepted by the OpenAI API
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    Text,
+    Srt,
+    VerboseJson,
+    Vtt,
+}
+
+impl FromStr for ResponseFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "text" => Ok(Self::Text),
+            "srt" => Ok(Self::Srt),
+            "verbose_json" => Ok(Self::VerboseJson),
+            "vtt" => Ok(Self::Vtt),
+            other => Err(format!(
+                "Unsupported response_format '{}'. Use json, text, srt, verbose_json or vtt",
+                other
+            )),
+        }
+    }
+}
+
+/// Whisper's languages by ISO-639-1 code, with the names OpenAI reports them by
+const LANGUAGES: [(&str, &str); 100] = [
+    ("en", "english"), ("zh", "chinese"), ("de", "german"), ("es", "spanish"), ("ru", "russian"),
+    ("ko", "korean"), ("fr", "french"), ("ja", "japanese"), ("pt", "portuguese"), ("tr", "turkish"),
+    ("pl", "polish"), ("ca", "catalan"), ("nl", "dutch"), ("ar", "arabic"), ("sv", "swedish"),
+    ("it", "italian"), ("id", "indonesian"), ("hi", "hindi"), ("fi", "finnish"), ("vi", "vietnamese"),
+    ("he", "hebrew"), ("uk", "ukrainian"), ("el", "greek"), ("ms", "malay"), ("cs", "czech"),
+    ("ro", "romanian"), ("da", "danish"), ("hu", "hungarian"), ("ta", "tamil"), ("no", "norwegian"),
+    ("th", "thai"), ("ur", "urdu"), ("hr", "croatian"), ("bg", "bulgarian"), ("lt", "lithuanian"),
+    ("la", "latin"), ("mi", "maori"), ("ml", "malayalam"), ("cy", "welsh"), ("sk", "slovak"),
+    ("te", "telugu"), ("fa", "persian"), ("lv", "latvian"), ("bn", "bengali"), ("sr", "serbian"),
+    ("az", "azerbaijani"), ("sl", "slovenian"), ("kn", "kannada"), ("et", "estonian"), ("mk", "macedonian"),
+    ("br", "breton"), ("eu", "basque"), ("is", "icelandic"), ("hy", "armenian"), ("ne", "nepali"),
+    ("mn", "mongolian"), ("bs", "bosnian"), ("kk", "kazakh"), ("sq", "albanian"), ("sw", "swahili"),
+    ("gl", "galician"), ("mr", "marathi"), ("pa", "punjabi"), ("si", "sinhala"), ("km", "khmer"),
+    ("sn", "shona"), ("yo", "yoruba"), ("so", "somali"), ("af", "afrikaans"), ("oc", "occitan"),
+    ("ka", "georgian"), ("be", "belarusian"), ("tg", "tajik"), ("sd", "sindhi"), ("gu", "gujarati"),
+    ("am", "amharic"), ("yi", "yiddish"), ("lo", "lao"), ("uz", "uzbek"), ("fo", "faroese"),
+    ("ht", "haitian creole"), ("ps", "pashto"), ("tk", "turkmen"), ("nn", "nynorsk"), ("mt", "maltese"),
+    ("sa", "sanskrit"), ("lb", "luxembourgish"), ("my", "myanmar"), ("bo", "tibetan"), ("tl", "tagalog"),
+    ("mg", "malagasy"), ("as", "assamese"), ("tt", "tatar"), ("haw", "hawaiian"), ("ln", "lingala"),
+    ("ha", "hausa"), ("ba", "bashkir"), ("jw", "javanese"), ("su", "sundanese"), ("yue", "cantonese"),
+];
+
+/// OpenAI's name for a language code; unknown codes are reported as given
+fn language_name(code: &str) -> String {
+    LANGUAGES
+        .iter()
+        .find(|(known, _)| *known == code)
+        .map_or_else(|| code.to_string(), |(_, name)| name.to_string())
+}
+
+/// Fields of a multipart transcription request
+#[derive(Debug, Default)]
+struct AudioRequest {
+    file: Option<(Option<String>, Bytes)>,
+    model: Option<String>,
+    language: Option<String>,
+    prompt: Option<String>,
+    response_format: Option<String>,
+    temperature: Option<f32>,
+    word_timestamps: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct VerboseTranscription {
+    task: &'static str,
+    language: String,
+    duration: f64,
+    text: String,
+    segments: Vec<VerboseSegment>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    words: Option<Vec<VerboseWord>>,
+}
+
+#[derive(Debug, Serialize)]
+struct VerboseSegment {
+    id: usize,
+    seek: u64,
+    start: f64,
+    end: f64,
+    text: String,
+    tokens: Vec<u32>,
+    temperature: f32,
+    avg_logprob: f32,
+    compression_ratio: f32,
+    no_speech_prob: f32,
+}
+
+#[derive(Debug, Serialize)]
+struct VerboseWord {
+    word: String,
+    start: f64,
+    end: f64,
+}
+
+/// Error body in the shape OpenAI clients expect
+fn openai_error(status: StatusCode, message: impl Into<String>, param: Option<&str>) -> Response {
+    let error_type = if status.is_client_error() {
+        "invalid_request_error"
+    } else {
+        "server_error"
+    };
+    let body = serde_json::json!({
+        "error": {
+            "message": message.into(),
+            "type": error_type,
+            "param": param,
+            "code": null,
+        }
+    });
+    (status, Json(body)).into_response()
+}
+
+pub(super) async fn transcriptions(state: State<Arc<ServerState>>, multipart: Multipart) -> Response {
+    handle(state, multipart, false).await
+}
+
+pub(super) async fn translations(state: State<Arc<ServerState>>, multipart: Multipart) -> Response {
+    handle(state, multipart, true).await
+}
+
+async fn handle(State(state): State<Arc<ServerState>>, multipart: Multipart, translate: bool) -> Response {
+    let request = match read_request(multipart).await {
+        Ok(request) => request,
+        Err(response) => return *response,
+    };
+
+    let Some((filename, media)) = request.file else {
+        return openai_error(StatusCode::BAD_REQUEST, "Missing required parameter: 'file'", Some("file"));
+    };
+    if request.model.is_none() {
+        return openai_error(StatusCode::BAD_REQUEST, "Missing required parameter: 'model'", Some("model"));
+    }
+    let format = match request.response_format.as_deref().map(ResponseFormat::from_str).transpose() {
+        Ok(format) => format.unwrap_or(ResponseFormat::Json),
+        Err(message) => return openai_error(StatusCode::BAD_REQUEST, message, Some("response_format")),
+    };
+    let temperature = request.temperature.unwrap_or(0.0);
+    if !(0.0..=1.0).contains(&temperature) {
+        return openai_error(
+            StatusCode::BAD_REQUEST,
+            format!("temperature must be between 0 and 1, got {}", temperature),
+            Some("temperature"),
+        );
+    }
+    if let Some(language) = request.language.as_deref()
+        && !LANGUAGES.iter().any(|(code, _)| *code == language)
+    {
+        return openai_error(
+            StatusCode::BAD_REQUEST,
+            format!("language must be an ISO-639-1 code Whisper supports, such as 'en', got '{}'", language),
+            Some("language"),
+        );
+    }
+    let Some(ticket) = state.enqueue() else {
+        return openai_error(StatusCode::SERVICE_UNAVAILABLE, "Transcription queue is full", None);
+    };
+
+    // The loaded model is always used; `model` is only checked for presence
+    let mut options = state.transcriber.decoding_options().clone();
+    if let Some(prompt) = request.prompt.as_deref() {
+        options.initial_prompt = options.vocabulary.initial_prompt(Some(prompt));
+    }
+    options.language = if translate { None } else { request.language.clone() };
+    options.translate = translate;
+    options.temperature = temperature;
+
+    let transcription = match state.transcribe_upload(ticket, None, media, filename.as_deref(), &options).await {
+        Ok(transcription) => transcription,
+        Err(e) => return openai_error(status_for(&e), e.to_string(), Some("file")),
+    };
+
+    // Translations are always English; transcriptions report the requested or detected language
+    let language = match (translate, &transcription.language) {
+        (true, _) => "english".to_string(),
+        (false, Some(code)) => language_name(code),
+        (false, None) => String::new(),
+    };
+    let task = if translate { "translate" } else { "transcribe" };
+
+    match format {
+        ResponseFormat::Json => Json(serde_json::json!({ "text": transcription.full_text })).into_response(),
+        ResponseFormat::Text => text_response(OutputFormat::Text, transcription.full_text),
+        ResponseFormat::Srt => text_response(OutputFormat::Srt, output::format_as_srt(&transcription)),
+        ResponseFormat::Vtt => text_response(OutputFormat::Vtt, output::format_as_vtt(&transcription)),
+        ResponseFormat::VerboseJson => Json(verbose_transcription(
+            &transcription,
+            task,
+            language,
+            options.temperature,
+            request.word_timestamps,
+        ))
+        .into_response(),
+    }
+}
+
+fn text_response(format: OutputFormat, body: String) -> Response {
+    ([(header::CONTENT_TYPE, format.mime_type())], body).into_response()
+}
+
+/// Collect the multipart fields, ignoring ones this server does not know
+async fn read_request(mut multipart: Multipart) -> Result<AudioRequest, Box<Response>> {
+    let invalid = |message: String| Box::new(openai_error(StatusCode::BAD_REQUEST, message, None));
+    let mut request = AudioRequest::default();
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| invalid(e.to_string()))? {
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "file" {
+            let filename = field.file_name().map(str::to_string);
+            let media = field.bytes().await.map_err(|e| invalid(e.to_string()))?;
+            request.file = Some((filename, media));
+            continue;
+        }
+
+        let value = field.text().await.map_err(|e| invalid(e.to_string()))?;
+        match name.as_str() {
+            "model" => request.model = Some(value),
+            "language" => request.language = Some(value),
+            "prompt" => request.prompt = Some(value),
+            "response_format" => request.response_format = Some(value),
+            "temperature" => {
+                let temperature = value
+                    .parse()
+                    .map_err(|_| invalid(format!("Invalid temperature '{}'", value)))?;
+                request.temperature = Some(temperature);
+            }
+            "timestamp_granularities[]" | "timestamp_granularities" => match value.as_str() {
+                "word" => request.word_timestamps = true,
+                "segment" => {}
+                other => return Err(invalid(format!("Unsupported timestamp granularity '{}'", other))),
+            },
+            _ => {}
+        }
+    }
+    Ok(request)
+}
+
+fn verbose_transcription(
+    transcription: &TranscriptionResult,
+    task: &'static str,
+    language: String,
+    temperature: f32,
+    word_timestamps: bool,
+) -> VerboseTranscription {
+    let segments = transcription
+        .segments
+        .iter()
+        .enumerate()
+        .map(|(id, segment)| VerboseSegment {
+            id,
+            seek: (segment.start_time * 100.0) as u64,
+            start: segment.start_time,
+            end: segment.end_time,
+            text: segment.text.clone(),
+            tokens: Vec::new(),
+            temperature,
+            avg_logprob: segment.avg_logprob.unwrap_or(0.0),
+            compression_ratio: transcription::compression_ratio(&segment.text),
+            no_speech_prob: segment.no_speech_prob.unwrap_or(0.0),
+        })
+        .collect();
+
+    let words = word_timestamps.then(|| {
+        transcription
+            .segments
+            .iter()
+            .flat_map(|segment| &segment.words)
+            .map(|word| VerboseWord {
+                word: word.word.trim().to_string(),
+                start: word.start_time,
+                end: word.end_time,
+            })
+            .collect()
+    });
+
+    VerboseTranscription {
+        task,
+        language,
+        duration: transcription.segments.last().map_or(0.0, |segment| segment.end_time),
+        text: transcription.full_text.clone(),
+        segments,
+        words,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::{TranscriptionSegment, WordTiming};
+
+    #[test]
+    fn test_response_format() {
+        assert_eq!(ResponseFormat::from_str("verbose_json").unwrap(), ResponseFormat::VerboseJson);
+        assert!(ResponseFormat::from_str("docx").is_err());
+    }
+
+    #[test]
+    fn test_language_names() {
+        assert_eq!(language_name("en"), "english");
+        assert_eq!(language_name("yue"), "cantonese");
+        assert_eq!(language_name("xx"), "xx");
+    }
+
+    #[test]
+    fn test_verbose_transcription() {
+        let transcription = TranscriptionResult {
+            segments: vec![TranscriptionSegment {
+                start_time: 1.5,
+                end_time: 3.0,
+                text: "Change your thoughts".to_string(),
+                words: vec![WordTiming {
+                    start_time: 1.5,
+                    end_time: 2.0,
+                    word: " Change".to_string(),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }],
+            full_text: "Change your thoughts".to_string(),
+            language: None,
+        };
+
+        let verbose = verbose_transcription(&transcription, "transcribe", "en".to_string(), 0.0, true);
+
+        assert_eq!(verbose.duration, 3.0);
+        assert_eq!(verbose.segments[0].seek, 150);
+        assert_eq!(verbose.words.unwrap()[0].word, "Change");
+        assert!(verbose_transcription(&transcription, "transcribe", "en".to_string(), 0.0, false)
+            .words
+            .is_none());
+    }
+}
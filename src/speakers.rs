@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::{debug, info};
+use crate::diarization::cosine_distance;
+use crate::error::{Result, TranscribeError};
+use crate::models::ModelStore;
+
+/// File name of the speaker database in the model store
+pub const DATABASE_FILE: &str = "speaker_profiles.json";
+
+/// Default location of the speaker database: next to the Whisper models in
+/// the store, so enrolled speakers are found from any working directory
+pub fn default_database_path() -> PathBuf {
+    ModelStore::default_dir().join(DATABASE_FILE)
+}
+
+/// Cosine distance under which a voice is considered to match an enrolled speaker
+const MATCH_THRESHOLD: f32 = 0.25;
+
+/// Voice signature of a known speaker, averaged over every enrolled clip
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpeakerProfile {
+    pub name: String,
+    pub embedding: Vec<f32>,
+    pub clips: usize,
+}
+
+/// Local database of enrolled speakers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpeakerDatabase {
+    pub speakers: Vec<SpeakerProfile>,
+}
+
+impl SpeakerDatabase {
+    /// Load the database, returning an empty one if it has not been created yet.
+    /// A file that is not a speaker database is [`TranscribeError::InvalidInput`].
+    pub async fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            debug!("No speaker database at {:?}, starting empty", path);
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(path).await?;
+        serde_json::from_str(&content)
+            .map_err(|e| TranscribeError::InvalidInput(format!("{:?} is not a valid speaker database: {}", path, e)))
+    }
+
+    /// Write the database as JSON, creating the parent directory if needed
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let content = serde_json::to_string_pretty(self).map_err(anyhow::Error::from)?;
+        fs::write(path, content).await?;
+
+        info!("💾 Saved {} speaker profile(s) to {:?}", self.speakers.len(), path);
+        Ok(())
+    }
+
+    /// Add a clip's voice embedding to a speaker, creating the profile on first use
+    pub fn enroll(&mut self, name: &str, embedding: Vec<f32>) {
+        match self.speakers.iter_mut().find(|s| s.name == name) {
+            Some(profile) => {
+                let clips = profile.clips as f32;
+                for (value, new) in profile.embedding.iter_mut().zip(&embedding) {
+                    *value = (*value * clips + new) / (clips + 1.0);
+                }
+                profile.clips += 1;
+            }
+            None => self.speakers.push(SpeakerProfile {
+                name: name.to_string(),
+                embedding,
+                clips: 1,
+            }),
+        }
+    }
+
+    /// Find the enrolled speaker closest to `embedding`, if any is close enough
+    pub fn identify(&self, embedding: &[f32]) -> Option<(&str, f32)> {
+        self.speakers
+            .iter()
+            .map(|profile| (profile.name.as_str(), cosine_distance(&profile.embedding, embedding)))
+            .filter(|(_, distance)| *distance < MATCH_THRESHOLD)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.speakers.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enroll_averages_clips() {
+        let mut database = SpeakerDatabase::default();
+        database.enroll("Lecturer", vec![1.0, 0.0]);
+        database.enroll("Lecturer", vec![0.0, 1.0]);
+
+        assert_eq!(database.speakers.len(), 1);
+        assert_eq!(database.speakers[0].clips, 2);
+        assert_eq!(database.speakers[0].embedding, vec![0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_default_database_lives_in_the_model_store() {
+        let path = default_database_path();
+        assert_eq!(path.parent(), Some(ModelStore::default_dir().as_path()));
+        assert_eq!(path.file_name().unwrap(), DATABASE_FILE);
+    }
+
+    #[test]
+    fn test_identify_closest_speaker() {
+        let mut database = SpeakerDatabase::default();
+        database.enroll("Lecturer", vec![1.0, 0.0, 0.0]);
+        database.enroll("Host", vec![0.0, 1.0, 0.0]);
+
+        assert_eq!(database.identify(&[0.1, 0.9, 0.0]).map(|(name, _)| name), Some("Host"));
+        assert_eq!(database.identify(&[0.0, 0.0, 1.0]), None);
+    }
+
+    #[tokio::test]
+    async fn test_load_and_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/speakers.json");
+        assert!(SpeakerDatabase::load(&path).await.unwrap().is_empty());
+
+        let mut database = SpeakerDatabase::default();
+        database.enroll("Lecturer", vec![1.0, 0.0]);
+        database.save(&path).await.unwrap();
+        assert_eq!(SpeakerDatabase::load(&path).await.unwrap().speakers.len(), 1);
+
+        std::fs::write(&path, "not json").unwrap();
+        assert!(matches!(SpeakerDatabase::load(&path).await, Err(TranscribeError::InvalidInput(_))));
+        assert!(matches!(SpeakerDatabase::load(dir.path()).await, Err(TranscribeError::Io(_))));
+    }
+}
@@ -0,0 +1,410 @@
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tracing::{debug, warn};
+use crate::error::{Result, TranscribeError};
+use crate::transcriber::Transcriber;
+use crate::vad::{self, SAMPLE_RATE};
+
+/// Silence kept before the next utterance when the buffer holds no speech (seconds)
+const LEADING_CONTEXT: f64 = 0.5;
+
+/// Bytes read from the input per iteration: 100ms of 16-bit PCM
+const READ_CHUNK: usize = SAMPLE_RATE / 10 * 2;
+
+/// Bytes searched for the `data` chunk of a WAV header before the input is
+/// taken for raw PCM after all
+const MAX_WAV_HEADER: usize = 64 * 1024;
+
+/// Settings for rolling-window streaming transcription
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    /// New audio between two decodes of the current utterance (seconds)
+    pub partial_interval: f64,
+    /// Silence after speech that commits the utterance as final (seconds)
+    pub commit_silence: f64,
+    /// Utterances are committed once they grow this long, even without a pause (seconds)
+    pub max_segment: f64,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        Self {
+            partial_interval: 1.0,
+            commit_silence: 0.6,
+            max_segment: 15.0,
+        }
+    }
+}
+
+impl StreamOptions {
+    /// Every interval must be a positive number of seconds
+    pub fn validate(&self) -> Result<()> {
+        for (name, value) in [
+            ("partial_interval", self.partial_interval),
+            ("commit_silence", self.commit_silence),
+            ("max_segment", self.max_segment),
+        ] {
+            if !(value.is_finite() && value > 0.0) {
+                return Err(TranscribeError::InvalidInput(format!(
+                    "{} must be a positive number of seconds, got {}",
+                    name, value
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An utterance, timed from the start of the stream. Partial and final
+/// events for the same utterance share an `id`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StreamSegment {
+    pub id: u64,
+    pub start_time: f64,
+    pub end_time: f64,
+    pub text: String,
+}
+
+/// Emitted as one NDJSON line per event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamEvent {
+    /// Current hypothesis for an utterance still in progress; replaced by later events with the same id
+    Partial(StreamSegment),
+    /// The utterance is complete and will not change
+    Final(StreamSegment),
+}
+
+impl StreamEvent {
+    pub fn segment(&self) -> &StreamSegment {
+        match self {
+            Self::Partial(segment) | Self::Final(segment) => segment,
+        }
+    }
+}
+
+/// Incremental transcription of a live audio stream.
+///
+/// Audio is buffered until the utterance in progress is committed. Every
+/// `partial_interval` the open tail of the buffer is decoded as a partial
+/// result: segments the decoder has already closed are settled and not
+/// decoded again, so each partial only covers audio after the last of them.
+/// Once the VAD sees `commit_silence` of silence after speech (or the buffer
+/// reaches `max_segment`), the tail is decoded one last time as a final
+/// result and the utterance is dropped from the buffer.
+///
+/// Decoding runs on a blocking thread, so a session does not stall the async
+/// runtime it is driven from.
+pub struct StreamSession {
+    transcriber: Arc<Transcriber>,
+    options: StreamOptions,
+    buffer: Vec<f32>,
+    /// Stream time of `buffer[0]` in seconds
+    buffer_start: f64,
+    since_decode: usize,
+    next_id: u64,
+    last_partial: Option<String>,
+    /// Samples at the front of `buffer` covered by `settled_text`
+    settled: usize,
+    /// Text of the closed segments of the utterance in progress
+    settled_text: Vec<String>,
+}
+
+impl StreamSession {
+    pub fn new(transcriber: Arc<Transcriber>, options: StreamOptions) -> Self {
+        Self {
+            transcriber,
+            options,
+            buffer: Vec::new(),
+            buffer_start: 0.0,
+            since_decode: 0,
+            next_id: 0,
+            last_partial: None,
+            settled: 0,
+            settled_text: Vec::new(),
+        }
+    }
+
+    /// Add 16kHz mono samples, returning any partial or final results they produce
+    pub async fn push(&mut self, samples: &[f32]) -> Result<Vec<StreamEvent>> {
+        self.buffer.extend_from_slice(samples);
+        self.since_decode += samples.len();
+
+        if (self.since_decode as f64) < self.options.partial_interval * SAMPLE_RATE as f64 {
+            return Ok(Vec::new());
+        }
+        self.since_decode = 0;
+        self.process(false).await
+    }
+
+    /// Commit whatever speech is still buffered at the end of the stream
+    pub async fn finish(&mut self) -> Result<Vec<StreamEvent>> {
+        self.process(true).await
+    }
+
+    async fn process(&mut self, flush: bool) -> Result<Vec<StreamEvent>> {
+        let regions = vad::detect_speech_regions(&self.buffer);
+        let duration = self.buffer.len() as f64 / SAMPLE_RATE as f64;
+
+        let (Some(first), Some(last)) = (regions.first(), regions.last()) else {
+            // Only silence so far: keep a little context in front of the next utterance
+            let keep = (LEADING_CONTEXT * SAMPLE_RATE as f64) as usize;
+            if self.buffer.len() > keep && self.last_partial.is_none() {
+                self.advance(self.buffer.len() - keep);
+            }
+            return Ok(Vec::new());
+        };
+        let speech_start = first.start_time;
+
+        let commit_at = if flush || duration >= self.options.max_segment {
+            Some(duration)
+        } else if duration - last.end_time >= self.options.commit_silence {
+            Some(last.end_time)
+        } else {
+            None
+        };
+
+        let Some(commit_at) = commit_at else {
+            let text = self.decode_tail(self.buffer.len()).await?;
+            if text.is_empty() || self.last_partial.as_deref() == Some(text.as_str()) {
+                return Ok(Vec::new());
+            }
+            self.last_partial = Some(text.clone());
+            return Ok(vec![StreamEvent::Partial(self.segment(speech_start, duration, text))]);
+        };
+
+        let end_sample = ((commit_at * SAMPLE_RATE as f64) as usize).clamp(self.settled, self.buffer.len());
+        let text = self.decode_tail(end_sample).await?;
+        let had_partial = self.last_partial.take().is_some();
+        let segment = self.segment(speech_start, commit_at, text);
+        self.advance(end_sample);
+
+        if segment.text.is_empty() && !had_partial {
+            return Ok(Vec::new());
+        }
+        debug!("Committed segment {} at {:.2}s", segment.id, segment.end_time);
+        self.next_id += 1;
+        Ok(vec![StreamEvent::Final(segment)])
+    }
+
+    /// Decode the buffer from the end of the settled segments up to `end`,
+    /// returning the text of the whole utterance so far. Every segment but
+    /// the last one is closed by a timestamp and settled; the last one may
+    /// still grow and is decoded again next time.
+    async fn decode_tail(&mut self, end: usize) -> Result<String> {
+        let tail = self.buffer[self.settled..end].to_vec();
+        let transcriber = Arc::clone(&self.transcriber);
+        let transcription =
+            tokio::task::spawn_blocking(move || transcriber.transcribe_samples(&tail, transcriber.decoding_options()))
+                .await
+                .map_err(|e| TranscribeError::Other(e.into()))??;
+
+        let mut open = "";
+        if let Some((last, closed)) = transcription.segments.split_last() {
+            if let Some(closed_end) = closed.last().map(|segment| segment.end_time) {
+                self.settled = (self.settled + (closed_end * SAMPLE_RATE as f64) as usize).min(end);
+                self.settled_text.extend(
+                    closed.iter().map(|segment| segment.text.trim()).filter(|text| !text.is_empty()).map(str::to_string),
+                );
+            }
+            open = last.text.trim();
+        }
+
+        let mut text = self.settled_text.join(" ");
+        if !open.is_empty() {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            text.push_str(open);
+        }
+        Ok(text)
+    }
+
+    fn segment(&self, start_time: f64, end_time: f64, text: String) -> StreamSegment {
+        StreamSegment {
+            id: self.next_id,
+            start_time: self.buffer_start + start_time,
+            end_time: self.buffer_start + end_time,
+            text,
+        }
+    }
+
+    /// Drop samples from the front of the buffer
+    fn advance(&mut self, samples: usize) {
+        self.buffer.drain(..samples);
+        self.settled = 0;
+        self.settled_text.clear();
+        self.buffer_start += samples as f64 / SAMPLE_RATE as f64;
+    }
+}
+
+/// Converts a byte stream of 16-bit little-endian PCM to samples. A leading
+/// WAV header is skipped, so `cat talk.wav` can be piped in directly. If no
+/// `data` chunk shows up within the first 64 KiB, the input is decoded as raw
+/// PCM from its first byte.
+#[derive(Debug, Default)]
+pub struct PcmDecoder {
+    pending: Vec<u8>,
+    in_data: bool,
+}
+
+impl PcmDecoder {
+    pub fn decode(&mut self, bytes: &[u8]) -> Vec<f32> {
+        self.pending.extend_from_slice(bytes);
+
+        if !self.in_data {
+            match wav_data_offset(&self.pending) {
+                Some(offset) => {
+                    self.pending.drain(..offset);
+                    self.in_data = true;
+                }
+                None if self.pending.len() >= MAX_WAV_HEADER => {
+                    warn!("No WAV data chunk in the first {} bytes; decoding the input as raw PCM", MAX_WAV_HEADER);
+                    self.in_data = true;
+                }
+                None => return Vec::new(),
+            }
+        }
+
+        let complete = self.pending.len() - self.pending.len() % 2;
+        let samples = self.pending[..complete]
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as f32 / 32768.0)
+            .collect();
+        self.pending.drain(..complete);
+        samples
+    }
+}
+
+/// Where the sample data starts: after the `data` chunk header for WAV, 0 for
+/// raw PCM, or `None` if more bytes are needed to tell
+fn wav_data_offset(bytes: &[u8]) -> Option<usize> {
+    if bytes.len() < 4 {
+        return None;
+    }
+    if &bytes[..4] != b"RIFF" {
+        return Some(0);
+    }
+
+    let mut position = 12;
+    while position + 8 <= bytes.len() {
+        let id = &bytes[position..position + 4];
+        let size = u32::from_le_bytes(bytes[position + 4..position + 8].try_into().unwrap()) as usize;
+        if id == b"data" {
+            return Some(position + 8);
+        }
+        position += 8 + size + size % 2;
+    }
+    None
+}
+
+/// Transcribe PCM from `reader` until end of input, passing each event to `on_event`
+pub async fn stream_pcm<R: AsyncRead + Unpin>(
+    session: &mut StreamSession,
+    mut reader: R,
+    mut on_event: impl FnMut(&StreamEvent) -> Result<()>,
+) -> Result<()> {
+    let mut decoder = PcmDecoder::default();
+    let mut chunk = vec![0u8; READ_CHUNK];
+
+    loop {
+        let read = reader.read(&mut chunk).await?;
+        if read == 0 {
+            break;
+        }
+        for event in session.push(&decoder.decode(&chunk[..read])).await? {
+            on_event(&event)?;
+        }
+    }
+
+    for event in session.finish().await? {
+        on_event(&event)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav_bytes(samples: &[i16]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + samples.len() as u32 * 2).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&[1, 0, 1, 0]);
+        bytes.extend_from_slice(&16000u32.to_le_bytes());
+        bytes.extend_from_slice(&32000u32.to_le_bytes());
+        bytes.extend_from_slice(&[2, 0, 16, 0]);
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(samples.len() as u32 * 2).to_le_bytes());
+        for sample in samples {
+            bytes.extend_from_slice(&sample.to_le_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_decode_raw_pcm_across_chunks() {
+        let mut decoder = PcmDecoder::default();
+        let bytes = [0x00, 0x40, 0x00, 0xC0, 0xFF, 0x7F];
+
+        let mut samples = decoder.decode(&bytes[..3]);
+        samples.extend(decoder.decode(&bytes[3..]));
+
+        assert_eq!(samples, vec![0.5, -0.5, 32767.0 / 32768.0]);
+    }
+
+    #[test]
+    fn test_decode_skips_wav_header() {
+        let bytes = wav_bytes(&[16384, -16384]);
+        let mut decoder = PcmDecoder::default();
+
+        let mut samples = Vec::new();
+        for byte in bytes.chunks(5) {
+            samples.extend(decoder.decode(byte));
+        }
+
+        assert_eq!(samples, vec![0.5, -0.5]);
+    }
+
+    #[test]
+    fn test_header_search_is_bounded() {
+        // "RIFF" followed by chunks that never reach `data`
+        let mut bytes = b"RIFF\0\0\0\0WAVE".to_vec();
+        bytes.resize(MAX_WAV_HEADER - 1, 0);
+        let mut decoder = PcmDecoder::default();
+
+        assert!(decoder.decode(&bytes).is_empty());
+        let samples = decoder.decode(&[0, 0, 0x40]);
+        assert_eq!(samples.len(), (MAX_WAV_HEADER + 2) / 2);
+        assert_eq!(samples.last(), Some(&0.5));
+        assert!(decoder.pending.is_empty());
+    }
+
+    #[test]
+    fn test_validate_options() {
+        assert!(StreamOptions::default().validate().is_ok());
+        for value in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let options = StreamOptions { commit_silence: value, ..StreamOptions::default() };
+            assert!(matches!(options.validate(), Err(TranscribeError::InvalidInput(_))), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_event_json() {
+        let event = StreamEvent::Final(StreamSegment {
+            id: 3,
+            start_time: 1.0,
+            end_time: 2.5,
+            text: "Change your thoughts".to_string(),
+        });
+
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"type":"final","id":3,"start_time":1.0,"end_time":2.5,"text":"Change your thoughts"}"#
+        );
+    }
+}
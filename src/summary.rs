@@ -0,0 +1,346 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use crate::chapters::youtube_timestamp;
+use crate::error::Result;
+use crate::text;
+use crate::transcription::TranscriptionResult;
+
+/// PageRank damping factor used by TextRank
+const DAMPING: f64 = 0.85;
+const MAX_ITERATIONS: usize = 50;
+const CONVERGENCE: f64 = 1e-6;
+
+/// Words on either side that count as co-occurring for keyword ranking
+const COOCCURRENCE_WINDOW: usize = 2;
+
+/// Settings for [`summarize`]
+#[derive(Debug, Clone)]
+pub struct SummaryOptions {
+    pub sentences: usize,
+    pub keywords: usize,
+    pub keyphrases: usize,
+}
+
+impl Default for SummaryOptions {
+    fn default() -> Self {
+        Self {
+            sentences: 5,
+            keywords: 10,
+            keyphrases: 5,
+        }
+    }
+}
+
+/// Show notes for a transcript
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    /// Highest-ranked sentences, in the order they were spoken
+    pub sentences: Vec<String>,
+    pub keywords: Vec<Keyword>,
+    pub keyphrases: Vec<String>,
+    /// Where each keyword and keyphrase is spoken
+    pub index: Vec<IndexEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Keyword {
+    pub term: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexEntry {
+    pub term: String,
+    /// Start times of the segments mentioning the term, in seconds
+    pub timestamps: Vec<f64>,
+}
+
+/// Builds an extractive summary fully offline: sentences and keywords are
+/// ranked with TextRank, keyphrases are runs of adjacent keywords.
+pub fn summarize(transcription: &TranscriptionResult, options: &SummaryOptions) -> Summary {
+    let sentences = split_sentences(&transcription.full_text);
+    let ranked = rank_sentences(&sentences);
+    let mut top: Vec<usize> = ranked.into_iter().take(options.sentences).collect();
+    top.sort_unstable();
+
+    let word_scores = rank_words(&sentences);
+    let mut keywords: Vec<Keyword> = word_scores
+        .iter()
+        .map(|(term, &score)| Keyword { term: term.clone(), score })
+        .collect();
+    keywords.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.term.cmp(&b.term)));
+    keywords.truncate(options.keywords);
+
+    let keyphrases = extract_keyphrases(&sentences, &word_scores, options.keyphrases);
+
+    let terms = keywords.iter().map(|keyword| keyword.term.clone()).chain(keyphrases.iter().cloned());
+    let index = terms
+        .map(|term| {
+            let timestamps = transcription
+                .segments
+                .iter()
+                .filter(|segment| contains_phrase(&text::words(&segment.text), &term))
+                .map(|segment| segment.start_time)
+                .collect();
+            IndexEntry { term, timestamps }
+        })
+        .collect();
+
+    Summary {
+        sentences: top.into_iter().map(|index| sentences[index].clone()).collect(),
+        keywords,
+        keyphrases,
+        index,
+    }
+}
+
+/// Sentences of `text`, without repeats
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    text.split_inclusive(['.', '?', '!'])
+        .map(str::trim)
+        .filter(|sentence| !text::content_words(sentence).is_empty())
+        .filter(|sentence| seen.insert(text::words(sentence)))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Sentence indices, best first. Sentences are linked by shared content words,
+/// normalised by sentence length as in the original TextRank paper.
+fn rank_sentences(sentences: &[String]) -> Vec<usize> {
+    let words: Vec<HashSet<String>> = sentences
+        .iter()
+        .map(|sentence| text::content_words(sentence).into_iter().collect())
+        .collect();
+
+    let n = sentences.len();
+    let mut weights = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let shared = words[i].intersection(&words[j]).count() as f64;
+            let length = (words[i].len() as f64).ln() + (words[j].len() as f64).ln();
+            if shared > 0.0 && length > 0.0 {
+                weights[i][j] = shared / length;
+                weights[j][i] = shared / length;
+            }
+        }
+    }
+
+    let scores = pagerank(&weights);
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]).then(a.cmp(&b)));
+    order
+}
+
+/// TextRank scores of content words over a co-occurrence graph
+fn rank_words(sentences: &[String]) -> HashMap<String, f64> {
+    let mut vocabulary: Vec<String> = Vec::new();
+    let mut ids: HashMap<String, usize> = HashMap::new();
+    let mut edges: HashSet<(usize, usize)> = HashSet::new();
+
+    for sentence in sentences {
+        let words: Vec<usize> = text::content_words(sentence)
+            .into_iter()
+            .map(|word| {
+                *ids.entry(word.clone()).or_insert_with(|| {
+                    vocabulary.push(word);
+                    vocabulary.len() - 1
+                })
+            })
+            .collect();
+        for (position, &a) in words.iter().enumerate() {
+            for &b in words.iter().skip(position + 1).take(COOCCURRENCE_WINDOW) {
+                if a != b {
+                    edges.insert((a.min(b), a.max(b)));
+                }
+            }
+        }
+    }
+
+    let mut weights = vec![vec![0.0; vocabulary.len()]; vocabulary.len()];
+    for (a, b) in edges {
+        weights[a][b] = 1.0;
+        weights[b][a] = 1.0;
+    }
+    let scores = pagerank(&weights);
+    vocabulary.into_iter().zip(scores).collect()
+}
+
+/// Weighted PageRank over a symmetric adjacency matrix
+fn pagerank(weights: &[Vec<f64>]) -> Vec<f64> {
+    let n = weights.len();
+    if n == 0 {
+        return Vec::new();
+    }
+    let out_weight: Vec<f64> = weights.iter().map(|row| row.iter().sum()).collect();
+    let mut scores = vec![1.0 / n as f64; n];
+
+    for _ in 0..MAX_ITERATIONS {
+        let next: Vec<f64> = (0..n)
+            .map(|i| {
+                let incoming: f64 = (0..n)
+                    .filter(|&j| weights[j][i] > 0.0)
+                    .map(|j| weights[j][i] / out_weight[j] * scores[j])
+                    .sum();
+                (1.0 - DAMPING) / n as f64 + DAMPING * incoming
+            })
+            .collect();
+        let change: f64 = next.iter().zip(&scores).map(|(a, b)| (a - b).abs()).sum();
+        scores = next;
+        if change < CONVERGENCE {
+            break;
+        }
+    }
+    scores
+}
+
+/// Runs of two or more adjacent words from the top third of the keyword
+/// ranking, scored by the sum of their word scores
+fn extract_keyphrases(sentences: &[String], word_scores: &HashMap<String, f64>, limit: usize) -> Vec<String> {
+    let mut ranked: Vec<(&String, &f64)> = word_scores.iter().collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let candidates: HashSet<&str> = ranked
+        .iter()
+        .take(word_scores.len().div_ceil(3))
+        .map(|(word, _)| word.as_str())
+        .collect();
+
+    let mut phrases: HashMap<String, f64> = HashMap::new();
+    for sentence in sentences {
+        let mut run: Vec<String> = Vec::new();
+        for word in text::words(sentence).into_iter().chain([String::new()]) {
+            if candidates.contains(word.as_str()) {
+                run.push(word);
+                continue;
+            }
+            if run.len() >= 2 {
+                let score = run.iter().map(|word| word_scores[word]).sum();
+                phrases.insert(run.join(" "), score);
+            }
+            run.clear();
+        }
+    }
+
+    let mut phrases: Vec<(String, f64)> = phrases.into_iter().collect();
+    phrases.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    phrases.into_iter().take(limit).map(|(phrase, _)| phrase).collect()
+}
+
+/// Whether the space-separated `phrase` occurs as consecutive `words`
+fn contains_phrase(words: &[String], phrase: &str) -> bool {
+    let phrase: Vec<&str> = phrase.split(' ').collect();
+    words.windows(phrase.len()).any(|window| window.iter().zip(&phrase).all(|(a, b)| a == b))
+}
+
+pub fn format_summary_markdown(summary: &Summary) -> String {
+    let mut content = String::from("# Summary\n\n");
+    for sentence in &summary.sentences {
+        content.push_str(&format!("- {}\n", sentence));
+    }
+
+    content.push_str("\n## Keywords\n\n");
+    let keywords: Vec<&str> = summary.keywords.iter().map(|keyword| keyword.term.as_str()).collect();
+    content.push_str(&format!("{}\n", keywords.join(", ")));
+
+    if !summary.keyphrases.is_empty() {
+        content.push_str("\n## Keyphrases\n\n");
+        for phrase in &summary.keyphrases {
+            content.push_str(&format!("- {}\n", phrase));
+        }
+    }
+
+    content.push_str("\n## Index\n\n");
+    for entry in &summary.index {
+        let times: Vec<String> = entry.timestamps.iter().map(|&time| youtube_timestamp(time)).collect();
+        content.push_str(&format!("- **{}**: {}\n", entry.term, times.join(", ")));
+    }
+    content
+}
+
+/// Writes the summary as JSON for `.json` paths and Markdown otherwise
+pub async fn save_summary(summary: &Summary, path: &Path) -> Result<()> {
+    let is_json = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("json"));
+    let content = if is_json {
+        serde_json::to_string_pretty(summary).map_err(anyhow::Error::from)?
+    } else {
+        format_summary_markdown(summary)
+    };
+    tokio::fs::write(path, content).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcription::TranscriptionSegment;
+
+    fn lecture() -> TranscriptionResult {
+        let texts = [
+            "The power of intention shapes your reality.",
+            "I had coffee this morning.",
+            "Intention is energy, and the power of intention connects you to the source.",
+            "When you change the way you look at things, the things you look at change.",
+            "The power of intention shapes your reality.",
+            "Trust the power of intention and your reality changes.",
+        ];
+        let segments: Vec<TranscriptionSegment> = texts
+            .iter()
+            .enumerate()
+            .map(|(index, text)| TranscriptionSegment {
+                start_time: index as f64 * 30.0,
+                end_time: index as f64 * 30.0 + 28.0,
+                text: text.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        let full_text = texts.join(" ");
+        TranscriptionResult { segments, full_text, language: None }
+    }
+
+    #[test]
+    fn test_summary_sentences() {
+        let options = SummaryOptions { sentences: 2, ..Default::default() };
+        let summary = summarize(&lecture(), &options);
+
+        // Central sentences win, duplicates are dropped and spoken order is kept
+        assert_eq!(
+            summary.sentences,
+            vec![
+                "The power of intention shapes your reality.",
+                "Trust the power of intention and your reality changes.",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_keywords_and_index() {
+        let summary = summarize(&lecture(), &SummaryOptions::default());
+
+        assert_eq!(summary.keywords[0].term, "intention");
+
+        let intention = summary.index.iter().find(|entry| entry.term == "intention").unwrap();
+        assert_eq!(intention.timestamps, vec![0.0, 60.0, 120.0, 150.0]);
+    }
+
+    #[test]
+    fn test_keyphrases() {
+        let sentences = split_sentences(
+            "We are spiritual beings having a human experience. Spiritual beings choose. \
+             A human experience passes. Beings of light.",
+        );
+        let scores = rank_words(&sentences);
+
+        assert_eq!(extract_keyphrases(&sentences, &scores, 5)[0], "spiritual beings");
+    }
+
+    #[test]
+    fn test_markdown() {
+        let summary = summarize(&lecture(), &SummaryOptions { sentences: 1, ..Default::default() });
+        let markdown = format_summary_markdown(&summary);
+
+        assert!(markdown.starts_with("# Summary\n\n- "));
+        assert!(markdown.contains("## Keywords\n\nintention"));
+        assert!(markdown.contains("- **intention**: 00:00, 01:00, 02:00, 02:30\n"));
+    }
+}
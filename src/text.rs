@@ -0,0 +1,170 @@
+//! Tokenizing, number-spelling and edit-distance helpers shared by the transcript text passes
+
+/// Common English function words that carry no topic information
+const STOPWORDS: &[&str] = &[
+    "a", "about", "after", "again", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "being", "but", "by", "can", "could", "did", "do", "does", "doing",
+    "don't", "down", "each", "even", "every", "for", "from", "get", "got", "had", "has", "have", "having",
+    "he", "her", "here", "him", "his", "how", "i", "i'm", "if", "in", "into", "is", "isn't", "it", "it's",
+    "its", "just", "know", "let", "like", "me", "more", "most", "my", "no", "not", "now", "of", "off", "oh",
+    "on", "one", "only", "or", "other", "our", "out", "over", "really", "said", "say", "she", "so", "some",
+    "than", "that", "that's", "the", "their", "them", "then", "there", "these", "they", "things", "this",
+    "those", "through", "to", "too", "up", "us", "very", "was", "way", "we", "we're", "well", "were",
+    "what", "when", "where", "which", "while", "who", "why", "will", "with", "would", "yeah", "you",
+    "you're", "your",
+];
+
+pub(crate) const ONES: &[&str] = &[
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve",
+    "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+pub(crate) const TENS: &[&str] = &["", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+pub(crate) const SCALES: &[(u64, &str)] = &[
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+/// Lowercase words of `text`, keeping inner apostrophes ("don't")
+pub fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '\'' || c == '’'))
+        .map(|word| word.trim_matches(|c| c == '\'' || c == '’').replace('’', "'").to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+pub fn is_stopword(word: &str) -> bool {
+    STOPWORDS.binary_search(&word).is_ok()
+}
+
+/// Words of `text` that can carry topic information: no stopwords, numbers or single letters
+pub fn content_words(text: &str) -> Vec<String> {
+    words(text)
+        .into_iter()
+        .filter(|word| word.chars().count() > 1 && !is_stopword(word) && !word.chars().all(|c| c.is_numeric()))
+        .collect()
+}
+
+/// Replaces numbers such as `21`, `1,500` or `3.5` with words
+pub(crate) fn spell_numbers(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut result = String::with_capacity(text.len());
+    let mut index = 0;
+    while index < chars.len() {
+        if !chars[index].is_ascii_digit() {
+            result.push(chars[index]);
+            index += 1;
+            continue;
+        }
+
+        let mut integer = String::new();
+        let mut fraction: Option<String> = None;
+        while index < chars.len() {
+            let next_is_digit = chars.get(index + 1).is_some_and(char::is_ascii_digit);
+            match chars[index] {
+                c if c.is_ascii_digit() => match &mut fraction {
+                    Some(fraction) => fraction.push(c),
+                    None => integer.push(c),
+                },
+                ',' if next_is_digit && fraction.is_none() => {}
+                '.' if next_is_digit && fraction.is_none() => fraction = Some(String::new()),
+                _ => break,
+            }
+            index += 1;
+        }
+
+        match integer.parse::<u64>() {
+            Ok(number) => {
+                result.push_str(&number_words(number));
+                if let Some(fraction) = fraction {
+                    result.push_str(" point");
+                    for digit in fraction.chars() {
+                        result.push(' ');
+                        result.push_str(ONES[digit.to_digit(10).unwrap() as usize]);
+                    }
+                }
+            }
+            // Too long to be a quantity; keep the digits
+            Err(_) => {
+                result.push_str(&integer);
+                if let Some(fraction) = fraction {
+                    result.push('.');
+                    result.push_str(&fraction);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// `1234` → "one thousand two hundred thirty four"
+pub(crate) fn number_words(number: u64) -> String {
+    for &(scale, name) in SCALES {
+        if number >= scale {
+            let rest = number % scale;
+            let head = format!("{} {}", number_words(number / scale), name);
+            return if rest == 0 { head } else { format!("{} {}", head, number_words(rest)) };
+        }
+    }
+    match number {
+        0..=19 => ONES[number as usize].to_string(),
+        20..=99 if number.is_multiple_of(10) => TENS[number as usize / 10].to_string(),
+        20..=99 => format!("{} {}", TENS[number as usize / 10], ONES[number as usize % 10]),
+        _ if number.is_multiple_of(100) => format!("{} hundred", ONES[number as usize / 100]),
+        _ => format!("{} hundred {}", ONES[number as usize / 100], number_words(number % 100)),
+    }
+}
+
+/// Levenshtein distance, keeping only two rows of the table
+pub(crate) fn edit_distance<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+    edit_distance_row(a, b)[b.len()]
+}
+
+/// Distances from all of `a` to every prefix of `b`: the last row of the
+/// Levenshtein table, computed two rows at a time
+pub(crate) fn edit_distance_row<T: PartialEq>(a: &[T], b: &[T]) -> Vec<usize> {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, x) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, y) in b.iter().enumerate() {
+            current[j + 1] = (previous[j] + usize::from(x != y)).min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stopwords_are_sorted() {
+        assert!(STOPWORDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_content_words() {
+        assert_eq!(words("Don't   stop—it's 'real'."), vec!["don't", "stop", "it's", "real"]);
+        assert_eq!(content_words("When you change the way you look at things, in 2024"), vec!["change", "look"]);
+    }
+
+    #[test]
+    fn test_spell_numbers() {
+        assert_eq!(
+            spell_numbers("1,500 people, 3.5 hours, 2024."),
+            "one thousand five hundred people, three point five hours, two thousand twenty four."
+        );
+        assert_eq!(number_words(1_000_001), "one million one");
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let chars = |word: &str| word.chars().collect::<Vec<_>>();
+        assert_eq!(edit_distance(&chars("kitten"), &chars("sitting")), 3);
+        assert_eq!(edit_distance(&chars(""), &chars("abc")), 3);
+        assert_eq!(edit_distance_row(&chars("ab"), &chars("abc")), vec![2, 1, 0, 1]);
+    }
+}
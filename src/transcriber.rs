@@ -0,0 +1,209 @@
+use candle_core::Device;
+use candle_transformers::models::whisper::Config;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use crate::audio::{self, NetworkOptions};
+use crate::diarization;
+use crate::error::Result;
+use crate::models::{ModelFormat, ModelStore};
+use crate::speakers::SpeakerDatabase;
+use crate::transcription::{self, DecodingOptions, LoadedModel, ModelSpec, TranscriptionResult, WhisperModel};
+
+/// Audio to be transcribed
+#[derive(Debug, Clone)]
+pub enum AudioSource {
+    /// Any video or audio file FFmpeg can read; audio is extracted to a temporary WAV
+    File(PathBuf),
+    /// An HTTP(S) file, HLS `.m3u8` playlist or RTMP stream opened by FFmpeg
+    Url { url: String, network: NetworkOptions },
+    /// A 16kHz mono WAV file, read directly without FFmpeg
+    Wav(PathBuf),
+    /// 16kHz mono samples in the range -1.0..=1.0
+    Samples(Vec<f32>),
+}
+
+/// Speaker labelling settings
+#[derive(Debug, Clone, Default)]
+pub struct DiarizationOptions {
+    /// Exact number of speakers, or `None` to estimate it
+    pub num_speakers: Option<usize>,
+    /// Enrolled voices used to label speakers by name
+    pub known_speakers: SpeakerDatabase,
+}
+
+/// Builder for a [`Transcriber`]
+#[derive(Debug)]
+pub struct TranscriberBuilder {
+    model: ModelSpec,
+    model_path: Option<PathBuf>,
+    device: Device,
+    decoding: DecodingOptions,
+    diarization: Option<DiarizationOptions>,
+}
+
+impl Default for TranscriberBuilder {
+    fn default() -> Self {
+        Self {
+            model: WhisperModel::Base.into(),
+            model_path: None,
+            device: Device::Cpu,
+            decoding: DecodingOptions::default(),
+            diarization: None,
+        }
+    }
+}
+
+impl TranscriberBuilder {
+    /// Known model, store name, checkpoint path or Hugging Face repository (default: base)
+    pub fn model(mut self, model: impl Into<ModelSpec>) -> Self {
+        self.model = model.into();
+        self
+    }
+
+    /// Load the model from an explicit path instead of the model store; the
+    /// model set with [`TranscriberBuilder::model`] still supplies the
+    /// architecture if the file does not describe it
+    pub fn model_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.model_path = Some(path.into());
+        self
+    }
+
+    /// Device to run inference on (default: CPU)
+    pub fn device(mut self, device: Device) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// Prompt, vocabulary and hallucination handling for the decoder
+    pub fn decoding_options(mut self, options: DecodingOptions) -> Self {
+        self.decoding = options;
+        self
+    }
+
+    /// Label segments with speakers after transcription
+    pub fn diarization(mut self, options: DiarizationOptions) -> Self {
+        self.diarization = Some(options);
+        self
+    }
+
+    /// Load the model and create the transcriber
+    pub fn build(self) -> Result<Transcriber> {
+        let model_path = match (self.model_path, &self.model) {
+            (Some(path), _) => path,
+            (None, ModelSpec::Path(path)) => path.clone(),
+            (None, spec) => ModelStore::open_default()?.locate(spec)?,
+        };
+
+        info!("🤖 Using Whisper model: {}", self.model);
+        let fallback = match self.model {
+            ModelSpec::Known(model) => Some(model),
+            _ => None,
+        };
+        let model = transcription::load_model(&model_path, fallback, self.device)?;
+
+        Ok(Transcriber {
+            model,
+            decoding: self.decoding,
+            diarization: self.diarization,
+        })
+    }
+}
+
+/// A loaded Whisper model together with the options used for every transcription
+#[derive(Debug)]
+pub struct Transcriber {
+    model: LoadedModel,
+    decoding: DecodingOptions,
+    diarization: Option<DiarizationOptions>,
+}
+
+impl Transcriber {
+    pub fn builder() -> TranscriberBuilder {
+        TranscriberBuilder::default()
+    }
+
+    /// Path of the loaded model file
+    pub fn model_path(&self) -> &Path {
+        &self.model.path
+    }
+
+    /// Architecture of the loaded model
+    pub fn model_config(&self) -> &Config {
+        &self.model.config
+    }
+
+    /// Checkpoint format and weight precision of the loaded model
+    pub fn model_format(&self) -> &ModelFormat {
+        &self.model.format
+    }
+
+    /// Size of the loaded model's weights on disk
+    pub fn weights_bytes(&self) -> u64 {
+        self.model.weights_bytes
+    }
+
+    /// Decoding options used by [`Transcriber::transcribe`]
+    pub fn decoding_options(&self) -> &DecodingOptions {
+        &self.decoding
+    }
+
+    /// Transcribe audio, then label speakers if diarization is enabled
+    pub async fn transcribe(&self, source: AudioSource) -> Result<TranscriptionResult> {
+        self.transcribe_with_options(source, &self.decoding).await
+    }
+
+    /// Like [`Transcriber::transcribe`], but with per-call decoding options such as a prompt or language
+    pub async fn transcribe_with_options(
+        &self,
+        source: AudioSource,
+        options: &DecodingOptions,
+    ) -> Result<TranscriptionResult> {
+        let audio_data = load_source(source).await?;
+        self.transcribe_samples(&audio_data, options)
+    }
+
+    /// Decode 16kHz mono samples and label speakers. This is CPU-bound; async
+    /// callers should run it on a blocking thread.
+    pub fn transcribe_samples(&self, audio_data: &[f32], options: &DecodingOptions) -> Result<TranscriptionResult> {
+        let mut transcription = transcription::transcribe_samples(&self.model, audio_data, options)?;
+
+        if let Some(options) = &self.diarization {
+            info!("Identifying speakers...");
+            diarization::diarize(
+                audio_data,
+                &mut transcription.segments,
+                options.num_speakers,
+                &options.known_speakers,
+            );
+        }
+
+        Ok(transcription)
+    }
+}
+
+/// Read an audio source into 16kHz mono samples
+pub async fn load_source(source: AudioSource) -> Result<Vec<f32>> {
+    match source {
+        AudioSource::File(path) => {
+            info!("Extracting audio from {:?}...", path);
+            load_extracted(&audio::extract_audio(&path).await?).await
+        }
+        AudioSource::Url { url, network } => {
+            info!("Extracting audio from {}...", url);
+            load_extracted(&audio::extract_audio_from_url(&url, &network).await?).await
+        }
+        AudioSource::Wav(path) => transcription::load_audio_file(&path),
+        AudioSource::Samples(samples) => Ok(samples),
+    }
+}
+
+/// Read audio extracted by FFmpeg, then remove the temporary file
+async fn load_extracted(audio_path: &Path) -> Result<Vec<f32>> {
+    let audio_data = transcription::load_audio_file(audio_path);
+
+    // Cleanup temporary audio file
+    if let Err(e) = tokio::fs::remove_file(audio_path).await {
+        warn!("Could not clean up temporary audio file: {}", e);
+    }
+    audio_data
+}
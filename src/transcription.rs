@@ -1,49 +1,164 @@
-use anyhow::{Context, Result};
-use std::path::Path;
+use candle_core::{DType, Device};
+use candle_transformers::models::whisper::{model, quantized_model, Config};
+use candle_transformers::quantized_var_builder;
+use flate2::{write::ZlibEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use tracing::{info, debug};
+use tokenizers::Tokenizer;
+use crate::decoder::{self, SpecialTokens, WhisperWeights};
+use crate::error::{Result, TranscribeError};
+use crate::models::{self, ModelFormat};
+use crate::vad::{self, SpeechRegion};
+use crate::vocabulary::Vocabulary;
 
 /// Transcription result with text and timing information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TranscriptionSegment {
     pub start_time: f64,
     pub end_time: f64,
     pub text: String,
+    /// Speaker label assigned by diarization, e.g. "Speaker 1"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<String>,
+    /// Average log-probability of the decoded tokens, when the decoder reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_logprob: Option<f32>,
+    /// Probability that the window contained no speech, when the decoder reports it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_speech_prob: Option<f32>,
+    /// Overall confidence in the segment between 0 and 1, set by `validate_segments`
+    #[serde(default)]
+    pub confidence: f32,
+    /// Problems found by `validate_segments`
+    #[serde(default)]
+    pub flags: Vec<SegmentFlag>,
+    /// Word-level timings and scores from the decoder
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<WordTiming>,
 }
 
+/// A single decoded word with its timing and scores
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WordTiming {
+    pub start_time: f64,
+    pub end_time: f64,
+    pub word: String,
+    /// Average log-probability of the tokens that make up the word
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_logprob: Option<f32>,
+    /// No-speech probability of the decoding window the word belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_speech_prob: Option<f32>,
+}
+
+/// Reasons a segment is suspected to be a hallucination or otherwise unreliable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentFlag {
+    /// Text compresses too well, typical of decoder loops
+    HighCompressionRatio,
+    /// Average token log-probability is below the threshold
+    LowLogProb,
+    /// The decoder itself believes the window contains no speech
+    NoSpeech,
+    /// Repeated n-grams within the segment, or the same text as the previous segment
+    Repetition,
+    /// VAD found (almost) no speech energy under the segment
+    SilentAudio,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TranscriptionResult {
     pub segments: Vec<TranscriptionSegment>,
     pub full_text: String,
+    /// Spoken language as an ISO-639-1 code, as requested or detected
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
 }
 
-/// Available Whisper model sizes
-#[derive(Debug, Clone)]
+/// Options that steer the Whisper decoder towards the expected vocabulary
+#[derive(Debug, Clone, Default)]
+pub struct DecodingOptions {
+    /// Text fed to the decoder as preceding context of every window; the
+    /// vocabulary's glossary is used when this is `None`
+    pub initial_prompt: Option<String>,
+    /// Added to the logits of the tokens that spell out the vocabulary terms
+    pub logit_bias: f32,
+    /// Glossary terms and the post-decode replacement dictionary
+    pub vocabulary: Vocabulary,
+    /// Remove segments that `validate_segments` judges to be hallucinations
+    pub drop_hallucinations: bool,
+    /// Spoken language as an ISO-639-1 code, or `None` to detect it
+    pub language: Option<String>,
+    /// Translate the speech into English instead of transcribing it
+    pub translate: bool,
+    /// Sampling temperature; 0.0 decodes greedily
+    pub temperature: f32,
+}
+
+/// Known Whisper models, usable as `--model` shortcuts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum WhisperModel {
     Tiny,
     Base,
     Small,
     Medium,
+    #[value(alias = "large-v3")]
     Large,
+    #[value(name = "large-v3-turbo", alias = "turbo")]
+    LargeV3Turbo,
+    #[value(name = "distil-large-v3")]
+    DistilLargeV3,
 }
 
-impl WhisperModel {
-    pub fn from_str(s: &str) -> Result<Self> {
+impl FromStr for WhisperModel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
         match s.to_lowercase().as_str() {
             "tiny" => Ok(Self::Tiny),
             "base" => Ok(Self::Base),
             "small" => Ok(Self::Small),
             "medium" => Ok(Self::Medium),
-            "large" => Ok(Self::Large),
-            _ => anyhow::bail!("Unsupported model size: {}. Use: tiny, base, small, medium, large", s),
+            "large" | "large-v3" => Ok(Self::Large),
+            "large-v3-turbo" | "turbo" => Ok(Self::LargeV3Turbo),
+            "distil-large-v3" => Ok(Self::DistilLargeV3),
+            _ => anyhow::bail!(
+                "Unsupported model: {}. Use: tiny, base, small, medium, large, large-v3-turbo, distil-large-v3",
+                s
+            ),
         }
     }
+}
 
+impl WhisperModel {
+    /// Name of the model in the [`ModelStore`](crate::models::ModelStore)
     pub fn model_name(&self) -> &'static str {
         match self {
-            Self::Tiny => "models/ggml-tiny.bin",// the only real model imported
+            Self::Tiny => "tiny",
+            Self::Base => "base",
+            Self::Small => "small",
+            Self::Medium => "medium",
+            Self::Large => "large",
+            Self::LargeV3Turbo => "large-v3-turbo",
+            Self::DistilLargeV3 => "distil-large-v3",
+        }
+    }
+
+    /// Hugging Face repository holding the Transformers checkpoint
+    pub fn hub_repo(&self) -> &'static str {
+        match self {
+            Self::Tiny => "openai/whisper-tiny",
             Self::Base => "openai/whisper-base",
             Self::Small => "openai/whisper-small",
             Self::Medium => "openai/whisper-medium",
             Self::Large => "openai/whisper-large-v3",
+            Self::LargeV3Turbo => "openai/whisper-large-v3-turbo",
+            Self::DistilLargeV3 => "distil-whisper/distil-large-v3",
         }
     }
 
@@ -53,30 +168,303 @@ impl WhisperModel {
             Self::Base => "Base (good balance of speed/accuracy)",
             Self::Small => "Small (better accuracy, slower)",
             Self::Medium => "Medium (high accuracy, slower)",
-            Self::Large => "Large (highest accuracy, slowest)",
+            Self::Large => "Large v3 (highest accuracy, slowest)",
+            Self::LargeV3Turbo => "Large v3 turbo (near-large accuracy, 4 decoder layers)",
+            Self::DistilLargeV3 => "Distil large v3 (English, 2 decoder layers)",
+        }
+    }
+
+    /// Architecture of the published checkpoint, used when a model file does
+    /// not describe its own
+    pub fn config(&self) -> Config {
+        // (mel bins, d_model, attention heads, encoder layers, decoder layers, vocabulary)
+        let (num_mel_bins, d_model, heads, encoder_layers, decoder_layers, vocab_size) = match self {
+            Self::Tiny => (80, 384, 6, 4, 4, 51865),
+            Self::Base => (80, 512, 8, 6, 6, 51865),
+            Self::Small => (80, 768, 12, 12, 12, 51865),
+            Self::Medium => (80, 1024, 16, 24, 24, 51865),
+            Self::Large => (128, 1280, 20, 32, 32, 51866),
+            Self::LargeV3Turbo => (128, 1280, 20, 32, 4, 51866),
+            Self::DistilLargeV3 => (128, 1280, 20, 32, 2, 51866),
+        };
+        Config {
+            num_mel_bins,
+            max_source_positions: 1500,
+            d_model,
+            encoder_attention_heads: heads,
+            encoder_layers,
+            decoder_attention_heads: heads,
+            decoder_layers,
+            vocab_size,
+            max_target_positions: 448,
+            suppress_tokens: vec![],
         }
     }
 }
 
+/// The checkpoint to transcribe with, as given to `--model`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModelSpec {
+    /// A known model, stored under its [`WhisperModel::model_name`]
+    Known(WhisperModel),
+    /// Any other model in the [`ModelStore`](crate::models::ModelStore), e.g. an imported fine-tune
+    Stored(String),
+    /// A checkpoint file, or a directory with `config.json` and `model.safetensors`
+    Path(PathBuf),
+    /// A Hugging Face repository with a Transformers checkpoint, e.g. `openai/whisper-large-v3-turbo`
+    Repo(String),
+}
 
-/// Transcribes audio using OpenAI Whisper model via Candle
-pub async fn transcribe_audio(audio_path: &Path, model_size: &str) -> Result<TranscriptionResult> {
-    let model = WhisperModel::from_str(model_size)
-        .context("Invalid model size specified")?;
-    
-    info!("🤖 Using Whisper model: {} ({})", model.model_name(), model.description());
-    
-    // Load and analyze audio for basic transcription info
-    info!("🎵 Loading audio file...");
-    let audio_data = load_audio_file(audio_path).context("Failed to load audio file")?;
-    let duration = estimate_audio_duration(&audio_data, 16000.0); // Assuming 16kHz
-    
-    info!("🎯 Performing basic transcription (simplified version)...");
-    info!("Audio duration: {:.2} seconds", duration);
-    info!("Audio samples: {}", audio_data.len());
+impl From<WhisperModel> for ModelSpec {
+    fn from(model: WhisperModel) -> Self {
+        Self::Known(model)
+    }
+}
+
+impl FromStr for ModelSpec {
+    type Err = anyhow::Error;
+
+    /// Known aliases first, then existing paths, then `owner/name` repositories;
+    /// anything else is a store name
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s.is_empty() {
+            anyhow::bail!("Model must not be empty");
+        }
+        if let Ok(model) = s.parse() {
+            return Ok(Self::Known(model));
+        }
+        let path = Path::new(s);
+        if path.exists() {
+            return Ok(Self::Path(path.to_path_buf()));
+        }
+        if is_repo_id(s) {
+            return Ok(Self::Repo(s.to_string()));
+        }
+        let checkpoint_extension = path
+            .extension()
+            .is_some_and(|ext| ["bin", "gguf", "safetensors"].contains(&ext.to_string_lossy().as_ref()));
+        if s.contains(std::path::is_separator) || checkpoint_extension {
+            return Ok(Self::Path(path.to_path_buf()));
+        }
+        Ok(Self::Stored(s.to_string()))
+    }
+}
+
+impl std::fmt::Display for ModelSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Known(model) => write!(f, "{} ({})", model.model_name(), model.description()),
+            Self::Stored(name) => write!(f, "{}", name),
+            Self::Path(path) => write!(f, "{}", path.display()),
+            Self::Repo(repo) => write!(f, "{}", repo),
+        }
+    }
+}
+
+impl ModelSpec {
+    /// Name the model has, or would have, in the [`ModelStore`](crate::models::ModelStore)
+    pub fn store_name(&self) -> Option<&str> {
+        match self {
+            Self::Known(model) => Some(model.model_name()),
+            Self::Stored(name) => Some(name),
+            Self::Path(_) => None,
+            // `openai/whisper-large-v3-turbo` → `whisper-large-v3-turbo`
+            Self::Repo(repo) => repo.rsplit('/').next(),
+        }
+    }
+}
+
+/// `owner/name` with Hugging Face's allowed characters
+fn is_repo_id(s: &str) -> bool {
+    let Some((owner, name)) = s.split_once('/') else {
+        return false;
+    };
+    let valid = |part: &str| {
+        !part.is_empty()
+            && !part.starts_with(['.', '-'])
+            && part.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+    };
+    valid(owner) && valid(name)
+}
+
+/// A Whisper model that has been loaded onto its device for decoding
+#[derive(Debug)]
+pub struct LoadedModel {
+    pub path: PathBuf,
+    pub config: Config,
+    pub device: Device,
+    pub format: ModelFormat,
+    /// Size of the weights on disk
+    pub weights_bytes: u64,
+    pub weights: WhisperWeights,
+    pub tokenizer: Tokenizer,
+    pub special_tokens: SpecialTokens,
+}
+
+/// How fast one transcription ran and how much memory it took
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Performance {
+    pub audio_seconds: f64,
+    pub processing_seconds: f64,
+    pub weights_bytes: u64,
+    /// Peak resident memory of the process, where the OS reports it
+    pub peak_memory_bytes: Option<u64>,
+}
+
+impl Performance {
+    /// Measures a transcription of `audio_seconds` that started at `started`
+    pub fn measure(audio_seconds: f64, started: std::time::Instant, weights_bytes: u64) -> Self {
+        Self {
+            audio_seconds,
+            processing_seconds: started.elapsed().as_secs_f64(),
+            weights_bytes,
+            peak_memory_bytes: peak_memory_bytes(),
+        }
+    }
+
+    /// Seconds of audio transcribed per second of processing
+    pub fn real_time_factor(&self) -> f64 {
+        self.audio_seconds / self.processing_seconds.max(f64::EPSILON)
+    }
+}
+
+impl std::fmt::Display for Performance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.1}s of audio in {:.2}s ({:.1}x real time), weights {:.1} MB",
+            self.audio_seconds,
+            self.processing_seconds,
+            self.real_time_factor(),
+            self.weights_bytes as f64 / 1_000_000.0
+        )?;
+        if let Some(peak) = self.peak_memory_bytes {
+            write!(f, ", peak memory {:.1} MB", peak as f64 / 1_000_000.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// High-water mark of resident memory (`VmHWM`), on Linux
+fn peak_memory_bytes() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kilobytes: u64 = line.trim_start_matches("VmHWM:").trim().trim_end_matches("kB").trim().parse().ok()?;
+    Some(kilobytes * 1024)
+}
+
+/// Load the Whisper checkpoint at `model_path` (safetensors or GGUF, with its
+/// `tokenizer.json`) onto `device`. The architecture is read from the
+/// checkpoint, falling back to `fallback`'s published one for files that carry none.
+pub fn load_model(model_path: &Path, fallback: Option<WhisperModel>, device: Device) -> Result<LoadedModel> {
+    info!("🔄 Loading Whisper model from: {:?}", model_path);
+
+    // Check if model file exists
+    if fs::metadata(model_path).is_err() {
+        return Err(TranscribeError::ModelNotFound(model_path.to_path_buf()));
+    }
+
+    let config = match models::read_config(model_path)? {
+        Some(config) => config,
+        None => match fallback {
+            Some(model) => model.config(),
+            None => {
+                return Err(TranscribeError::UnsupportedFormat(format!(
+                    "{:?} has no GGML header or config.json describing its architecture",
+                    model_path
+                )))
+            }
+        },
+    };
+    if !matches!(config.num_mel_bins, 80 | 128) {
+        return Err(TranscribeError::UnsupportedFormat(format!(
+            "{} mel bins (Whisper uses 80, or 128 from large-v3)",
+            config.num_mel_bins
+        )));
+    }
+
+    info!(
+        "⚙️  Whisper architecture: {} mel bins, d_model {}, {} encoder / {} decoder layers",
+        config.num_mel_bins, config.d_model, config.encoder_layers, config.decoder_layers
+    );
+
+    let weights = if model_path.is_dir() { model_path.join(models::WEIGHTS_FILE) } else { model_path.to_path_buf() };
+    let format = ModelFormat::detect(&weights).unwrap_or(ModelFormat::Unknown);
+    let weights_bytes = fs::metadata(&weights).map(|metadata| metadata.len()).unwrap_or(0);
+    let invalid = |e: candle_core::Error| {
+        TranscribeError::UnsupportedFormat(format!("{:?} is not a Whisper checkpoint: {}", weights, e))
+    };
+    let whisper_weights = match format {
+        ModelFormat::Safetensors { .. } => {
+            // SAFETY: the file is not modified while the model is loaded
+            let vb = unsafe { candle_nn::VarBuilder::from_mmaped_safetensors(&[&weights], DType::F32, &device) }
+                .map_err(invalid)?;
+            WhisperWeights::Full(model::Whisper::load(&vb, config.clone()).map_err(invalid)?)
+        }
+        ModelFormat::Gguf { quantization } => {
+            info!("🗜️  Loading {} quantized weights...", quantization);
+            let vb = quantized_var_builder::VarBuilder::from_gguf(&weights, &device).map_err(invalid)?;
+            WhisperWeights::Quantized(quantized_model::Whisper::load(&vb, config.clone()).map_err(invalid)?)
+        }
+        ModelFormat::Ggml { .. } => {
+            return Err(TranscribeError::UnsupportedFormat(format!(
+                "{:?} is a whisper.cpp GGML file, which Candle cannot run; use a safetensors checkpoint \
+                 (`models download`) or a GGUF one (`models quantize`)",
+                weights
+            )))
+        }
+        ModelFormat::Unknown => {
+            return Err(TranscribeError::UnsupportedFormat(format!(
+                "{:?} is not a safetensors or GGUF checkpoint",
+                weights
+            )))
+        }
+    };
+    let tokenizer = decoder::load_tokenizer(model_path)?;
+    let special_tokens = SpecialTokens::from_tokenizer(&tokenizer)?;
+    info!("💾 {} weights: {:.1} MB", format, weights_bytes as f64 / 1_000_000.0);
+
+    Ok(LoadedModel {
+        path: model_path.to_path_buf(),
+        config,
+        device,
+        format,
+        weights_bytes,
+        weights: whisper_weights,
+        tokenizer,
+        special_tokens,
+    })
+}
+
+/// Transcribes 16kHz mono samples with a loaded Whisper model
+pub fn transcribe_samples(
+    model: &LoadedModel,
+    audio_data: &[f32],
+    options: &DecodingOptions,
+) -> Result<TranscriptionResult> {
+    let started = std::time::Instant::now();
+    let duration = estimate_audio_duration(audio_data, 16000.0); // Assuming 16kHz
     
-    // Create realistic segments based on audio length
-    let segments = transcribe_with_whisper(&audio_data, model.model_name())?;
+    info!("🎯 Transcribing {:.2} seconds of audio...", duration);
+
+    let speech_regions = vad::regions_to_decode(audio_data);
+    let (mut segments, language) = transcribe_with_whisper(model, audio_data, &speech_regions, options)?;
+
+    // Fix known misspellings from the custom vocabulary
+    for segment in &mut segments {
+        segment.text = options.vocabulary.apply(&segment.text);
+        for word in &mut segment.words {
+            word.word = options.vocabulary.apply(&word.word);
+        }
+    }
+
+    // Flag (and optionally drop) repetition loops and text invented over silence
+    validate_segments(&mut segments, &speech_regions);
+    if options.drop_hallucinations {
+        let before = segments.len();
+        segments.retain(|segment| !is_likely_hallucination(&segment.flags));
+        info!("🧹 Dropped {} likely hallucinated segments", before - segments.len());
+    }
 
     // Combine segments into full text
     let full_text = segments.iter()
@@ -85,17 +473,21 @@ pub async fn transcribe_audio(audio_path: &Path, model_size: &str) -> Result<Tra
         .join(" ");
 
     info!("✅ Transcription completed! Generated {} segments", segments.len());
+    info!("⏱️  {}", Performance::measure(duration as f64, started, model.weights_bytes));
 
     Ok(TranscriptionResult {
         segments,
         full_text,
+        language,
     })
 }
 
 /// Load audio file and convert to format expected by Whisper
-fn load_audio_file(path: &Path) -> Result<Vec<f32>> {
-    let mut reader = hound::WavReader::open(path)
-        .context("Failed to open WAV file")?;
+pub fn load_audio_file(path: &Path) -> Result<Vec<f32>> {
+    if !path.exists() {
+        return Err(TranscribeError::MissingInput(path.to_path_buf()));
+    }
+    let mut reader = hound::WavReader::open(path)?;
     
     let spec = reader.spec();
     debug!("WAV spec: {} channels, {} Hz, {} bits", spec.channels, spec.sample_rate, spec.bits_per_sample);
@@ -104,30 +496,31 @@ fn load_audio_file(path: &Path) -> Result<Vec<f32>> {
     let float_samples: Vec<f32> = match spec.sample_format {
         hound::SampleFormat::Float => {
             reader.samples::<f32>()
-                .collect::<Result<Vec<_>, _>>()
-                .context("Failed to read float samples")?
+                .collect::<Result<Vec<_>, _>>()?
         }
         hound::SampleFormat::Int => {
             match spec.bits_per_sample {
                 16 => {
                     reader.samples::<i16>()
                         .map(|sample| sample.map(|s| s as f32 / 32768.0))
-                        .collect::<Result<Vec<_>, _>>()
-                        .context("Failed to read 16-bit samples")?
+                        .collect::<Result<Vec<_>, _>>()?
                 }
                 24 => {
                     reader.samples::<i32>()
                         .map(|sample| sample.map(|s| s as f32 / 8388608.0))
-                        .collect::<Result<Vec<_>, _>>()
-                        .context("Failed to read 24-bit samples")?
+                        .collect::<Result<Vec<_>, _>>()?
                 }
                 32 => {
                     reader.samples::<i32>()
                         .map(|sample| sample.map(|s| s as f32 / 2147483648.0))
-                        .collect::<Result<Vec<_>, _>>()
-                        .context("Failed to read 32-bit samples")?
+                        .collect::<Result<Vec<_>, _>>()?
+                }
+                _ => {
+                    return Err(TranscribeError::UnsupportedFormat(format!(
+                        "{}-bit integer WAV",
+                        spec.bits_per_sample
+                    )))
                 }
-                _ => anyhow::bail!("Unsupported bit depth: {} bits", spec.bits_per_sample),
             }
         }
     };
@@ -141,162 +534,258 @@ fn estimate_audio_duration(samples: &[f32], sample_rate: f32) -> f32 {
     samples.len() as f32 / sample_rate
 }
 
-/// Create test transcription segments based on audio duration
-fn create_test_segments(duration: f32) -> Vec<TranscriptionSegment> {
-    // Create realistic segments for our test audio
-    if duration > 10.0 {
-        vec![
-            TranscriptionSegment {
-                start_time: 0.0,
-                end_time: 4.5,
-                text: "Hello everyone, this is a test of the Wayne Dyer video transcription tool.".to_string(),
-            },
-            TranscriptionSegment {
-                start_time: 4.5,
-                end_time: 8.8,
-                text: "Today we will explore the power of intention and how our thoughts create our reality.".to_string(),
-            },
-            TranscriptionSegment {
-                start_time: 8.8,
-                end_time: duration as f64,
-                text: "Remember, when you change the way you look at things, the things you look at change.".to_string(),
-            },
-        ]
-    } else {
-        vec![
-            TranscriptionSegment {
-                start_time: 0.0,
-                end_time: duration as f64,
-                text: "Short audio test - this is a placeholder transcription.".to_string(),
+/// Thresholds used by `validate_segments`, matching Whisper's own defaults
+const COMPRESSION_RATIO_THRESHOLD: f32 = 2.4;
+pub(crate) const LOGPROB_THRESHOLD: f32 = -1.0;
+const NO_SPEECH_THRESHOLD: f32 = 0.6;
+const MIN_SPEECH_RATIO: f64 = 0.1;
+
+/// Checks every segment for signs of hallucination and assigns `flags` and `confidence`.
+///
+/// Confidence starts from the token probability (`exp(avg_logprob)`) when the
+/// decoder reports it, is scaled by the probability of speech and by how much
+/// of the segment VAD considers speech, and is halved for every repetition or
+/// compression flag.
+pub fn validate_segments(segments: &mut [TranscriptionSegment], speech_regions: &[SpeechRegion]) {
+    let mut previous_text: Option<String> = None;
+
+    for segment in segments.iter_mut() {
+        let mut flags = Vec::new();
+        let normalized = normalize_for_comparison(&segment.text);
+
+        if compression_ratio(&segment.text) > COMPRESSION_RATIO_THRESHOLD {
+            flags.push(SegmentFlag::HighCompressionRatio);
+        }
+        if segment.avg_logprob.is_some_and(|logprob| logprob < LOGPROB_THRESHOLD) {
+            flags.push(SegmentFlag::LowLogProb);
+        }
+        if segment.no_speech_prob.is_some_and(|prob| prob > NO_SPEECH_THRESHOLD) {
+            flags.push(SegmentFlag::NoSpeech);
+        }
+        if has_repeated_ngrams(&normalized) || previous_text.as_deref() == Some(normalized.as_str()) {
+            flags.push(SegmentFlag::Repetition);
+        }
+
+        let speech_ratio = vad::speech_ratio(speech_regions, segment.start_time, segment.end_time);
+        if speech_ratio < MIN_SPEECH_RATIO {
+            flags.push(SegmentFlag::SilentAudio);
+        }
+
+        let mut confidence = segment.avg_logprob.map_or(1.0, f32::exp)
+            * (1.0 - segment.no_speech_prob.unwrap_or(0.0))
+            * (speech_ratio as f32).min(1.0);
+        for flag in &flags {
+            if matches!(flag, SegmentFlag::Repetition | SegmentFlag::HighCompressionRatio) {
+                confidence *= 0.5;
             }
-        ]
+        }
+
+        if !flags.is_empty() {
+            debug!("⚠️  Segment at {:.1}s flagged: {:?}", segment.start_time, flags);
+        }
+        segment.confidence = confidence.clamp(0.0, 1.0);
+        segment.flags = flags;
+        previous_text = Some(normalized);
     }
 }
 
-/// Create more intelligent segments by analyzing the audio data
-fn create_intelligent_segments(audio_data: &[f32], duration: f32) -> Vec<TranscriptionSegment> {
-    // Analyze audio for speech patterns and create more realistic segments
-    let mut segments = Vec::new();
-    
-    info!("🔍 Analyzing audio: {} samples, {} seconds", audio_data.len(), duration);
-    
-    // Calculate RMS (volume) over time to detect speech segments
-    let chunk_size = (16000.0 * 2.0) as usize; // 2-second chunks
-    let mut current_time = 0.0;
-    let chunk_duration = 2.0;
-    
-    // Calculate overall audio statistics
-    let max_amplitude = audio_data.iter().map(|&x| x.abs()).fold(0.0f32, f32::max);
-    let avg_rms: f32 = audio_data.iter().map(|&x| x * x).sum::<f32>().sqrt() / audio_data.len() as f32;
-    info!("📊 Audio stats - Max amplitude: {}, Avg RMS: {}", max_amplitude, avg_rms);
-    
-    for chunk in audio_data.chunks(chunk_size) {
-        let end_time = ((current_time + chunk_duration) as f64).min(duration as f64);
-        
-        // Calculate RMS to detect speech presence
-        let rms: f32 = chunk.iter().map(|&x| x * x).sum::<f32>().sqrt() / chunk.len() as f32;
-        debug!("🔊 Chunk at {:.1}s: RMS = {:.6}", current_time, rms);
-        
-        // Generate segments for any detected audio (very permissive)
-        if rms > 0.000001 || chunk.len() > 0 { // Always generate if we have audio data
-            info!("✅ Creating segment at {:.1}s with RMS {:.6}", current_time, rms);
-            
-            // Create segment based on chunk position and characteristics
-            let segment_text = if current_time < 60.0 {
-                "Welcome, I'm Wayne Dyer. Today we're exploring the profound power of intention and how our thoughts shape our reality.".to_string()
-            } else if current_time < 120.0 {
-                "When you change the way you look at things, the things you look at change. This is not just a philosophy, it's a practical truth.".to_string()
-            } else if current_time < 240.0 {
-                "Your intentions create your reality. Every thought you have is contributing to what shows up in your life.".to_string()
-            } else if current_time < 360.0 {
-                "We are not human beings having a spiritual experience. We are spiritual beings having a human experience.".to_string()
-            } else if current_time < 480.0 {
-                "The highest form of ignorance is rejecting something you know nothing about. Open your mind to infinite possibilities.".to_string()
-            } else if current_time < 600.0 {
-                "Your purpose in life is to serve. When you serve from love, you connect with the power of intention.".to_string()
-            } else {
-                "Remember that you have the power within you to create the life you desire. Trust in the process and align with your highest self.".to_string()
-            };
-            
-            segments.push(TranscriptionSegment {
-                start_time: current_time,
-                end_time,
-                text: segment_text,
-            });
+/// Whether a segment's flags indicate text that was not actually spoken.
+/// Low log-probability alone only means a hard passage, so it is kept unless
+/// the decoder also reported no speech.
+pub fn is_likely_hallucination(flags: &[SegmentFlag]) -> bool {
+    flags.iter().any(|flag| {
+        matches!(
+            flag,
+            SegmentFlag::Repetition | SegmentFlag::HighCompressionRatio | SegmentFlag::SilentAudio
+        )
+    }) || (flags.contains(&SegmentFlag::NoSpeech) && flags.contains(&SegmentFlag::LowLogProb))
+}
+
+/// Ratio of text length to its zlib-compressed length, as used by Whisper
+pub(crate) fn compression_ratio(text: &str) -> f32 {
+    if text.is_empty() {
+        return 0.0;
+    }
+
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let compressed = encoder
+        .write_all(text.as_bytes())
+        .and_then(|_| encoder.finish())
+        .map(|bytes| bytes.len())
+        .unwrap_or(text.len());
+    text.len() as f32 / compressed.max(1) as f32
+}
+
+/// Lowercase words without punctuation, for comparing segment texts
+fn normalize_for_comparison(text: &str) -> String {
+    text.split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Detects an n-gram repeated back to back: three times for phrases of two to
+/// four words, four times for single words
+fn has_repeated_ngrams(normalized: &str) -> bool {
+    let words: Vec<&str> = normalized.split(' ').filter(|w| !w.is_empty()).collect();
+
+    for n in 1..=4 {
+        let needed = if n == 1 { 4 } else { 3 };
+        if words.len() < n * needed {
+            continue;
         }
-        
-        current_time = end_time;
-        if current_time >= duration as f64 {
-            break;
+        for start in 0..=words.len() - n * needed {
+            let gram = &words[start..start + n];
+            if (1..needed).all(|repeat| &words[start + repeat * n..start + (repeat + 1) * n] == gram) {
+                return true;
+            }
         }
     }
-    
-    if segments.is_empty() {
-        // Fallback if no speech detected
-        segments.push(TranscriptionSegment {
-            start_time: 0.0,
-            end_time: duration as f64,
-            text: "Audio processed but no clear speech patterns detected.".to_string(),
-        });
-    }
-    
-    segments
+    false
 }
 
 /// Perform the actual transcription using the loaded Whisper model
 fn transcribe_with_whisper(
+    model: &LoadedModel,
     audio_data: &[f32],
-    model_path: &str,
-) -> Result<Vec<TranscriptionSegment>> {
-    use candle_core::{Device, Tensor};
-    use candle_transformers::models::whisper::Config;
-    use std::fs;
+    speech_regions: &[SpeechRegion],
+    options: &DecodingOptions,
+) -> Result<(Vec<TranscriptionSegment>, Option<String>)> {
+    debug!(
+        "Decoding with {:?} ({}, {} mel bins, d_model {}) on {:?}",
+        model.path, model.format, model.config.num_mel_bins, model.config.d_model, model.device
+    );
 
-    info!("🔄 Loading Whisper model from: {}", model_path);
-    
-    // Check if model file exists
-    if !fs::metadata(model_path).is_ok() {
-        anyhow::bail!("Model file not found: {}", model_path);
+    if let Some(prompt) = &options.initial_prompt {
+        info!("📝 Initial prompt: {}", prompt);
+    }
+    if options.logit_bias != 0.0 && !options.vocabulary.terms.is_empty() {
+        info!("🎚️  Biasing {} vocabulary terms by {:+.1}", options.vocabulary.terms.len(), options.logit_bias);
+    }
+    if let Some(language) = &options.language {
+        info!("🌐 Language: {}", language);
+    }
+    if options.translate {
+        info!("🔤 Translating to English");
+    }
+    if options.temperature > 0.0 {
+        debug!("Sampling at temperature {:.2}", options.temperature);
     }
 
-    // Setup device (CPU for now)
-    let device = Device::Cpu;
-    
-    // Load the GGML model - for now we'll try to load it as a safetensors model
-    // The GGML format requires special handling in Candle
-    info!("📁 Model file exists, attempting to load...");
-    
-    // Create Whisper config for tiny model
-    let config = Config {
-        num_mel_bins: 80,
-        max_source_positions: 1500,
-        d_model: 384,
-        encoder_attention_heads: 6,
-        encoder_layers: 4,
-        decoder_attention_heads: 6,
-        decoder_layers: 4,
-        vocab_size: 51865,
-        max_target_positions: 448,
-        suppress_tokens: vec![],
-    };
+    let (segments, language) = decoder::transcribe(model, audio_data, speech_regions, options)?;
+    info!("✅ Decoded {} segments", segments.len());
+    Ok((segments, language))
+}
 
-    info!("⚙️  Created Whisper tiny config");
-    
-    // For now, since GGML loading is complex, let's do a simplified transcription
-    // that at least processes the real audio data
-    info!("🎤 Processing {} audio samples for transcription...", audio_data.len());
-    
-    // Convert audio to the right format for Whisper (16kHz mono)
-    let audio_tensor = Tensor::from_slice(audio_data, (1, audio_data.len()), &device)
-        .context("Failed to create audio tensor")?;
-    
-    info!("📊 Audio tensor shape: {:?}", audio_tensor.dims());
-    
-    // Since we can't easily load GGML in current Candle, let's do intelligent chunking
-    // of the audio and create more realistic segments
-    let duration = audio_data.len() as f32 / 16000.0;
-    let segments = create_intelligent_segments(audio_data, duration);
-    
-    info!("✅ Generated {} intelligent transcription segments", segments.len());
-    Ok(segments)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(start_time: f64, end_time: f64, text: &str) -> TranscriptionSegment {
+        TranscriptionSegment {
+            start_time,
+            end_time,
+            text: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn speech(start_time: f64, end_time: f64) -> SpeechRegion {
+        SpeechRegion { start_time, end_time }
+    }
+
+    #[test]
+    fn test_repeated_ngrams() {
+        assert!(has_repeated_ngrams("thank you thank you thank you"));
+        assert!(has_repeated_ngrams("so so so so"));
+        assert!(!has_repeated_ngrams("so so so"));
+        assert!(!has_repeated_ngrams("when you change the way you look at things"));
+    }
+
+    #[test]
+    fn test_compression_ratio() {
+        assert!(compression_ratio(&"Thank you. ".repeat(20)) > COMPRESSION_RATIO_THRESHOLD);
+        assert!(compression_ratio("Your intentions create your reality.") < COMPRESSION_RATIO_THRESHOLD);
+    }
+
+    #[test]
+    fn test_validate_flags_loops_and_silence() {
+        let mut segments = vec![
+            segment(0.0, 2.0, "Welcome to the power of intention."),
+            segment(2.0, 4.0, "Thank you. Thank you. Thank you."),
+            segment(4.0, 6.0, "Thank you. Thank you. Thank you."),
+            segment(8.0, 10.0, "Music playing softly."),
+        ];
+        validate_segments(&mut segments, &[speech(0.0, 6.0)]);
+
+        assert!(segments[0].flags.is_empty());
+        assert_eq!(segments[0].confidence, 1.0);
+        assert_eq!(segments[1].flags, vec![SegmentFlag::Repetition]);
+        assert_eq!(segments[1].confidence, 0.5);
+        assert_eq!(segments[2].flags, vec![SegmentFlag::Repetition]);
+        assert_eq!(segments[3].flags, vec![SegmentFlag::SilentAudio]);
+        assert_eq!(segments[3].confidence, 0.0);
+    }
+
+    #[test]
+    fn test_validate_uses_decoder_probabilities() {
+        let mut segments = vec![TranscriptionSegment {
+            avg_logprob: Some(-1.5),
+            no_speech_prob: Some(0.8),
+            ..segment(0.0, 2.0, "Hmm.")
+        }];
+        validate_segments(&mut segments, &[speech(0.0, 2.0)]);
+
+        assert_eq!(segments[0].flags, vec![SegmentFlag::LowLogProb, SegmentFlag::NoSpeech]);
+        assert!(is_likely_hallucination(&segments[0].flags));
+        assert!(segments[0].confidence < 0.1);
+        assert!(!is_likely_hallucination(&[SegmentFlag::LowLogProb]));
+    }
+
+    #[test]
+    fn test_model_spec_parsing() {
+        assert_eq!("tiny".parse::<ModelSpec>().unwrap(), ModelSpec::Known(WhisperModel::Tiny));
+        assert_eq!("turbo".parse::<ModelSpec>().unwrap(), ModelSpec::Known(WhisperModel::LargeV3Turbo));
+        assert_eq!(
+            "distil-large-v3".parse::<ModelSpec>().unwrap(),
+            ModelSpec::Known(WhisperModel::DistilLargeV3)
+        );
+        let repo: ModelSpec = "openai/whisper-large-v3-turbo".parse().unwrap();
+        assert_eq!(repo, ModelSpec::Repo("openai/whisper-large-v3-turbo".into()));
+        assert_eq!(repo.store_name(), Some("whisper-large-v3-turbo"));
+        assert_eq!("my-finetune".parse::<ModelSpec>().unwrap(), ModelSpec::Stored("my-finetune".into()));
+        assert_eq!("missing.gguf".parse::<ModelSpec>().unwrap(), ModelSpec::Path("missing.gguf".into()));
+        assert_eq!("./models/custom".parse::<ModelSpec>().unwrap(), ModelSpec::Path("./models/custom".into()));
+        assert_eq!("src".parse::<ModelSpec>().unwrap(), ModelSpec::Path("src".into()));
+        assert!("".parse::<ModelSpec>().is_err());
+    }
+
+    #[test]
+    fn test_known_architectures() {
+        let tiny = WhisperModel::Tiny.config();
+        assert_eq!((tiny.num_mel_bins, tiny.encoder_layers, tiny.decoder_layers), (80, 4, 4));
+        let turbo = WhisperModel::LargeV3Turbo.config();
+        assert_eq!((turbo.num_mel_bins, turbo.encoder_layers, turbo.decoder_layers), (128, 32, 4));
+        let distil = WhisperModel::DistilLargeV3.config();
+        assert_eq!((distil.num_mel_bins, distil.decoder_layers, distil.vocab_size), (128, 2, 51866));
+    }
+
+    #[test]
+    fn test_performance_report() {
+        let performance = Performance {
+            audio_seconds: 60.0,
+            processing_seconds: 4.0,
+            weights_bytes: 539_000_000,
+            peak_memory_bytes: Some(702_000_000),
+        };
+
+        assert_eq!(performance.real_time_factor(), 15.0);
+        assert_eq!(
+            performance.to_string(),
+            "60.0s of audio in 4.00s (15.0x real time), weights 539.0 MB, peak memory 702.0 MB"
+        );
+        #[cfg(target_os = "linux")]
+        assert!(peak_memory_bytes().is_some_and(|bytes| bytes > 0));
+    }
 }
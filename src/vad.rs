@@ -0,0 +1,174 @@
+use tracing::debug;
+
+/// Sample rate of the audio produced by `audio::extract_audio`
+pub const SAMPLE_RATE: usize = 16000;
+
+/// Analysis frame length (25ms) and hop (10ms) in samples
+pub const FRAME_LENGTH: usize = 400;
+pub const FRAME_HOP: usize = 160;
+
+/// Gaps shorter than this are bridged, regions shorter than this are dropped (seconds)
+const MIN_SILENCE: f64 = 0.3;
+const MIN_SPEECH: f64 = 0.3;
+
+/// Share of the input above which speech regions are not worth skipping around
+const FULL_DECODE_RATIO: f64 = 0.8;
+
+/// Peak amplitude below which an input counts as silent (-60 dBFS)
+const SILENT_AMPLITUDE: f32 = 0.001;
+
+/// A contiguous stretch of detected speech, in seconds
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpeechRegion {
+    pub start_time: f64,
+    pub end_time: f64,
+}
+
+/// Energy-based voice activity detection with an adaptive threshold
+pub fn detect_speech_regions(audio_data: &[f32]) -> Vec<SpeechRegion> {
+    if audio_data.len() < FRAME_LENGTH {
+        return Vec::new();
+    }
+
+    let energies: Vec<f32> = audio_data
+        .windows(FRAME_LENGTH)
+        .step_by(FRAME_HOP)
+        .map(|frame| {
+            let power = frame.iter().map(|&x| x * x).sum::<f32>() / frame.len() as f32;
+            10.0 * (power + 1e-10).log10()
+        })
+        .collect();
+
+    let mut sorted = energies.clone();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let noise_floor = sorted[sorted.len() / 10];
+    let peak = sorted[sorted.len() * 9 / 10];
+    let threshold = (noise_floor + 0.3 * (peak - noise_floor)).max(-60.0);
+    debug!("VAD threshold {:.1} dB (floor {:.1} dB, peak {:.1} dB)", threshold, noise_floor, peak);
+
+    let frame_time = |index: usize| (index * FRAME_HOP) as f64 / SAMPLE_RATE as f64;
+    let mut regions: Vec<SpeechRegion> = Vec::new();
+    let mut start: Option<usize> = None;
+
+    for (index, &energy) in energies.iter().enumerate() {
+        match (energy > threshold, start) {
+            (true, None) => start = Some(index),
+            (false, Some(first)) => {
+                regions.push(SpeechRegion {
+                    start_time: frame_time(first),
+                    end_time: frame_time(index) + FRAME_LENGTH as f64 / SAMPLE_RATE as f64,
+                });
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(first) = start {
+        regions.push(SpeechRegion {
+            start_time: frame_time(first),
+            end_time: audio_data.len() as f64 / SAMPLE_RATE as f64,
+        });
+    }
+
+    // Bridge short pauses, then drop blips that are too short to be speech
+    let mut merged: Vec<SpeechRegion> = Vec::new();
+    for region in regions {
+        match merged.last_mut() {
+            Some(last) if region.start_time - last.end_time < MIN_SILENCE => last.end_time = region.end_time,
+            _ => merged.push(region),
+        }
+    }
+    merged.retain(|region| region.end_time - region.start_time >= MIN_SPEECH);
+    merged
+}
+
+/// The parts of `audio_data` the decoder should look at. The VAD threshold is
+/// relative to the input's own energy range, so audio without pauses can come
+/// out with no speech at all: unless the input is silent, it is decoded in
+/// full when VAD finds nothing or when speech covers most of it anyway, and
+/// Whisper's no-speech check is left to skip what is not speech.
+pub fn regions_to_decode(audio_data: &[f32]) -> Vec<SpeechRegion> {
+    let duration = audio_data.len() as f64 / SAMPLE_RATE as f64;
+    let regions = detect_speech_regions(audio_data);
+    let silent = audio_data.iter().all(|sample| sample.abs() < SILENT_AMPLITUDE);
+    if (regions.is_empty() && !silent) || speech_ratio(&regions, 0.0, duration) >= FULL_DECODE_RATIO {
+        debug!("Decoding all {:.1}s of audio ({} speech regions)", duration, regions.len());
+        return vec![SpeechRegion { start_time: 0.0, end_time: duration }];
+    }
+    regions
+}
+
+/// Fraction of `[start_time, end_time)` covered by speech regions
+pub fn speech_ratio(regions: &[SpeechRegion], start_time: f64, end_time: f64) -> f64 {
+    let length = end_time - start_time;
+    if length <= 0.0 {
+        return 0.0;
+    }
+
+    let covered: f64 = regions
+        .iter()
+        .map(|region| (end_time.min(region.end_time) - start_time.max(region.start_time)).max(0.0))
+        .sum();
+    (covered / length).min(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tone(frequency: f32, seconds: f32) -> Vec<f32> {
+        (0..(seconds * SAMPLE_RATE as f32) as usize)
+            .map(|n| 0.5 * (2.0 * std::f32::consts::PI * frequency * n as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    fn silence(seconds: f32) -> Vec<f32> {
+        vec![0.0; (seconds * SAMPLE_RATE as f32) as usize]
+    }
+
+    #[test]
+    fn test_detect_speech_regions() {
+        let regions = detect_speech_regions(&[tone(200.0, 2.0), silence(1.0), tone(3000.0, 2.0), silence(1.0), tone(200.0, 2.0)].concat());
+
+        assert_eq!(regions.len(), 3);
+        assert!(regions[0].start_time < 0.1);
+        assert!((regions[1].start_time - 3.0).abs() < 0.1);
+        assert!((regions[2].end_time - 8.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_silence_has_no_speech() {
+        assert!(detect_speech_regions(&silence(3.0)).is_empty());
+    }
+
+    #[test]
+    fn test_continuous_speech_is_decoded_in_full() {
+        // Steady speech without pauses: a flat tone, and a voice modulated at syllable rate
+        let flat = tone(200.0, 5.0);
+        let syllables: Vec<f32> = tone(200.0, 5.0)
+            .iter()
+            .enumerate()
+            .map(|(n, sample)| sample * (0.6 + 0.4 * (2.0 * std::f32::consts::PI * 4.0 * n as f32 / SAMPLE_RATE as f32).sin()))
+            .collect();
+
+        for audio in [flat, syllables] {
+            assert_eq!(regions_to_decode(&audio), vec![SpeechRegion { start_time: 0.0, end_time: 5.0 }]);
+        }
+    }
+
+    #[test]
+    fn test_pauses_are_still_skipped() {
+        let audio = [tone(200.0, 2.0), silence(6.0), tone(200.0, 2.0)].concat();
+        assert_eq!(regions_to_decode(&audio).len(), 2);
+        assert!(regions_to_decode(&silence(3.0)).is_empty());
+    }
+
+    #[test]
+    fn test_speech_ratio() {
+        let regions = vec![SpeechRegion { start_time: 1.0, end_time: 2.0 }];
+
+        assert_eq!(speech_ratio(&regions, 0.0, 2.0), 0.5);
+        assert_eq!(speech_ratio(&regions, 1.0, 2.0), 1.0);
+        assert_eq!(speech_ratio(&regions, 3.0, 4.0), 0.0);
+    }
+}
@@ -0,0 +1,163 @@
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+use tokio::fs;
+use tracing::debug;
+
+/// Whisper only attends to the last ~224 prompt tokens, so longer prompts are
+/// trimmed from the front to roughly that many characters
+const MAX_PROMPT_CHARS: usize = 800;
+
+/// Custom vocabulary loaded from a `--vocab` file.
+///
+/// Each non-empty line is either a term the decoder should prefer
+/// (`Tao Te Ching`) or a replacement for a known misspelling
+/// (`Wane Dire => Wayne Dyer`). Lines starting with `#` are comments.
+#[derive(Debug, Clone, Default)]
+pub struct Vocabulary {
+    pub terms: Vec<String>,
+    pub replacements: Vec<(String, String)>,
+}
+
+impl Vocabulary {
+    pub async fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .await
+            .context("Failed to read vocabulary file")?;
+        let vocabulary = Self::parse(&content);
+        debug!(
+            "Loaded {} vocabulary terms and {} replacements",
+            vocabulary.terms.len(),
+            vocabulary.replacements.len()
+        );
+        Ok(vocabulary)
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut vocabulary = Self::default();
+
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match line.split_once("=>") {
+                Some((from, to)) => {
+                    let (from, to) = (from.trim(), to.trim());
+                    if !from.is_empty() && !to.is_empty() {
+                        vocabulary.replacements.push((from.to_string(), to.to_string()));
+                        vocabulary.terms.push(to.to_string());
+                    }
+                }
+                None => vocabulary.terms.push(line.to_string()),
+            }
+        }
+
+        let mut seen = HashSet::new();
+        vocabulary.terms.retain(|term| seen.insert(term.to_lowercase()));
+        vocabulary
+    }
+
+    /// Builds the initial decoder prompt from the glossary and the user's prompt.
+    /// The glossary is trimmed from the front if the prompt grows too long.
+    pub fn initial_prompt(&self, prompt: Option<&str>) -> Option<String> {
+        let glossary = (!self.terms.is_empty()).then(|| format!("Glossary: {}.", self.terms.join(", ")));
+        let prompt = prompt.map(str::trim).filter(|p| !p.is_empty());
+
+        let combined = match (glossary, prompt) {
+            (Some(glossary), Some(prompt)) => format!("{} {}", glossary, prompt),
+            (Some(glossary), None) => glossary,
+            (None, Some(prompt)) => prompt.to_string(),
+            (None, None) => return None,
+        };
+
+        let excess = combined.chars().count().saturating_sub(MAX_PROMPT_CHARS);
+        Some(combined.chars().skip(excess).collect::<String>().trim_start().to_string())
+    }
+
+    /// Fixes known misspellings and restores the canonical casing of listed terms
+    pub fn apply(&self, text: &str) -> String {
+        let mut result = text.to_string();
+        for (from, to) in &self.replacements {
+            result = replace_phrase(&result, from, to);
+        }
+        for term in &self.terms {
+            result = replace_phrase(&result, term, term);
+        }
+        result
+    }
+}
+
+/// Replaces whole-word, ASCII case-insensitive occurrences of `from` with `to`
+fn replace_phrase(text: &str, from: &str, to: &str) -> String {
+    let (haystack, needle) = (text.as_bytes(), from.as_bytes());
+    if needle.is_empty() || haystack.len() < needle.len() {
+        return text.to_string();
+    }
+
+    let is_word = |byte: Option<&u8>| byte.is_some_and(|b| b.is_ascii_alphanumeric() || *b >= 0x80);
+    let mut result = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut index = 0;
+
+    while index + needle.len() <= haystack.len() {
+        let candidate = &haystack[index..index + needle.len()];
+        let bounded = !is_word(index.checked_sub(1).and_then(|i| haystack.get(i)))
+            && !is_word(haystack.get(index + needle.len()));
+
+        if bounded && text.is_char_boundary(index) && candidate.eq_ignore_ascii_case(needle) {
+            result.push_str(&text[copied..index]);
+            result.push_str(to);
+            index += needle.len();
+            copied = index;
+        } else {
+            index += 1;
+        }
+    }
+
+    result.push_str(&text[copied..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOCAB: &str = "# Names and titles\n\
+        Tao Te Ching\n\
+        \n\
+        Wane Dire => Wayne Dyer\n\
+        dharma\n";
+
+    #[test]
+    fn test_parse_vocabulary() {
+        let vocabulary = Vocabulary::parse(VOCAB);
+
+        assert_eq!(vocabulary.terms, vec!["Tao Te Ching", "Wayne Dyer", "dharma"]);
+        assert_eq!(vocabulary.replacements, vec![("Wane Dire".to_string(), "Wayne Dyer".to_string())]);
+    }
+
+    #[test]
+    fn test_apply_replacements_and_casing() {
+        let vocabulary = Vocabulary::parse(VOCAB);
+
+        assert_eq!(
+            vocabulary.apply("wane dire wrote about the tao te ching and Dharma."),
+            "Wayne Dyer wrote about the Tao Te Ching and dharma."
+        );
+        assert_eq!(vocabulary.apply("dharmas stay untouched"), "dharmas stay untouched");
+    }
+
+    #[test]
+    fn test_initial_prompt() {
+        let vocabulary = Vocabulary::parse(VOCAB);
+
+        assert_eq!(
+            vocabulary.initial_prompt(Some("A lecture on intention.")).unwrap(),
+            "Glossary: Tao Te Ching, Wayne Dyer, dharma. A lecture on intention."
+        );
+        assert_eq!(Vocabulary::default().initial_prompt(Some("  ")), None);
+
+        let long_prompt = "word ".repeat(400);
+        assert!(vocabulary.initial_prompt(Some(&long_prompt)).unwrap().chars().count() <= MAX_PROMPT_CHARS);
+    }
+}
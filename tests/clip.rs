@@ -0,0 +1,62 @@
+use std::path::Path;
+use wayne_transcriber::clip::{self, ClipOptions};
+use wayne_transcriber::{TranscribeError, TranscriptionResult, TranscriptionSegment};
+
+const TEST_WAV: &str = "test_files/test_speech.wav";
+
+fn segment(start_time: f64, end_time: f64, text: &str) -> TranscriptionSegment {
+    TranscriptionSegment {
+        start_time,
+        end_time,
+        text: text.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Needs FFmpeg on PATH and is skipped without it
+#[tokio::test]
+async fn test_clip_wav_with_subtitles() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_path = dir.path().join("quote.wav");
+    let transcription = TranscriptionResult {
+        segments: vec![
+            segment(0.0, 4.0, "Welcome."),
+            segment(4.0, 8.0, "The power of intention."),
+            segment(8.0, 12.0, "Thank you."),
+        ],
+        full_text: "Welcome. The power of intention. Thank you.".to_string(),
+        language: None,
+    };
+    let options = ClipOptions { padding: 0.5, reencode: true };
+
+    let result = clip::extract_clip(Path::new(TEST_WAV), &output_path, 4.0, 8.0, Some(&transcription), &options).await;
+    if matches!(result, Err(TranscribeError::FfmpegNotInstalled)) {
+        eprintln!("FFmpeg is not installed; skipping");
+        return;
+    }
+
+    let srt_path = result.unwrap().unwrap();
+    assert_eq!(srt_path, dir.path().join("quote.srt"));
+    let srt = std::fs::read_to_string(srt_path).unwrap();
+    assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:00,500\nWelcome."));
+    assert!(srt.contains("00:00:00,500 --> 00:00:04,500\nThe power of intention."));
+
+    let reader = hound::WavReader::open(&output_path).unwrap();
+    let seconds = reader.duration() as f64 / reader.spec().sample_rate as f64;
+    assert!((seconds - 5.0).abs() < 0.1);
+}
+
+#[tokio::test]
+async fn test_clip_missing_media() {
+    let result = clip::extract_clip(
+        Path::new("test_files/missing.mp4"),
+        Path::new("clip.mp4"),
+        1.0,
+        2.0,
+        None,
+        &ClipOptions::default(),
+    )
+    .await;
+
+    assert!(matches!(result, Err(TranscribeError::MissingInput(_))));
+}
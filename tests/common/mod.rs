@@ -0,0 +1,97 @@
+//! A deterministic tiny Whisper checkpoint, so that the integration tests run
+//! the real decoder without downloading a model
+
+use candle_core::{DType, Device, Tensor};
+use candle_transformers::models::whisper::{model::Whisper, Config};
+use std::path::{Path, PathBuf};
+
+/// The words of test_speech.srt, which make up the checkpoint's vocabulary
+const WORDS: [&str; 33] = [
+    "hello", "everyone", "this", "is", "a", "test", "of", "the", "wayne", "dyer", "video", "transcription", "tool",
+    "today", "we", "will", "explore", "power", "intention", "and", "how", "our", "thoughts", "create", "reality",
+    "remember", "when", "you", "change", "way", "look", "at", "things",
+];
+/// Whisper's control tokens, in their order in the real vocabulary
+const SPECIAL_TOKENS: [&str; 9] = [
+    "<|endoftext|>",
+    "<|startoftranscript|>",
+    "<|en|>",
+    "<|translate|>",
+    "<|transcribe|>",
+    "<|startoflm|>",
+    "<|startofprev|>",
+    "<|nospeech|>",
+    "<|notimestamps|>",
+];
+/// Enough timestamp tokens to span the 3s window
+const TIMESTAMPS: usize = 151;
+
+/// Writes `model.safetensors`, `config.json` and `tokenizer.json` for a
+/// one-layer Whisper with fixed pseudo-random weights into `dir`. Its 3s
+/// windows keep decoding fast; `<|endoftext|>` is suppressed, so every
+/// window yields text.
+pub fn tiny_checkpoint(dir: &Path, num_mel_bins: usize) -> PathBuf {
+    std::fs::create_dir_all(dir).unwrap();
+    let eot = WORDS.len();
+    let config = serde_json::json!({
+        "num_mel_bins": num_mel_bins,
+        "max_source_positions": 150,
+        "d_model": 64,
+        "encoder_attention_heads": 2,
+        "encoder_layers": 1,
+        "vocab_size": WORDS.len() + SPECIAL_TOKENS.len() + TIMESTAMPS,
+        "max_target_positions": 32,
+        "decoder_attention_heads": 2,
+        "decoder_layers": 1,
+        "suppress_tokens": [eot],
+    });
+    std::fs::write(dir.join("config.json"), config.to_string()).unwrap();
+    let config: Config = serde_json::from_value(config).unwrap();
+
+    let varmap = candle_nn::VarMap::new();
+    let vb = candle_nn::VarBuilder::from_varmap(&varmap, DType::F32, &Device::Cpu);
+    Whisper::load(&vb, config).unwrap();
+    for (name, var) in varmap.data().lock().unwrap().iter() {
+        let mut state = name.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+        });
+        let values: Vec<f32> = (0..var.elem_count())
+            .map(|_| {
+                state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+                ((state >> 40) as f32 / (1u64 << 24) as f32 - 0.5) * 0.5
+            })
+            .collect();
+        var.set(&Tensor::from_vec(values, var.shape(), &Device::Cpu).unwrap()).unwrap();
+    }
+    varmap.save(dir.join("model.safetensors")).unwrap();
+
+    let vocab: serde_json::Map<String, serde_json::Value> = WORDS
+        .iter()
+        .chain(&SPECIAL_TOKENS)
+        .enumerate()
+        .map(|(id, token)| (token.to_string(), id.into()))
+        .collect();
+    let added_tokens: Vec<_> = SPECIAL_TOKENS
+        .iter()
+        .enumerate()
+        .map(|(offset, token)| {
+            serde_json::json!({
+                "id": eot + offset, "content": token, "single_word": false, "lstrip": false,
+                "rstrip": false, "normalized": false, "special": true,
+            })
+        })
+        .collect();
+    let tokenizer = serde_json::json!({
+        "version": "1.0",
+        "truncation": null,
+        "padding": null,
+        "added_tokens": added_tokens,
+        "normalizer": {"type": "Lowercase"},
+        "pre_tokenizer": {"type": "Whitespace"},
+        "post_processor": null,
+        "decoder": null,
+        "model": {"type": "WordLevel", "vocab": vocab, "unk_token": "<|endoftext|>"},
+    });
+    std::fs::write(dir.join("tokenizer.json"), tokenizer.to_string()).unwrap();
+    dir.to_path_buf()
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use std::path::Path;
+use wayne_transcriber::eval::{self, NormalizeOptions};
+use wayne_transcriber::output;
+use wayne_transcriber::{AudioSource, Transcriber};
+
+const TEST_WAV: &str = "test_files/test_speech.wav";
+const REFERENCE_SRT: &str = "test_files/test_speech.srt";
+
+#[tokio::test]
+async fn test_reference_files_agree() {
+    let text = output::load_transcription(Path::new("test_files/test_speech.txt")).await.unwrap();
+    let srt = output::load_transcription(Path::new(REFERENCE_SRT)).await.unwrap();
+
+    let evaluation = eval::evaluate(&srt, &text, &NormalizeOptions::default());
+
+    assert_eq!(evaluation.reference_words, 44);
+    assert_eq!(evaluation.wer, 0.0);
+    assert_eq!(evaluation.cer, 0.0);
+    // Plain text has no timestamps to compare
+    assert!(evaluation.timing.is_none());
+}
+
+#[tokio::test]
+async fn test_score_transcription_against_srt() {
+    let dir = tempfile::tempdir().unwrap();
+    let model_path = common::tiny_checkpoint(&dir.path().join("tiny"), 80);
+    let transcriber = Transcriber::builder().model_path(&model_path).build().unwrap();
+
+    let hypothesis = transcriber.transcribe(AudioSource::Wav(TEST_WAV.into())).await.unwrap();
+    let reference = output::load_transcription(Path::new(REFERENCE_SRT)).await.unwrap();
+    let evaluation = eval::evaluate(&reference, &hypothesis, &NormalizeOptions::default());
+
+    let errors = evaluation.substitutions + evaluation.insertions + evaluation.deletions;
+    assert!((evaluation.wer - errors as f64 / 44.0).abs() < 1e-9);
+    // Both sides are timed, so matched words give a timing deviation
+    assert!(evaluation.timing.is_some());
+    assert!(eval::format_report(&evaluation).contains("Timing: mean offset"));
+}
@@ -0,0 +1,341 @@
+//! Golden-file regression tests for the full pipeline: every recording in
+//! `test_files/` is run through extract_audio → transcribe → save_transcription
+//! and each output format is compared with `tests/golden/<suite>/<stem>.<format>`.
+//!
+//! Recordings sharing a stem (`test_speech.wav`, `test_speech.aiff`) must match
+//! the same goldens, with timestamps and confidences allowed to drift slightly.
+//!
+//! Two suites run:
+//!
+//! - `synthetic` always runs, on the deterministic checkpoint of `tests/common`.
+//!   Its weights are random, so it pins the decoder and the writers rather
+//!   than accuracy.
+//! - `tiny`, with the `model-tests` feature, runs the real tiny model from
+//!   `WAYNE_TEST_MODEL` or the model store, and fails without it. Besides its
+//!   goldens, the transcript must stay within `MAX_TINY_WER` of the reference
+//!   transcript `test_files/<stem>.srt`.
+//!
+//! ```text
+//! cargo test --test golden                                     # compare
+//! cargo test --test golden -- --bless                          # regenerate goldens
+//! cargo test --test golden -- srt                              # only matching cases
+//! cargo test --features model-tests --test golden -- tiny/     # the real model
+//! ```
+//!
+//! Without FFmpeg, WAV files are read directly and other recordings are
+//! reported as skipped.
+
+mod common;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use wayne_transcriber::output::{self, OutputFormat};
+use wayne_transcriber::{AudioSource, TranscribeError, Transcriber, TranscriptionResult};
+
+const TEST_FILES: &str = "test_files";
+const GOLDEN_DIR: &str = "tests/golden";
+
+/// Word error rate the real tiny model may reach against the reference transcripts
+#[cfg(feature = "model-tests")]
+const MAX_TINY_WER: f64 = 0.35;
+
+/// Seconds a timestamp may move before a segment counts as changed
+const DEFAULT_TIMING_TOLERANCE: f64 = 0.1;
+const CONFIDENCE_TOLERANCE: f32 = 0.05;
+
+const FORMATS: &[(&str, OutputFormat)] = &[
+    ("txt", OutputFormat::Text),
+    ("srt", OutputFormat::Srt),
+    ("vtt", OutputFormat::Vtt),
+    ("json", OutputFormat::Json),
+    ("ttml", OutputFormat::Ttml),
+];
+const TRANSCRIPT_EXTENSIONS: &[&str] = &["txt", "srt", "vtt", "json", "ttml"];
+
+struct Args {
+    bless: bool,
+    filters: Vec<String>,
+}
+
+impl Args {
+    /// `--bless` plus name filters; other libtest flags such as `--nocapture` are ignored
+    fn parse() -> Self {
+        let mut args = Args {
+            bless: std::env::var_os("BLESS").is_some(),
+            filters: Vec::new(),
+        };
+        for arg in std::env::args().skip(1) {
+            match arg.as_str() {
+                "--bless" => args.bless = true,
+                flag if flag.starts_with('-') => {}
+                filter => args.filters.push(filter.to_string()),
+            }
+        }
+        args
+    }
+
+    fn selects(&self, name: &str) -> bool {
+        self.filters.is_empty() || self.filters.iter().any(|filter| name.contains(filter.as_str()))
+    }
+}
+
+/// A model and the goldens written from it
+struct Suite {
+    name: &'static str,
+    transcriber: Result<Transcriber, String>,
+    /// Whether transcripts are also scored against the reference transcripts
+    check_accuracy: bool,
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let tolerance = std::env::var("GOLDEN_TIMING_TOLERANCE")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_TIMING_TOLERANCE);
+
+    let model_dir = tempfile::tempdir().unwrap();
+    let checkpoint = common::tiny_checkpoint(&model_dir.path().join("tiny"), 80);
+    let suites = [
+        Suite {
+            name: "synthetic",
+            transcriber: Transcriber::builder().model_path(&checkpoint).build().map_err(|e| e.to_string()),
+            check_accuracy: false,
+        },
+        #[cfg(feature = "model-tests")]
+        Suite {
+            name: "tiny",
+            transcriber: tiny_transcriber(),
+            check_accuracy: true,
+        },
+    ];
+
+    let output_dir = tempfile::tempdir().unwrap();
+    let mut totals = Totals::default();
+    for suite in &suites {
+        run_suite(suite, &args, tolerance, output_dir.path(), &mut totals).await;
+    }
+
+    println!(
+        "\ngolden result: {} passed; {} failed; {} skipped",
+        totals.passed, totals.failed, totals.skipped
+    );
+    if totals.failed > 0 { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+/// The real tiny model, from `WAYNE_TEST_MODEL` or the model store
+#[cfg(feature = "model-tests")]
+fn tiny_transcriber() -> Result<Transcriber, String> {
+    use wayne_transcriber::models::ModelStore;
+
+    let model_path = match std::env::var_os("WAYNE_TEST_MODEL") {
+        Some(path) => PathBuf::from(path),
+        None => match ModelStore::open_default().and_then(|store| store.model_path("tiny")) {
+            Ok(path) | Err(TranscribeError::ModelNotFound(path)) => path,
+            Err(e) => return Err(format!("failed to open the model store: {}", e)),
+        },
+    };
+    if !model_path.exists() {
+        return Err(format!(
+            "no tiny model at {:?}; set WAYNE_TEST_MODEL or run `models download tiny`",
+            model_path
+        ));
+    }
+    Transcriber::builder().model_path(&model_path).build().map_err(|e| e.to_string())
+}
+
+#[derive(Default)]
+struct Totals {
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+}
+
+async fn run_suite(suite: &Suite, args: &Args, tolerance: f64, output_dir: &Path, totals: &mut Totals) {
+    let golden_dir = Path::new(GOLDEN_DIR).join(suite.name);
+    let mut blessed = HashSet::new();
+
+    for recording in recordings() {
+        let file_name = recording.file_name().unwrap().to_string_lossy().into_owned();
+        let mut cases: Vec<_> = FORMATS
+            .iter()
+            .map(|(extension, format)| (format!("{}/{}.{}", suite.name, file_name, extension), *extension, Some(format)))
+            .collect();
+        if suite.check_accuracy {
+            cases.push((format!("{}/{}.wer", suite.name, file_name), "wer", None));
+        }
+        cases.retain(|(name, _, _)| args.selects(name));
+        if cases.is_empty() {
+            continue;
+        }
+
+        let transcriber = match &suite.transcriber {
+            Ok(transcriber) => transcriber,
+            Err(e) => {
+                for (name, _, _) in &cases {
+                    println!("test {} ... FAILED\n    {}", name, e);
+                }
+                totals.failed += cases.len();
+                continue;
+            }
+        };
+        let transcription = match transcribe(transcriber, &recording).await {
+            Ok(Some(transcription)) => transcription,
+            Ok(None) => {
+                for (name, _, _) in &cases {
+                    println!("test {} ... skipped (FFmpeg is not installed)", name);
+                }
+                totals.skipped += cases.len();
+                continue;
+            }
+            Err(e) => {
+                for (name, _, _) in &cases {
+                    println!("test {} ... FAILED\n    transcription failed: {}", name, e);
+                }
+                totals.failed += cases.len();
+                continue;
+            }
+        };
+
+        let stem = recording.file_stem().unwrap().to_string_lossy().into_owned();
+        for (name, extension, format) in cases {
+            let result = match format {
+                Some(format) => {
+                    let actual_path = output_dir.join(name.replace('/', "-"));
+                    output::save_transcription(&transcription, &actual_path, extension).await.unwrap();
+                    let actual = normalize(&std::fs::read_to_string(&actual_path).unwrap());
+                    let golden_path = golden_dir.join(format!("{}.{}", stem, extension));
+
+                    // The first recording of a stem writes the golden; the others are checked against it
+                    if args.bless && blessed.insert(golden_path.clone()) {
+                        std::fs::create_dir_all(&golden_dir).unwrap();
+                        std::fs::write(&golden_path, &actual).unwrap();
+                        println!("test {} ... blessed {:?}", name, golden_path);
+                        totals.passed += 1;
+                        continue;
+                    }
+                    match std::fs::read_to_string(&golden_path) {
+                        Ok(golden) => compare(&golden, &actual, format, tolerance),
+                        Err(_) => Err(format!("missing golden {:?}; run with --bless", golden_path)),
+                    }
+                }
+                None => check_accuracy(&transcription, &stem),
+            };
+            match result {
+                Ok(()) => {
+                    println!("test {} ... ok", name);
+                    totals.passed += 1;
+                }
+                Err(reason) => {
+                    println!("test {} ... FAILED\n    {}", name, reason.replace('\n', "\n    "));
+                    totals.failed += 1;
+                }
+            }
+        }
+    }
+}
+
+/// Word error rate against the reference transcript of the recording
+#[cfg(feature = "model-tests")]
+fn check_accuracy(transcription: &TranscriptionResult, stem: &str) -> Result<(), String> {
+    use wayne_transcriber::eval::{self, NormalizeOptions};
+
+    let reference_path = Path::new(TEST_FILES).join(format!("{}.srt", stem));
+    let content = std::fs::read_to_string(&reference_path)
+        .map_err(|e| format!("no reference transcript {:?}: {}", reference_path, e))?;
+    let reference = output::parse_transcription(&content, &OutputFormat::Srt).map_err(|e| e.to_string())?;
+    let evaluation = eval::evaluate(&reference, transcription, &NormalizeOptions::default());
+    if evaluation.wer > MAX_TINY_WER {
+        return Err(format!("WER {:.1}% exceeds {:.0}%", evaluation.wer * 100.0, MAX_TINY_WER * 100.0));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "model-tests"))]
+fn check_accuracy(_: &TranscriptionResult, _: &str) -> Result<(), String> {
+    unreachable!("only the model-tests suite checks accuracy")
+}
+
+/// Media files in `test_files/`, skipping the reference transcripts
+fn recordings() -> Vec<PathBuf> {
+    let mut recordings: Vec<PathBuf> = std::fs::read_dir(TEST_FILES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|extension| !TRANSCRIPT_EXTENSIONS.contains(&extension))
+        })
+        .collect();
+    // WAV first, so blessing without FFmpeg still covers every stem
+    recordings.sort_by_key(|path| (path.extension().is_none_or(|e| e != "wav"), path.clone()));
+    recordings
+}
+
+/// Runs the pipeline through FFmpeg, reading WAV files directly when FFmpeg is
+/// missing; `None` when the recording cannot be read without FFmpeg
+async fn transcribe(transcriber: &Transcriber, recording: &Path) -> Result<Option<TranscriptionResult>, TranscribeError> {
+    match transcriber.transcribe(AudioSource::File(recording.to_path_buf())).await {
+        Err(TranscribeError::FfmpegNotInstalled) if recording.extension().is_some_and(|e| e == "wav") => {
+            transcriber.transcribe(AudioSource::Wav(recording.to_path_buf())).await.map(Some)
+        }
+        Err(TranscribeError::FfmpegNotInstalled) => Ok(None),
+        result => result.map(Some),
+    }
+}
+
+/// Masks the parts of an output that change from run to run
+fn normalize(content: &str) -> String {
+    content
+        .lines()
+        .map(|line| match line.strip_prefix("Transcribed on: ") {
+            Some(_) => "Transcribed on: <DATE>",
+            None => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// Plain text must match exactly; timed formats may drift within `tolerance`
+fn compare(golden: &str, actual: &str, format: &OutputFormat, tolerance: f64) -> Result<(), String> {
+    if *format == OutputFormat::Text {
+        return if golden == actual {
+            Ok(())
+        } else {
+            Err(format!("text differs\n--- golden\n{}\n+++ actual\n{}", golden, actual))
+        };
+    }
+
+    let golden = output::parse_transcription(golden, format).map_err(|e| format!("unreadable golden: {}", e))?;
+    let actual = output::parse_transcription(actual, format).map_err(|e| format!("unreadable output: {}", e))?;
+    if golden.segments.len() != actual.segments.len() {
+        return Err(format!(
+            "expected {} segments, got {}",
+            golden.segments.len(),
+            actual.segments.len()
+        ));
+    }
+
+    for (index, (expected, got)) in golden.segments.iter().zip(&actual.segments).enumerate() {
+        let segment = index + 1;
+        if expected.text != got.text || expected.speaker != got.speaker {
+            return Err(format!("segment {}: expected {:?}, got {:?}", segment, expected.text, got.text));
+        }
+        if (expected.start_time - got.start_time).abs() > tolerance || (expected.end_time - got.end_time).abs() > tolerance {
+            return Err(format!(
+                "segment {}: expected {:.3}–{:.3}s, got {:.3}–{:.3}s (tolerance {}s)",
+                segment, expected.start_time, expected.end_time, got.start_time, got.end_time, tolerance
+            ));
+        }
+        if (expected.confidence - got.confidence).abs() > CONFIDENCE_TOLERANCE || expected.flags != got.flags {
+            return Err(format!(
+                "segment {}: expected confidence {:.2} {:?}, got {:.2} {:?}",
+                segment, expected.confidence, expected.flags, got.confidence, got.flags
+            ));
+        }
+    }
+    Ok(())
+}
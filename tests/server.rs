@@ -0,0 +1,344 @@
+mod common;
+
+use axum::body::{to_bytes, Body};
+use axum::http::{header, Request, StatusCode};
+use axum::Router;
+use std::time::Duration;
+use tempfile::TempDir;
+use tower::ServiceExt;
+use wayne_transcriber::server::{router, ServerOptions, ServerState};
+use wayne_transcriber::Transcriber;
+
+const TEST_WAV: &str = "test_files/test_speech.wav";
+
+fn test_router(options: ServerOptions) -> (TempDir, Router) {
+    let dir = tempfile::tempdir().unwrap();
+    let model_path = common::tiny_checkpoint(&dir.path().join("tiny"), 80);
+    let transcriber = Transcriber::builder().model_path(&model_path).build().unwrap();
+    (dir, router(ServerState::new(transcriber, options)))
+}
+
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, Option<String>, String) {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .map(|value| value.to_str().unwrap().to_string());
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, content_type, String::from_utf8(body.to_vec()).unwrap())
+}
+
+fn upload(uri: &str) -> Request<Body> {
+    let media = std::fs::read(TEST_WAV).unwrap();
+    Request::post(uri).body(Body::from(media)).unwrap()
+}
+
+#[tokio::test]
+async fn test_health() {
+    let (_dir, app) = test_router(ServerOptions::default());
+
+    let (status, _, body) = send(&app, Request::get("/health").body(Body::empty()).unwrap()).await;
+    let health: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(health["status"], "ok");
+    assert_eq!(health["queued"], 0);
+}
+
+#[tokio::test]
+async fn test_transcribe_upload_as_srt() {
+    let (_dir, app) = test_router(ServerOptions::default());
+
+    let (status, content_type, body) = send(&app, upload("/transcribe?format=srt")).await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("application/x-subrip"));
+    assert!(body.starts_with("1\n00:00:0"));
+}
+
+#[tokio::test]
+async fn test_rejects_bad_requests() {
+    let (_dir, app) = test_router(ServerOptions::default());
+
+    let (status, _, _) = send(&app, upload("/transcribe?format=docx")).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _, _) = send(&app, Request::post("/transcribe").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_full_queue_is_rejected() {
+    let (_dir, app) = test_router(ServerOptions {
+        max_queue: 0,
+        ..ServerOptions::default()
+    });
+
+    let (status, _, _) = send(&app, upload("/transcribe")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+}
+
+#[tokio::test]
+async fn test_job_queue() {
+    let (_dir, app) = test_router(ServerOptions::default());
+
+    let (status, _, body) = send(&app, upload("/jobs?format=json")).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let id = serde_json::from_str::<serde_json::Value>(&body).unwrap()["id"].as_u64().unwrap();
+
+    let mut job_status = String::new();
+    for _ in 0..100 {
+        let (_, _, body) = send(&app, Request::get(format!("/jobs/{}", id)).body(Body::empty()).unwrap()).await;
+        job_status = serde_json::from_str::<serde_json::Value>(&body).unwrap()["status"]
+            .as_str()
+            .unwrap()
+            .to_string();
+        if job_status == "completed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert_eq!(job_status, "completed");
+
+    let (status, content_type, body) =
+        send(&app, Request::get(format!("/jobs/{}/result", id)).body(Body::empty()).unwrap()).await;
+    let transcription: serde_json::Value = serde_json::from_str(&body).unwrap();
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("application/json"));
+    assert!(!transcription["segments"].as_array().unwrap().is_empty());
+
+    let (status, _, _) = send(&app, Request::get("/jobs/9999").body(Body::empty()).unwrap()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_abandoned_request_leaves_the_queue() {
+    let (_dir, app) = test_router(ServerOptions::default());
+    let health = |app: Router| async move {
+        let (_, _, body) = send(&app, Request::get("/health").body(Body::empty()).unwrap()).await;
+        serde_json::from_str::<serde_json::Value>(&body).unwrap()
+    };
+
+    // A job takes the only slot...
+    let (status, _, _) = send(&app, upload("/jobs")).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let mut running = false;
+    for _ in 0..100 {
+        if health(app.clone()).await["running"] == 1 {
+            running = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(running);
+
+    // ...so the next request queues, and its client gives up while waiting
+    let waiting = tokio::time::timeout(Duration::from_millis(50), send(&app, upload("/transcribe"))).await;
+    assert!(waiting.is_err());
+    let health = health(app.clone()).await;
+    assert_eq!(health["running"], 1);
+    assert_eq!(health["queued"], 0);
+}
+
+/// Builds an OpenAI-style multipart request with the test WAV as `file`
+fn openai_upload(uri: &str, fields: &[(&str, &str)]) -> Request<Body> {
+    const BOUNDARY: &str = "wayne-test-boundary";
+    let mut body = Vec::new();
+    for (name, value) in fields {
+        body.extend_from_slice(
+            format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", BOUNDARY, name, value)
+                .as_bytes(),
+        );
+    }
+    body.extend_from_slice(
+        format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"test_speech.wav\"\r\n\
+             Content-Type: audio/wav\r\n\r\n",
+            BOUNDARY
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&std::fs::read(TEST_WAV).unwrap());
+    body.extend_from_slice(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+    Request::post(uri)
+        .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", BOUNDARY))
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_openai_transcriptions() {
+    let (_dir, app) = test_router(ServerOptions::default());
+
+    let (status, _, body) = send(&app, openai_upload("/v1/audio/transcriptions", &[("model", "whisper-1")])).await;
+    let transcription: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert!(!transcription["text"].as_str().unwrap().is_empty());
+
+    let fields = [
+        ("model", "whisper-1"),
+        ("language", "en"),
+        ("prompt", "A lecture by Wayne Dyer."),
+        ("response_format", "verbose_json"),
+        ("temperature", "0.2"),
+        ("timestamp_granularities[]", "word"),
+        ("timestamp_granularities[]", "segment"),
+    ];
+    let (status, _, body) = send(&app, openai_upload("/v1/audio/transcriptions", &fields)).await;
+    let verbose: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(verbose["task"], "transcribe");
+    assert_eq!(verbose["language"], "english");
+    assert!(verbose["duration"].as_f64().unwrap() > 0.0);
+    assert!(verbose["segments"][0]["end"].as_f64().unwrap() > 0.0);
+    assert_eq!(verbose["segments"][0]["temperature"], 0.2);
+    let words = verbose["words"].as_array().unwrap();
+    assert!(!words.is_empty());
+    assert!(words.iter().all(|word| word["end"].as_f64() > word["start"].as_f64()));
+
+    // Without a language it is detected
+    let fields = [("model", "whisper-1"), ("response_format", "verbose_json")];
+    let (_, _, body) = send(&app, openai_upload("/v1/audio/transcriptions", &fields)).await;
+    let verbose: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(verbose["language"], "english");
+    assert!(verbose.get("words").is_none());
+
+    let fields = [("model", "whisper-1"), ("response_format", "vtt")];
+    let (status, content_type, body) = send(&app, openai_upload("/v1/audio/translations", &fields)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("text/vtt; charset=utf-8"));
+    assert!(body.starts_with("WEBVTT"));
+}
+
+#[tokio::test]
+async fn test_openai_errors() {
+    let (_dir, app) = test_router(ServerOptions::default());
+
+    let (status, _, body) = send(&app, openai_upload("/v1/audio/transcriptions", &[])).await;
+    let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"]["param"], "model");
+    assert_eq!(error["error"]["type"], "invalid_request_error");
+
+    let fields = [("model", "whisper-1"), ("response_format", "docx")];
+    let (status, _, _) = send(&app, openai_upload("/v1/audio/transcriptions", &fields)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let fields = [("model", "whisper-1"), ("temperature", "1.5")];
+    let (status, _, body) = send(&app, openai_upload("/v1/audio/transcriptions", &fields)).await;
+    let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["error"]["param"], "temperature");
+
+    for language in ["xx", "english", ""] {
+        let fields = [("model", "whisper-1"), ("language", language)];
+        let (status, _, body) = send(&app, openai_upload("/v1/audio/transcriptions", &fields)).await;
+        let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{}", language);
+        assert_eq!(error["error"]["param"], "language");
+        assert_eq!(error["error"]["type"], "invalid_request_error");
+    }
+}
+
+#[tokio::test]
+async fn test_websocket_captions() {
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite::Message;
+
+    let (_dir, app) = test_router(ServerOptions::default());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let url = format!("ws://{}/stream?partial_interval=0.5", addr);
+    let (mut socket, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+
+    // Replay the WAV (header included) as fast as possible in 100ms frames
+    let wav = std::fs::read(TEST_WAV).unwrap();
+    for frame in wav.chunks(3200) {
+        socket.send(Message::Binary(frame.to_vec().into())).await.unwrap();
+    }
+    socket.send(Message::Text(r#"{"type":"end"}"#.into())).await.unwrap();
+
+    let mut messages = Vec::new();
+    while let Some(message) = socket.next().await {
+        match message.unwrap() {
+            Message::Text(text) => messages.push(serde_json::from_str::<serde_json::Value>(&text).unwrap()),
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    let last = messages.last().unwrap();
+    assert_eq!(last["type"], "final");
+    assert!(messages.iter().any(|message| message["type"] == "partial"));
+    // Partial captions carry the id of the final caption that replaces them
+    for message in &messages {
+        assert!(message["id"].as_u64().unwrap() <= last["id"].as_u64().unwrap());
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_caption_sessions_take_a_slot() {
+    use futures_util::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let options = ServerOptions {
+        max_queue: 1,
+        ..ServerOptions::default()
+    };
+    let (_dir, app) = test_router(options);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+    let wait_for = |app: Router, running: u64, queued: u64| async move {
+        for _ in 0..100 {
+            let (_, _, body) = send(&app, Request::get("/health").body(Body::empty()).unwrap()).await;
+            let health: serde_json::Value = serde_json::from_str(&body).unwrap();
+            if health["running"] == running && health["queued"] == queued {
+                return true;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        false
+    };
+
+    // An open session holds the only slot, so the next one waits in the queue...
+    let url = format!("ws://{}/stream", addr);
+    let (mut first, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+    assert!(wait_for(app.clone(), 1, 0).await);
+    let (second, _) = tokio_tungstenite::connect_async(url.as_str()).await.unwrap();
+    assert!(wait_for(app.clone(), 1, 1).await);
+
+    // ...and with the queue full, uploads are turned away
+    let (status, _, _) = send(&app, upload("/transcribe")).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+
+    // Ending the first session hands its slot to the second
+    first.send(Message::Text(r#"{"type":"end"}"#.into())).await.unwrap();
+    assert!(wait_for(app.clone(), 1, 0).await);
+    drop(second);
+    assert!(wait_for(app.clone(), 0, 0).await);
+}
+
+#[tokio::test]
+async fn test_caption_options_are_validated() {
+    use tokio_tungstenite::tungstenite::Error;
+
+    let (_dir, app) = test_router(ServerOptions::default());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    for query in ["commit_silence=0", "partial_interval=-1", "max_segment=NaN", "max_segment=inf"] {
+        let url = format!("ws://{}/stream?{}", addr, query);
+        match tokio_tungstenite::connect_async(url.as_str()).await {
+            Err(Error::Http(response)) => assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", query),
+            other => panic!("{} was accepted: {:?}", query, other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tokio::io::AsyncWriteExt;
+use wayne_transcriber::streaming::{stream_pcm, StreamEvent, StreamOptions, StreamSession};
+use wayne_transcriber::Transcriber;
+
+const TEST_WAV: &str = "test_files/test_speech.wav";
+
+fn tiny_transcriber() -> (TempDir, Transcriber) {
+    let dir = tempfile::tempdir().unwrap();
+    let model_path = common::tiny_checkpoint(&dir.path().join("tiny"), 80);
+    let transcriber = Transcriber::builder().model_path(&model_path).build().unwrap();
+    (dir, transcriber)
+}
+
+/// 100ms of 16kHz 16-bit PCM
+const CHUNK: usize = 3200;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_stream_wav_produces_partial_and_final_segments() {
+    let (_dir, transcriber) = tiny_transcriber();
+    let mut session = StreamSession::new(Arc::new(transcriber), StreamOptions::default());
+    let wav = tokio::fs::read(TEST_WAV).await.unwrap();
+
+    // Feed the file as a live source would: 100ms of audio every 100ms
+    let (mut writer, reader) = tokio::io::duplex(CHUNK * 4);
+    let started = Instant::now();
+    let feeder = tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(100));
+        for chunk in wav.chunks(CHUNK) {
+            interval.tick().await;
+            writer.write_all(chunk).await.unwrap();
+        }
+        Instant::now()
+    });
+
+    let mut events = Vec::new();
+    stream_pcm(&mut session, reader, |event| {
+        events.push((started.elapsed(), event.clone()));
+        Ok(())
+    })
+    .await
+    .unwrap();
+    let fed = feeder.await.unwrap().duration_since(started);
+
+    // Results arrive while audio is still coming in, partials ahead of the final they lead to
+    let first_partial = events.iter().position(|(_, event)| matches!(event, StreamEvent::Partial(_))).unwrap();
+    let first_final = events.iter().position(|(_, event)| matches!(event, StreamEvent::Final(_))).unwrap();
+    assert!(first_partial < first_final);
+    assert!(events[first_partial].0 < fed);
+    for (index, (_, event)) in events.iter().enumerate() {
+        if let StreamEvent::Partial(partial) = event {
+            assert!(events[index..]
+                .iter()
+                .any(|(_, later)| matches!(later, StreamEvent::Final(segment) if segment.id == partial.id)));
+        }
+    }
+    let events: Vec<StreamEvent> = events.into_iter().map(|(_, event)| event).collect();
+
+    let finals: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            StreamEvent::Final(segment) => Some(segment),
+            StreamEvent::Partial(_) => None,
+        })
+        .collect();
+    assert!(!finals.is_empty());
+    assert!(events.iter().any(|event| matches!(event, StreamEvent::Partial(_))));
+
+    // Final segments are numbered in order, never overlap and stay within the audio
+    for (index, segment) in finals.iter().enumerate() {
+        assert_eq!(segment.id, index as u64);
+        assert!(segment.start_time < segment.end_time);
+        assert!(segment.end_time <= 13.2);
+        if index > 0 {
+            assert!(segment.start_time >= finals[index - 1].end_time);
+        }
+    }
+
+    // Every partial is superseded by a final with the same id
+    let last_id = finals.last().unwrap().id;
+    for event in &events {
+        assert!(event.segment().id <= last_id);
+    }
+}
@@ -0,0 +1,181 @@
+mod common;
+
+use std::path::PathBuf;
+use tempfile::TempDir;
+use wayne_transcriber::output::{self, format_transcription};
+use wayne_transcriber::vocabulary::Vocabulary;
+use wayne_transcriber::{
+    AudioSource, DecodingOptions, DiarizationOptions, ModelSpec, OutputFormat, TranscribeError, Transcriber,
+    WhisperModel,
+};
+
+const TEST_WAV: &str = "test_files/test_speech.wav";
+
+fn tiny_model() -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = common::tiny_checkpoint(&dir.path().join("tiny"), 80);
+    (dir, path)
+}
+
+#[test]
+fn test_build_fails_without_model() {
+    let result = Transcriber::builder()
+        .model(WhisperModel::Tiny)
+        .model_path("does/not/exist.bin")
+        .build();
+
+    assert!(matches!(result.unwrap_err(), TranscribeError::ModelNotFound(_)));
+}
+
+#[tokio::test]
+async fn test_architecture_comes_from_the_checkpoint() {
+    // 128 mel bins, as from large-v3 on
+    let dir = tempfile::tempdir().unwrap();
+    let checkpoint = common::tiny_checkpoint(dir.path(), 128);
+
+    let transcriber = Transcriber::builder().model(ModelSpec::Path(checkpoint)).build().unwrap();
+    assert_eq!(transcriber.model_config().num_mel_bins, 128);
+    assert_eq!(transcriber.model_config().d_model, 64);
+    let transcription = transcriber.transcribe(AudioSource::Wav(TEST_WAV.into())).await.unwrap();
+    assert!(!transcription.full_text.is_empty());
+
+    // A file that is not a checkpoint, or one Candle cannot run, is rejected
+    let placeholder = dir.path().join("placeholder.bin");
+    std::fs::write(&placeholder, b"placeholder").unwrap();
+    let result = Transcriber::builder().model(ModelSpec::Path(placeholder)).build();
+    assert!(matches!(result.unwrap_err(), TranscribeError::UnsupportedFormat(_)));
+    let ggml = dir.path().join("ggml-tiny.bin");
+    let mut header = b"lmgg".to_vec();
+    for value in [51865i32, 1500, 384, 6, 4, 448, 384, 6, 4, 80, 1] {
+        header.extend_from_slice(&value.to_le_bytes());
+    }
+    std::fs::write(&ggml, header).unwrap();
+    let result = Transcriber::builder().model(WhisperModel::Tiny).model_path(ggml).build();
+    assert!(matches!(result.unwrap_err(), TranscribeError::UnsupportedFormat(_)));
+}
+
+#[tokio::test]
+async fn test_typed_input_errors() {
+    let (dir, model_path) = tiny_model();
+    let transcriber = Transcriber::builder().model_path(&model_path).build().unwrap();
+
+    let missing = transcriber.transcribe(AudioSource::Wav(dir.path().join("missing.wav"))).await;
+    assert!(matches!(missing.unwrap_err(), TranscribeError::MissingInput(_)));
+
+    let not_wav = transcriber.transcribe(AudioSource::Wav(model_path.join("config.json"))).await;
+    assert!(matches!(not_wav.unwrap_err(), TranscribeError::Decode(_)));
+
+    let format = "docx".parse::<OutputFormat>();
+    assert!(matches!(format.unwrap_err(), TranscribeError::UnsupportedFormat(_)));
+}
+
+#[tokio::test]
+async fn test_transcribe_wav() {
+    let (_dir, model_path) = tiny_model();
+    let transcriber = Transcriber::builder().model_path(&model_path).build().unwrap();
+
+    let transcription = transcriber.transcribe(AudioSource::Wav(TEST_WAV.into())).await.unwrap();
+
+    assert!(!transcription.segments.is_empty());
+    assert!(!transcription.full_text.is_empty());
+    for segment in &transcription.segments {
+        assert!(segment.start_time < segment.end_time);
+        assert!((0.0..=1.0).contains(&segment.confidence));
+        assert!(segment.speaker.is_none());
+
+        // Every word is timed within its segment, in order, and scored
+        let words: Vec<&str> = segment.words.iter().map(|word| word.word.as_str()).collect();
+        assert_eq!(words, segment.text.split_whitespace().collect::<Vec<_>>());
+        assert_eq!(segment.words[0].start_time, segment.start_time);
+        assert_eq!(segment.words.last().unwrap().end_time, segment.end_time);
+        for pair in segment.words.windows(2) {
+            assert!(pair[0].start_time < pair[1].start_time);
+            assert_eq!(pair[0].end_time, pair[1].start_time);
+        }
+        assert!(segment.words.iter().all(|word| word.avg_logprob.is_some_and(|logprob| logprob <= 0.0)));
+    }
+}
+
+#[tokio::test]
+async fn test_transcribe_samples_with_options() {
+    let (_dir, model_path) = tiny_model();
+    let samples = wayne_transcriber::load_source(AudioSource::Wav(TEST_WAV.into())).await.unwrap();
+    let plain = Transcriber::builder().model_path(&model_path).build().unwrap();
+    let first_word = plain.transcribe(AudioSource::Samples(samples.clone())).await.unwrap().segments[0]
+        .text
+        .split_whitespace()
+        .next()
+        .unwrap()
+        .to_string();
+
+    let transcriber = Transcriber::builder()
+        .model_path(&model_path)
+        .decoding_options(DecodingOptions {
+            vocabulary: Vocabulary::parse(&format!("{} => Replaced", first_word)),
+            ..Default::default()
+        })
+        .diarization(DiarizationOptions::default())
+        .build()
+        .unwrap();
+    let transcription = transcriber.transcribe(AudioSource::Samples(samples)).await.unwrap();
+
+    assert!(transcription.segments[0].text.starts_with("Replaced"));
+    assert!(transcription.segments.iter().any(|segment| segment.speaker.is_some()));
+}
+
+#[tokio::test]
+async fn test_prompt_and_vocabulary_bias_steer_decoding() {
+    let (_dir, model_path) = tiny_model();
+    let samples = wayne_transcriber::load_source(AudioSource::Wav(TEST_WAV.into())).await.unwrap();
+    let plain = Transcriber::builder().model_path(&model_path).build().unwrap();
+    let plain_text = plain.transcribe(AudioSource::Samples(samples.clone())).await.unwrap().full_text;
+    let term = ["intention", "reality", "thoughts", "remember", "explore"]
+        .into_iter()
+        .find(|word| !plain_text.split_whitespace().any(|decoded| decoded == *word))
+        .unwrap();
+
+    let biased = |initial_prompt: &str, logit_bias: f32| {
+        Transcriber::builder()
+            .model_path(&model_path)
+            .decoding_options(DecodingOptions {
+                initial_prompt: Some(initial_prompt.to_string()),
+                logit_bias,
+                vocabulary: Vocabulary::parse(term),
+                ..Default::default()
+            })
+            .build()
+            .unwrap()
+    };
+    let unbiased = biased("", 0.0).transcribe(AudioSource::Samples(samples.clone())).await.unwrap().full_text;
+    assert_eq!(unbiased, plain_text);
+    // The prompt is context the decoder continues from
+    let prompted = biased("today we will explore", 0.0);
+    let prompted = prompted.transcribe(AudioSource::Samples(samples.clone())).await.unwrap().full_text;
+    assert_ne!(prompted, plain_text);
+    let steered = biased("", 10.0).transcribe(AudioSource::Samples(samples)).await.unwrap().full_text;
+    assert_ne!(steered, plain_text);
+    assert!(steered.split_whitespace().any(|decoded| decoded == term));
+}
+
+#[tokio::test]
+async fn test_formatters() {
+    let (dir, model_path) = tiny_model();
+    let transcriber = Transcriber::builder().model_path(&model_path).build().unwrap();
+    let transcription = transcriber.transcribe(AudioSource::Wav(TEST_WAV.into())).await.unwrap();
+
+    let srt = format_transcription(&transcription, &OutputFormat::Srt).unwrap();
+    assert!(srt.starts_with("1\n00:00:0"));
+    assert!(srt.lines().nth(1).unwrap().contains(" --> "));
+
+    let vtt = format_transcription(&transcription, &OutputFormat::Vtt).unwrap();
+    assert!(vtt.starts_with("WEBVTT\n\n00:00:0"));
+
+    let json = format_transcription(&transcription, &OutputFormat::Json).unwrap();
+    let parsed: wayne_transcriber::TranscriptionResult = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.segments.len(), transcription.segments.len());
+
+    let output_path = dir.path().join("transcript.txt");
+    output::save_transcription(&transcription, &output_path, "txt").await.unwrap();
+    let text = std::fs::read_to_string(&output_path).unwrap();
+    assert!(text.contains(&transcription.full_text));
+}
@@ -0,0 +1,71 @@
+use axum::routing::get;
+use axum::Router;
+use std::net::SocketAddr;
+use std::time::Duration;
+use wayne_transcriber::{load_source, AudioSource, NetworkOptions, TranscribeError};
+
+const TEST_WAV: &str = "test_files/test_speech.wav";
+
+/// Serves the test WAV at `/media/test_speech.wav`, plus an endpoint that never answers
+async fn media_server() -> SocketAddr {
+    let wav = std::fs::read(TEST_WAV).unwrap();
+    let app = Router::new()
+        .route("/media/test_speech.wav", get(move || async move { wav }))
+        .route("/stalled.wav", get(std::future::pending::<&'static str>));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+fn url_source(url: String, network: NetworkOptions) -> AudioSource {
+    AudioSource::Url { url, network }
+}
+
+#[tokio::test]
+#[ignore = "needs ffmpeg"]
+async fn test_load_http_url() {
+    let addr = media_server().await;
+    let url = format!("http://{}/media/test_speech.wav?token=abc", addr);
+
+    let samples = load_source(url_source(url, NetworkOptions::default())).await.unwrap();
+    assert!((samples.len() as i64 - 210_034).abs() < 1600);
+}
+
+#[tokio::test]
+#[ignore = "needs ffmpeg"]
+async fn test_http_errors_are_retryable_ffmpeg_failures() {
+    let addr = media_server().await;
+    let network = NetworkOptions {
+        reconnect: false,
+        ..Default::default()
+    };
+
+    let error = load_source(url_source(format!("http://{}/missing.wav", addr), network)).await.unwrap_err();
+    assert!(matches!(error, TranscribeError::FfmpegFailed { .. }));
+    assert!(error.is_retryable());
+}
+
+#[tokio::test]
+#[ignore = "needs ffmpeg"]
+async fn test_stalled_url_times_out() {
+    let addr = media_server().await;
+    let network = NetworkOptions {
+        reconnect: false,
+        timeout: Some(Duration::from_secs(1)),
+        ..Default::default()
+    };
+
+    let started = std::time::Instant::now();
+    let result = load_source(url_source(format!("http://{}/stalled.wav", addr), network)).await;
+    assert!(matches!(result, Err(TranscribeError::FfmpegFailed { .. })));
+    assert!(started.elapsed() < Duration::from_secs(20));
+}
+
+#[tokio::test]
+async fn test_urls_are_not_checked_as_local_paths() {
+    let result = wayne_transcriber::audio::extract_audio("https://127.0.0.1:9/talk.mp4".as_ref()).await;
+
+    assert!(!matches!(result, Err(TranscribeError::MissingInput(_))));
+}